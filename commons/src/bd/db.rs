@@ -1,17 +1,26 @@
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
+    io::{BufReader, Read, Write},
+    num::NonZeroUsize,
     path::Path,
     str::FromStr,
+    sync::{Arc, Mutex},
 };
 
+use chrono::Utc;
+use leveldb::batch::Writebatch;
+use lru::LruCache;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+
 use crate::{
-    errors::SubjectError,
-    identifier::{Derivable, DigestIdentifier},
+    errors::{AuditError, ChainError, ConflictError, DbError, SubjectError},
+    identifier::{derive::digest::DigestDerivator, Derivable, DigestIdentifier, KeyIdentifier},
     models::{
         event::Event,
         event_content::EventContent,
-        event_request::EventRequest,
-        signature::Signature,
+        event_request::{EventRequest, EventRequestType},
+        signature::{Signature, SignatureSet},
         state::{LedgerState, Subject},
     },
 };
@@ -19,6 +28,7 @@ use crate::{
 use super::{
     level_db::{
         error::WrapperLevelDBErrors,
+        metrics::{Metrics, TableMetrics},
         wrapper_leveldb::{CursorIndex, StringKey, WrapperLevelDB},
     },
     TapleDB,
@@ -27,285 +37,1746 @@ use super::{
 const SIGNATURE_TABLE: &str = "signature";
 const SUBJECT_TABLE: &str = "subject";
 const EVENT_TABLE: &str = "event";
+const EVENT_HASH_INDEX_TABLE: &str = "event-hash-index";
 const REQUEST_TABLE: &str = "request";
 const ID_TABLE: &str = "controller-id";
+const CONTROLLER_HISTORY_TABLE: &str = "controller-history";
+const NAMESPACE_INDEX_TABLE: &str = "namespace-index";
+const BLOB_TABLE: &str = "blob";
+const APPROVAL_TIME_TABLE: &str = "approval-time";
+const SYNC_MARKER_TABLE: &str = "sync-marker";
+
+/// First bytes of every dump written by `DB::export`
+const DUMP_MAGIC: &[u8; 8] = b"TAPLEDB\0";
+/// Version of the format of the dump. Bump it when the layout of the entries changes
+const DUMP_VERSION: u8 = 1;
+/// Key of the sync marker table written once every stored subject is in the
+/// namespace index
+const NAMESPACE_INDEX_MARKER: &str = "namespace-index-complete";
+/// Partition of the sync marker table with the last SN pruned of each subject
+const PRUNED_MARKER: &str = "pruned";
 
 pub struct DB {
     signature_db: WrapperLevelDB<StringKey, HashSet<Signature>>,
     subject_db: WrapperLevelDB<StringKey, Subject>,
     event_db: WrapperLevelDB<StringKey, Event>,
+    /// Subject and SN of each event, by its content hash
+    event_hash_index_db: WrapperLevelDB<StringKey, (DigestIdentifier, u64)>,
     request_db: WrapperLevelDB<StringKey, EventRequest>,
     id_db: WrapperLevelDB<StringKey, String>,
+    controller_history_db: WrapperLevelDB<StringKey, String>,
+    namespace_index_db: WrapperLevelDB<StringKey, String>,
+    blob_db: WrapperLevelDB<StringKey, Vec<u8>>,
+    /// Arrival time of each approval, by subject, SN and approver
+    approval_time_db: WrapperLevelDB<StringKey, i64>,
+    /// Time of the last `flush`
+    sync_marker_db: WrapperLevelDB<StringKey, i64>,
+    subject_cache: Option<Mutex<LruCache<DigestIdentifier, Subject>>>,
+    subject_locks: Mutex<HashMap<DigestIdentifier, Arc<Mutex<()>>>>,
+    oplog: Option<Mutex<Box<dyn Write + Send>>>,
+}
+
+/// Mutation recorded in the operation log of a `DB`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OpLogEntry {
+    /// Name of the `TapleDB` method
+    pub operation: String,
+    pub subject_id: Option<DigestIdentifier>,
+    pub sn: Option<u64>,
+    /// Milliseconds since the Unix epoch
+    pub timestamp: i64,
+}
+
+/// Page of the events of a subject returned by `DB::get_events_page`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventPage {
+    pub events: Vec<Event>,
+    /// Value of `from` that returns the following page, `None` if there are no more events
+    pub next_cursor: Option<String>,
+}
+
+/// A subject with its events and signatures, as returned by `DB::export_subject`.
+/// The subject keeps its keys, if the node owns it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubjectBundle {
+    pub subject: Subject,
+    /// Events in SN order
+    pub events: Vec<Event>,
+    pub signatures: BTreeMap<u64, HashSet<Signature>>,
+}
+
+/// Writes made through a `DB` while the session lives do not wait for LevelDB to
+/// fsync them. Committing the session, or dropping it, makes all of them durable
+/// at once. Returned by `DB::bulk_import_session`.
+pub struct BulkImportSession<'a> {
+    db: &'a DB,
+    finished: bool,
+}
+
+impl BulkImportSession<'_> {
+    /// Makes the writes of the session durable and restores synchronous writes.
+    pub fn commit(mut self) -> Result<(), DbError> {
+        self.finish()
+    }
+
+    fn finish(&mut self) -> Result<(), DbError> {
+        self.finished = true;
+        self.db.set_sync(true);
+        self.db.flush()
+    }
+}
+
+impl Drop for BulkImportSession<'_> {
+    fn drop(&mut self) {
+        if !self.finished {
+            if let Err(error) = self.finish() {
+                tracing::error!(%error, "Could not commit the bulk import session");
+            }
+        }
+    }
 }
 
 impl DB {
+    /// Subjects stored before the namespace index existed are indexed the first
+    /// time the database is opened, see `reindex_namespaces`.
     pub fn new(db: std::sync::Arc<leveldb::database::Database<StringKey>>) -> Self {
-        Self {
+        let db = Self {
             signature_db: WrapperLevelDB::<StringKey, HashSet<Signature>>::new(
                 db.clone(),
                 SIGNATURE_TABLE,
             ),
             subject_db: WrapperLevelDB::<StringKey, Subject>::new(db.clone(), SUBJECT_TABLE),
             event_db: WrapperLevelDB::<StringKey, Event>::new(db.clone(), EVENT_TABLE),
+            event_hash_index_db: WrapperLevelDB::<StringKey, (DigestIdentifier, u64)>::new(
+                db.clone(),
+                EVENT_HASH_INDEX_TABLE,
+            ),
             request_db: WrapperLevelDB::<StringKey, EventRequest>::new(db.clone(), REQUEST_TABLE),
             id_db: WrapperLevelDB::<StringKey, String>::new(db.clone(), ID_TABLE),
+            controller_history_db: WrapperLevelDB::<StringKey, String>::new(
+                db.clone(),
+                CONTROLLER_HISTORY_TABLE,
+            ),
+            namespace_index_db: WrapperLevelDB::<StringKey, String>::new(
+                db.clone(),
+                NAMESPACE_INDEX_TABLE,
+            ),
+            blob_db: WrapperLevelDB::<StringKey, Vec<u8>>::new(db.clone(), BLOB_TABLE),
+            approval_time_db: WrapperLevelDB::<StringKey, i64>::new(
+                db.clone(),
+                APPROVAL_TIME_TABLE,
+            ),
+            sync_marker_db: WrapperLevelDB::<StringKey, i64>::new(db.clone(), SYNC_MARKER_TABLE),
+            subject_cache: None,
+            subject_locks: Mutex::new(HashMap::new()),
+            oplog: None,
+        };
+        if !db.namespace_index_complete() {
+            // The scan of the subjects stands in for the index until it succeeds
+            if let Err(error) = db.reindex_namespaces() {
+                tracing::warn!(%error, "Could not index the subjects by namespace");
+            }
         }
+        db
     }
-}
 
-impl DB {
-    fn _get_subject(&self, subject_id: &DigestIdentifier) -> Result<Subject, WrapperLevelDBErrors> {
-        let id = subject_id.to_str();
-        self.subject_db.get(&id)
+    /// Appends an `OpLogEntry` to `writer`, as a line of JSON, after every successful
+    /// mutation made through the `TapleDB` methods of this instance. Meant to find
+    /// where the databases of two nodes diverge.
+    pub fn with_oplog<W: Write + Send + 'static>(mut self, writer: W) -> Self {
+        self.oplog = Some(Mutex::new(Box::new(writer)));
+        self
     }
-}
 
-impl TapleDB for DB {
-    fn get_controller_id(&self) -> Option<String> {
-        match self.id_db.get("") {
-            Ok(id) => Some(id),
-            Err(WrapperLevelDBErrors::EntryNotFoundError) => None,
-            Err(error) => {
-                panic!("Not recoverable error get_controller_id {:?}", error);
-            }
+    /// Counts the operations made on each table from now on, see `table_metrics`.
+    pub fn with_metrics(mut self) -> Self {
+        fn tracked<V: Serialize + DeserializeOwned>(
+            table: WrapperLevelDB<StringKey, V>,
+        ) -> WrapperLevelDB<StringKey, V> {
+            table.with_metrics(Arc::new(Metrics::default()))
+        }
+        self.signature_db = tracked(self.signature_db);
+        self.subject_db = tracked(self.subject_db);
+        self.event_db = tracked(self.event_db);
+        self.event_hash_index_db = tracked(self.event_hash_index_db);
+        self.request_db = tracked(self.request_db);
+        self.id_db = tracked(self.id_db);
+        self.controller_history_db = tracked(self.controller_history_db);
+        self.namespace_index_db = tracked(self.namespace_index_db);
+        self.blob_db = tracked(self.blob_db);
+        self.approval_time_db = tracked(self.approval_time_db);
+        self.sync_marker_db = tracked(self.sync_marker_db);
+        self
+    }
+
+    /// Operations made on each table, by table name. They are all zero unless
+    /// the `DB` was built `with_metrics`. Add them up for the totals of the `DB`.
+    pub fn table_metrics(&self) -> HashMap<&'static str, TableMetrics> {
+        HashMap::from([
+            (SIGNATURE_TABLE, self.signature_db.metrics_snapshot()),
+            (SUBJECT_TABLE, self.subject_db.metrics_snapshot()),
+            (EVENT_TABLE, self.event_db.metrics_snapshot()),
+            (
+                EVENT_HASH_INDEX_TABLE,
+                self.event_hash_index_db.metrics_snapshot(),
+            ),
+            (REQUEST_TABLE, self.request_db.metrics_snapshot()),
+            (ID_TABLE, self.id_db.metrics_snapshot()),
+            (
+                CONTROLLER_HISTORY_TABLE,
+                self.controller_history_db.metrics_snapshot(),
+            ),
+            (
+                NAMESPACE_INDEX_TABLE,
+                self.namespace_index_db.metrics_snapshot(),
+            ),
+            (BLOB_TABLE, self.blob_db.metrics_snapshot()),
+            (
+                APPROVAL_TIME_TABLE,
+                self.approval_time_db.metrics_snapshot(),
+            ),
+            (SYNC_MARKER_TABLE, self.sync_marker_db.metrics_snapshot()),
+        ])
+    }
+
+    /// Starts a session in which writes are not synced one by one, meant to catch
+    /// up with the events of other nodes. See `BulkImportSession`.
+    pub fn bulk_import_session(&self) -> BulkImportSession<'_> {
+        self.set_sync(false);
+        BulkImportSession {
+            db: self,
+            finished: false,
         }
     }
 
-    fn set_controller_id(&self, controller_id: String) {
-        if let Err(error) = self.id_db.put("", controller_id) {
-            panic!("Error while inserting controller_id. Error --> {}", error);
+    /// Makes every write made so far durable, even the ones that were not synced.
+    pub fn flush(&self) -> Result<(), DbError> {
+        // LevelDB appends every write to the same log, so syncing this one
+        // also syncs the ones made before it
+        self.sync_marker_db
+            .put("last-flush", Utc::now().timestamp_millis())?;
+        Ok(())
+    }
+
+    /// Rewrites with the format tag the values stored before it existed, see
+    /// `WrapperLevelDB::upgrade_untagged`. Returns how many values were rewritten.
+    pub fn upgrade_untagged(&self) -> Result<usize, DbError> {
+        Ok(self.signature_db.upgrade_untagged()?
+            + self.subject_db.upgrade_untagged()?
+            + self.event_db.upgrade_untagged()?
+            + self.event_hash_index_db.upgrade_untagged()?
+            + self.request_db.upgrade_untagged()?
+            + self.id_db.upgrade_untagged()?
+            + self.controller_history_db.upgrade_untagged()?
+            + self.namespace_index_db.upgrade_untagged()?
+            + self.blob_db.upgrade_untagged()?
+            + self.approval_time_db.upgrade_untagged()?
+            + self.sync_marker_db.upgrade_untagged()?)
+    }
+
+    /// Adds every stored subject to the namespace index, in a single write, and
+    /// marks the index as complete. Returns how many subjects were indexed.
+    pub fn reindex_namespaces(&self) -> Result<usize, DbError> {
+        let mut batch = Writebatch::new();
+        let mut indexed = 0;
+        for (key, subject) in self.subject_db.get_all() {
+            let Some(subject_data) = subject.subject_data else {
+                continue;
+            };
+            self.namespace_index_db
+                .partition(&subject_data.namespace)
+                .batch_put(&mut batch, &key.0, &key.0)?;
+            indexed += 1;
         }
+        self.sync_marker_db.batch_put(
+            &mut batch,
+            NAMESPACE_INDEX_MARKER,
+            &Utc::now().timestamp_millis(),
+        )?;
+        self.sync_marker_db.write_batch(&batch)?;
+        Ok(indexed)
+    }
+
+    /// Whether every stored subject is known to be in the namespace index.
+    fn namespace_index_complete(&self) -> bool {
+        self.sync_marker_db
+            .exists(NAMESPACE_INDEX_MARKER)
+            .unwrap_or(false)
+    }
+
+    fn set_sync(&self, sync: bool) {
+        self.signature_db.set_sync(sync);
+        self.subject_db.set_sync(sync);
+        self.event_db.set_sync(sync);
+        self.event_hash_index_db.set_sync(sync);
+        self.request_db.set_sync(sync);
+        self.id_db.set_sync(sync);
+        self.controller_history_db.set_sync(sync);
+        self.namespace_index_db.set_sync(sync);
+        self.blob_db.set_sync(sync);
+        self.approval_time_db.set_sync(sync);
+    }
+
+    /// Keeps up to `capacity` of the most recently read subjects in memory.
+    /// Subjects written through this `DB` are evicted from the cache, but writes
+    /// made through other `DB` instances over the same database are not seen, so
+    /// it should only be enabled on the instance that writes the subjects.
+    /// A capacity of 0 disables the cache.
+    pub fn with_subject_cache(mut self, capacity: usize) -> Self {
+        self.subject_cache = NonZeroUsize::new(capacity).map(|cap| Mutex::new(LruCache::new(cap)));
+        self
     }
+}
 
-    fn get_event(&self, subject_id: &DigestIdentifier, sn: u64) -> Option<Event> {
+impl DB {
+    fn _get_subject(&self, subject_id: &DigestIdentifier) -> Result<Subject, WrapperLevelDBErrors> {
         let id = subject_id.to_str();
-        let events_by_subject = self.event_db.partition(&id);
-        match events_by_subject.get(&sn.to_string()) {
-            Ok(event) => Some(event),
-            Err(error) => match error {
-                WrapperLevelDBErrors::EntryNotFoundError => None,
-                _ => {
-                    println!("ERRORR: {:?}", error);
-                    panic!("Not recoverable error get event")},
-            },
+        self.subject_db.get(&id)
+    }
+
+    /// Runs `f` holding the lock of the subject. Locks are only shared by the
+    /// callers of the same `DB` instance. Every writer of the subject table takes
+    /// it, except `import`, which loads a whole dump. It is not reentrant,
+    /// so `f` must not call another method that takes it.
+    fn with_subject_lock<T>(&self, subject_id: &DigestIdentifier, f: impl FnOnce() -> T) -> T {
+        let lock = self
+            .subject_locks
+            .lock()
+            .unwrap()
+            .entry(subject_id.clone())
+            .or_default()
+            .clone();
+        let result = {
+            let _guard = lock.lock().unwrap();
+            f()
+        };
+        let mut locks = self.subject_locks.lock().unwrap();
+        // Nobody else is waiting for the lock if only the map and we hold it
+        if Arc::strong_count(&lock) == 2 {
+            locks.remove(subject_id);
         }
+        result
     }
 
-    fn get_events_by_range(
+    fn log_operation(
         &self,
-        subject_id: &DigestIdentifier,
-        from: Option<String>,
-        quantity: isize,
-    ) -> Vec<Event> {
-        let id = subject_id.to_str();
-        let events_by_subject = self.event_db.partition(&id);
-        let cursor = match from {
-            Some(value) => CursorIndex::FromKey(value),
-            None => {
-                if quantity < 0 {
-                    CursorIndex::FromEnding
-                } else {
-                    CursorIndex::FromBeginning
-                }
-            }
+        operation: &str,
+        subject_id: Option<&DigestIdentifier>,
+        sn: Option<u64>,
+    ) {
+        let Some(oplog) = self.oplog.as_ref() else {
+            return;
         };
-        let quantity = match cursor {
-            CursorIndex::FromBeginning => quantity.abs(),
-            CursorIndex::FromEnding => {
-                if quantity > 0 {
-                    quantity * -1
-                } else {
-                    quantity
-                }
-            }
-            CursorIndex::FromKey(_) => quantity,
+        let entry = OpLogEntry {
+            operation: operation.to_owned(),
+            subject_id: subject_id.cloned(),
+            sn,
+            timestamp: Utc::now().timestamp_millis(),
         };
-        events_by_subject
-            .get_range(&cursor, quantity)
-            .into_iter()
-            .map(|x| x.1)
-            .collect()
+        let mut writer = oplog.lock().unwrap();
+        // The log is only a debugging aid, so failing to write it does not fail the mutation
+        let result = serde_json::to_writer(&mut *writer, &entry)
+            .map_err(std::io::Error::from)
+            .and_then(|_| writer.write_all(b"\n"));
+        if let Err(error) = result {
+            tracing::warn!(%error, "Could not write to the operation log");
+        }
     }
 
-    fn set_event(&self, subject_id: &DigestIdentifier, event: Event) {
+    /// Stores a subject keeping the cache and the namespace index up to date.
+    fn store_subject(
+        &self,
+        subject_id: &DigestIdentifier,
+        subject: Subject,
+    ) -> Result<(), DbError> {
+        let mut cache = self
+            .subject_cache
+            .as_ref()
+            .map(|cache| cache.lock().unwrap());
         let id = subject_id.to_str();
-        let events_by_subject = self.event_db.partition(&id);
-        let sn = event.event_content.sn.to_string();
-        if let Err(error) = events_by_subject.put(&sn, event) {
-            panic!(
-                "Error while inserting event sn:{} on subject_id:[{}]. Error --> {}",
-                sn, id, error
-            );
+        let old_namespace = match self._get_subject(subject_id) {
+            Ok(old_subject) => old_subject.subject_data.map(|data| data.namespace),
+            Err(WrapperLevelDBErrors::EntryNotFoundError) => None,
+            Err(error) => return Err(DbError::DatabaseError(error)),
+        };
+        let new_namespace = subject
+            .subject_data
+            .as_ref()
+            .map(|data| data.namespace.clone());
+        self.subject_db.put(&id, subject)?;
+        if let Some(cache) = cache.as_mut() {
+            cache.pop(subject_id);
+        }
+        // Keep the namespace index consistent with the stored subject. The entry is
+        // written even if the namespace did not change, as the subject may have
+        // been stored before the index existed
+        if let Some(namespace) = old_namespace.filter(|old| Some(old) != new_namespace.as_ref()) {
+            self.namespace_index_db.partition(&namespace).del(&id)?;
         }
+        if let Some(namespace) = new_namespace {
+            self.namespace_index_db
+                .partition(&namespace)
+                .put(&id, id.clone())?;
+        }
+        Ok(())
     }
 
-    fn get_signatures(&self, subject_id: &DigestIdentifier, sn: u64) -> Option<HashSet<Signature>> {
-        let id = subject_id.to_str();
-        let signatures_by_subject = self.signature_db.partition(&id);
-        match signatures_by_subject.get(&sn.to_string()) {
-            Ok(signatures) => Some(signatures),
-            Err(error) => match error {
-                WrapperLevelDBErrors::EntryNotFoundError => None,
-                _ => panic!("Not recoverable error get signatures"),
-            },
+    /// Points the content hash of `event` to its subject and SN. Must be called
+    /// before the event is stored, to drop the entry of the event it replaces.
+    fn index_event_hash(
+        &self,
+        subject_id: &DigestIdentifier,
+        event: &Event,
+    ) -> Result<(), DbError> {
+        let sn = event.event_content.sn;
+        let hash = &event.signature.content.event_content_hash;
+        let events_by_subject = self.event_db.partition(&subject_id.to_str());
+        match events_by_subject.get(&sn.to_string()) {
+            Ok(old_event) if &old_event.signature.content.event_content_hash != hash => {
+                let old_hash = old_event.signature.content.event_content_hash;
+                self.event_hash_index_db.del(&old_hash.to_str())?;
+            }
+            Ok(_) | Err(WrapperLevelDBErrors::EntryNotFoundError) => {}
+            Err(error) => return Err(DbError::DatabaseError(error)),
         }
+        self.event_hash_index_db
+            .put(&hash.to_str(), (subject_id.clone(), sn))?;
+        Ok(())
     }
 
-    fn set_signatures(
+    /// Adds to `batch` the put of `event` and the update of the hash index that
+    /// `index_event_hash` would make, so that both are written at once.
+    fn batch_set_event(
         &self,
+        batch: &mut Writebatch<StringKey>,
         subject_id: &DigestIdentifier,
-        sn: u64,
-        signatures: HashSet<Signature>,
-    ) {
-        let id = subject_id.to_str();
-        let signatures_by_subject = self.signature_db.partition(&id);
-        let sn = sn.to_string();
-        let total_signatures = match signatures_by_subject.get(&sn.to_string()) {
-            Ok(other) => signatures.union(&other).cloned().collect(),
-            Err(error) => match error {
-                WrapperLevelDBErrors::EntryNotFoundError => signatures,
-                _ => panic!("Not recoverable error get signatures"),
-            },
-        };
-        if let Err(error) = signatures_by_subject.put(&sn.to_string(), total_signatures) {
-            panic!(
-                "Error while inserting event sn:{} on subject_id:[{}]. Error --> {}",
-                sn, id, error
-            );
+        event: &Event,
+    ) -> Result<(), DbError> {
+        let events_by_subject = self.event_db.partition(&subject_id.to_str());
+        let sn = event.event_content.sn.to_string();
+        let hash = &event.signature.content.event_content_hash;
+        match events_by_subject.get(&sn) {
+            Ok(old_event) if &old_event.signature.content.event_content_hash != hash => {
+                let old_hash = old_event.signature.content.event_content_hash;
+                self.event_hash_index_db
+                    .batch_delete(batch, &old_hash.to_str());
+            }
+            Ok(_) | Err(WrapperLevelDBErrors::EntryNotFoundError) => {}
+            Err(error) => return Err(DbError::DatabaseError(error)),
         }
+        self.event_hash_index_db.batch_put(
+            batch,
+            &hash.to_str(),
+            &(subject_id.clone(), event.event_content.sn),
+        )?;
+        events_by_subject.batch_put(batch, &sn, event)?;
+        Ok(())
     }
 
-    fn get_subject(&self, subject_id: &DigestIdentifier) -> Option<Subject> {
+    fn read_subject(&self, subject_id: &DigestIdentifier) -> Option<Subject> {
         match self._get_subject(subject_id) {
             Ok(subject) => Some(subject),
             Err(error) => match error {
                 WrapperLevelDBErrors::EntryNotFoundError => None,
-                _ => panic!("Not recoverable error get subject"),
+                _ => {
+                    tracing::error!(%error, "Could not read subject");
+                    panic!("Not recoverable error get subject")
+                }
             },
         }
     }
 
-    fn set_subject(&self, subject_id: &DigestIdentifier, subject: Subject) {
+    /// Checks that the stored events of a subject form an unbroken hash chain,
+    /// with contiguous SNs starting at 0. The events deleted by `prune_events` are
+    /// skipped, so the chain goes on from the create event to the first one kept.
+    /// The first break found is returned.
+    pub fn verify_event_chain(&self, subject_id: &DigestIdentifier) -> Result<(), ChainError> {
         let id = subject_id.to_str();
-        if let Err(error) = self.subject_db.put(&id, subject) {
-            panic!(
-                "Error while inserting subject_id:[{}]. Error --> {}",
-                id, error
+        let events_by_subject = self.event_db.partition(&id);
+        // Keys are compared as strings, so the SN order has to be restored
+        let mut events: Vec<Event> = events_by_subject
+            .get_all()
+            .into_iter()
+            .map(|(_, event)| event)
+            .collect();
+        events.sort_by_key(|event| event.event_content.sn);
+        let pruned_until = self.pruned_until(subject_id);
+        let mut prev_hash: Option<DigestIdentifier> = None;
+        let mut expected_sn = 0;
+        for event in events.iter() {
+            let sn = event.event_content.sn;
+            if sn != expected_sn {
+                return Err(ChainError::MissingEvent(expected_sn));
+            }
+            if let Some(prev_hash) = prev_hash {
+                if prev_hash != event.event_content.previous_hash {
+                    return Err(ChainError::PreviousHashMismatch(sn));
+                }
+            }
+            prev_hash = Some(
+                event
+                    .get_event_content_hash()
+                    .map_err(|_| ChainError::EventHashingFailed(sn))?,
             );
+            expected_sn = sn + 1;
+            if let Some(pruned_until) = pruned_until.filter(|_| sn == 0) {
+                // The first event kept can not be chained to the create event
+                expected_sn = pruned_until + 1;
+                prev_hash = None;
+            }
         }
+        Ok(())
     }
 
-    fn apply_event_sourcing(&self, event_content: EventContent) -> Result<(), SubjectError> {
-        let subject_id = event_content.subject_id.clone();
-        let mut subject = self._get_subject(&subject_id).unwrap();
-        subject.apply(event_content.clone())?;
-        // Persist the change
-        self.set_subject(&subject_id, subject);
-        let id = subject_id.to_str();
-        let signatures_by_subject = self.signature_db.partition(&id);
-        match signatures_by_subject.del(&(event_content.sn - 1).to_string()) {
-            Ok(_) => Ok(()),
-            Err(error) => match error {
-                WrapperLevelDBErrors::EntryNotFoundError => Ok(()),
-                _ => Err(SubjectError::DeleteSignaturesFailed),
-            },
+    /// Returns up to `limit` events of the subject starting at the key `from`, or at the
+    /// first event if it is `None`, in the order of the keys, like `get_events_by_range`.
+    /// Keys are the SN compared as strings, so pages follow that order (`10` comes
+    /// before `2`), but following `next_cursor` still returns every event once.
+    pub fn get_events_page(
+        &self,
+        subject_id: &DigestIdentifier,
+        from: Option<String>,
+        limit: usize,
+    ) -> Result<EventPage, DbError> {
+        // A page without events could never advance the cursor
+        if limit == 0 {
+            return Err(DbError::EmptyPageLimit);
         }
+        let id = subject_id.to_str();
+        let events_by_subject = self.event_db.partition(&id);
+        let cursor = match from {
+            Some(key) => CursorIndex::FromKey(key),
+            None => CursorIndex::FromBeginning,
+        };
+        // One more event is read to know where the next page starts
+        let quantity = isize::try_from(limit.saturating_add(1)).unwrap_or(isize::MAX);
+        let mut entries = events_by_subject.try_get_range(&cursor, quantity)?;
+        let next_cursor = if entries.len() > limit {
+            entries.pop().map(|(StringKey(key), _)| key)
+        } else {
+            None
+        };
+        Ok(EventPage {
+            events: entries.into_iter().map(|(_, event)| event).collect(),
+            next_cursor,
+        })
     }
 
-    fn get_all_heads(&self) -> HashMap<DigestIdentifier, LedgerState> {
-        let mut result = HashMap::new();
-        for (key, subject) in self.subject_db.get_all().iter() {
-            let subject_id = DigestIdentifier::from_str(&key.0).expect("La conversion va bien");
-            result.insert(subject_id, subject.ledger_state.to_owned());
-        }
-        result
+    /// Returns the stored subject, or the one built by `make` if there is none.
+    /// Unlike `WrapperLevelDB::get_or_insert_with`, the default is not stored.
+    pub fn get_subject_or_default(
+        &self,
+        subject_id: &DigestIdentifier,
+        make: impl FnOnce() -> Subject,
+    ) -> Subject {
+        self.get_subject(subject_id).unwrap_or_else(make)
     }
 
-    fn set_negociating_true(&self, subject_id: &DigestIdentifier) -> Result<(), SubjectError> {
-        let mut subject = match self._get_subject(subject_id) {
-            Ok(subject) => subject,
-            Err(error) => match error {
-                WrapperLevelDBErrors::EntryNotFoundError => {
-                    return Err(SubjectError::SubjectNotFound)
-                }
-                _ => panic!("Not recoverable error get subject"),
-            },
+    /// Returns the SN of the next event of the subject and the content hash of its
+    /// current head, which the next event must chain to.
+    pub fn next_event_context(
+        &self,
+        subject_id: &DigestIdentifier,
+    ) -> Result<(u64, DigestIdentifier), SubjectError> {
+        let Some(subject) = self.get_subject(subject_id) else {
+            return Err(SubjectError::SubjectNotFound);
         };
-        subject.ledger_state.negociating_next = true;
-        // Persist the change
-        self.set_subject(&subject_id, subject);
-        Ok(())
+        let Some(subject_data) = subject.subject_data else {
+            return Err(SubjectError::SubjectHasNoData);
+        };
+        let head = self
+            .get_event(subject_id, subject_data.sn)
+            .map_err(|error| SubjectError::DatabaseError(error.to_string()))?;
+        let Some(head) = head else {
+            return Err(SubjectError::EventAlreadyAppliedNotFound);
+        };
+        Ok((
+            subject_data.sn + 1,
+            head.signature.content.event_content_hash,
+        ))
     }
 
-    fn get_all_subjects(&self) -> Vec<Subject> {
-        let mut result = Vec::new();
-        for (_, subject) in self.subject_db.get_all().iter() {
-            result.push(subject.to_owned());
+    /// Checks that the ledger state of a subject agrees with its stored events. The
+    /// head event must be stored and no event may be stored past the head, except
+    /// for the head candidate and the event being negotiated. The SN of the subject
+    /// data must be the head SN.
+    pub fn audit_subject(&self, subject_id: &DigestIdentifier) -> Result<(), AuditError> {
+        let Some(subject) = self.get_subject(subject_id) else {
+            return Err(AuditError::SubjectNotFound);
+        };
+        let ledger_state = subject.ledger_state;
+        let head_sn = ledger_state.head_sn;
+        let id = subject_id.to_str();
+        // Keys are compared as strings, so the highest SN is not the last key
+        let stored_sn = self
+            .event_db
+            .partition(&id)
+            .get_all()
+            .into_iter()
+            .map(|(_, event)| event.event_content.sn)
+            .max();
+        let negotiated_sn = match head_sn {
+            Some(head_sn) if ledger_state.negociating_next => Some(head_sn + 1),
+            _ => None,
+        };
+        let highest_allowed_sn = head_sn
+            .max(ledger_state.head_candidate_sn)
+            .max(negotiated_sn);
+        let head_stored = match head_sn {
+            Some(head_sn) => self
+                .has_event(subject_id, head_sn)
+                .map_err(|error| AuditError::DatabaseError(error.to_string()))?,
+            None => true,
+        };
+        if !head_stored || stored_sn > highest_allowed_sn {
+            return Err(AuditError::HeadSnMismatch { head_sn, stored_sn });
         }
-        result
-    }
-
-    fn get_all_request(&self) -> Vec<EventRequest> {
-        let mut result = Vec::new();
-        for (_, request) in self.request_db.get_all().iter() {
-            result.push(request.to_owned());
+        if let Some(subject_data) = subject.subject_data {
+            if Some(subject_data.sn) != head_sn {
+                return Err(AuditError::SubjectSnMismatch {
+                    subject_sn: subject_data.sn,
+                    head_sn,
+                });
+            }
         }
-        result
+        Ok(())
     }
 
-    fn get_request(
+    /// Deletes the events of a subject, their signatures and their hash index
+    /// entries, in a single write, except the create event and the last
+    /// `keep_last` ones. The head event is always kept, as the next event chains
+    /// to it. Returns how many events were deleted.
+    ///
+    /// The subject can not be rebuilt from a pruned history, so `rebuild_subject`
+    /// fails afterwards, and `verify_event_chain` checks the chain from the first
+    /// event kept after the create event.
+    pub fn prune_events(
         &self,
         subject_id: &DigestIdentifier,
-        request_id: &DigestIdentifier,
-    ) -> Option<EventRequest> {
+        keep_last: usize,
+    ) -> Result<usize, DbError> {
         let id = subject_id.to_str();
-        let requests_by_subject = self.request_db.partition(&id);
-        match requests_by_subject.get(&request_id.to_str()) {
-            Ok(request) => Some(request),
-            Err(error) => match error {
-                WrapperLevelDBErrors::EntryNotFoundError => None,
-                _ => panic!("Not recoverable error get request"),
-            },
-        }
+        let events_by_subject = self.event_db.partition(&id);
+        let signatures_by_subject = self.signature_db.partition(&id);
+        self.with_subject_lock(subject_id, || {
+            // Keys are compared as strings, so the SN order has to be restored
+            let mut events: Vec<(u64, DigestIdentifier)> = events_by_subject
+                .get_all()
+                .into_iter()
+                .map(|(_, event)| {
+                    let hash = event.signature.content.event_content_hash;
+                    (event.event_content.sn, hash)
+                })
+                .filter(|(sn, _)| *sn != 0)
+                .collect();
+            events.sort_unstable_by_key(|(sn, _)| *sn);
+            let pruned = events.len().saturating_sub(keep_last.max(1));
+            if pruned == 0 {
+                return Ok(0);
+            }
+            let last_pruned_sn = events[pruned - 1].0;
+            let mut batch = Writebatch::new();
+            for (sn, hash) in events.into_iter().take(pruned) {
+                let sn = sn.to_string();
+                events_by_subject.batch_delete(&mut batch, &sn);
+                signatures_by_subject.batch_delete(&mut batch, &sn);
+                self.event_hash_index_db
+                    .batch_delete(&mut batch, &hash.to_str());
+            }
+            self.sync_marker_db.partition(PRUNED_MARKER).batch_put(
+                &mut batch,
+                &id,
+                &(last_pruned_sn as i64),
+            )?;
+            self.event_db.write_batch(&batch)?;
+            Ok(pruned)
+        })
     }
 
-    fn del_request(
+    /// Last SN deleted by `prune_events` from the history of a subject.
+    fn pruned_until(&self, subject_id: &DigestIdentifier) -> Option<u64> {
+        self.sync_marker_db
+            .partition(PRUNED_MARKER)
+            .get(&subject_id.to_str())
+            .ok()
+            .map(|sn| sn as u64)
+    }
+
+    /// Rebuilds a subject from its stored events and persists it. The create event
+    /// gives the initial state and the rest are applied in SN order. The private
+    /// keys and the ledger state can not be recovered from the events, so the ones of
+    /// the stored subject are kept. If there is none, the rebuilt subject has no keys.
+    pub fn rebuild_subject(
         &self,
         subject_id: &DigestIdentifier,
-        request_id: &DigestIdentifier,
-    ) -> Option<EventRequest> {
-        let id = subject_id.to_str();
-        let requests_by_subject = self.request_db.partition(&id);
-        match requests_by_subject.del(&request_id.to_str()) {
-            Ok(request) => request,
-            Err(error) => match error {
-                WrapperLevelDBErrors::EntryNotFoundError => None,
-                _ => panic!("Not recoverable error get request"),
-            },
+        subject_schema: &Value,
+    ) -> Result<Subject, SubjectError> {
+        let genesis_event = self
+            .get_genesis_event(subject_id)
+            .map_err(|error| SubjectError::DatabaseError(error.to_string()))?;
+        let Some(genesis_event) = genesis_event else {
+            return Err(SubjectError::SubjectNotFound);
+        };
+        if let Some(pruned_until) = self.pruned_until(subject_id) {
+            return Err(SubjectError::HistoryPruned(pruned_until));
+        }
+        let mut event_contents = self.iter_event_contents(subject_id);
+        let Some(genesis_content) = event_contents.next() else {
+            return Err(SubjectError::SubjectNotFound);
+        };
+        let mut state_hash = genesis_content.state_hash.clone();
+        let mut subject = Subject::new(
+            &genesis_content,
+            genesis_event.signature.content.signer,
+            None,
+            subject_schema,
+        )?;
+        for (expected_sn, event_content) in (1u64..).zip(event_contents) {
+            if event_content.sn != expected_sn {
+                return Err(SubjectError::EventSourcingNotInOrder(
+                    expected_sn - 1,
+                    event_content.sn,
+                ));
+            }
+            state_hash = event_content.state_hash.clone();
+            subject.apply(event_content)?;
+        }
+        // The state reached must be the one announced by the last event
+        let Some(subject_data) = subject.subject_data.as_ref() else {
+            return Err(SubjectError::SubjectHasNoData);
+        };
+        let Ok(hash) = DigestIdentifier::from_serializable_borsh(subject_data.clone()) else {
+            return Err(SubjectError::EventContentSerializationFailed);
+        };
+        if hash != state_hash {
+            return Err(SubjectError::EventSourcingHashNotEqual);
+        }
+        let sn = subject_data.sn;
+        let stored = self.with_subject_lock(subject_id, || {
+            match self._get_subject(subject_id) {
+                Ok(stored) => {
+                    subject.keys = stored.keys;
+                    subject.ledger_state = stored.ledger_state;
+                }
+                Err(WrapperLevelDBErrors::EntryNotFoundError) => {}
+                Err(error) => return Err(DbError::DatabaseError(error)),
+            }
+            self.store_subject(subject_id, subject.clone())
+        });
+        if let Err(error) = stored {
+            return Err(SubjectError::SubjectNotStored(error.to_string()));
         }
+        self.log_operation("set_subject", Some(subject_id), Some(sn));
+        Ok(subject)
     }
 
-    fn set_request(&self, subject_id: &DigestIdentifier, request: EventRequest) {
-        let id = subject_id.to_str();
-        let requests_by_subject = self.request_db.partition(&id);
-        let req_id = request.signature.content.event_content_hash.to_str();
-        if let Err(error) = requests_by_subject.put(&req_id, request) {
-            panic!(
-                "Error while inserting request id:{} on subject_id:[{}]. Error --> {}",
-                req_id, id, error
-            );
+    /// Replaces the controller id and returns the previous one, which is appended
+    /// to the controller history. The history is written first, so an interrupted
+    /// rotation can leave it with an id that was never replaced, but never loses one.
+    pub fn rotate_controller(
+        &self,
+        new_id: &KeyIdentifier,
+    ) -> Result<Option<KeyIdentifier>, DbError> {
+        let old_id = self.get_controller_id()?;
+        if let Some(old_id) = old_id.as_ref() {
+            // Zero padded, so that the order of the keys is the order of the rotations
+            let position = format!("{:020}", self.controller_history_db.get_count());
+            self.controller_history_db.put(&position, old_id.to_str())?;
         }
+        self.set_controller_id(new_id)?;
+        Ok(old_id)
     }
-}
-
-use leveldb::options::Options as LevelDBOptions;
 
-pub fn open_db(path: &Path) -> std::sync::Arc<leveldb::database::Database<StringKey>> {
-    let mut db_options = LevelDBOptions::new();
-    db_options.create_if_missing = true;
+    /// Previous controller ids, from the oldest to the most recently replaced.
+    pub fn get_controller_history(&self) -> Result<Vec<KeyIdentifier>, DbError> {
+        self.controller_history_db
+            .get_all()
+            .into_iter()
+            .map(|(_, id)| {
+                KeyIdentifier::from_str(&id).map_err(|_| DbError::InvalidControllerId(id))
+            })
+            .collect()
+    }
 
-    if let Ok(db) = crate::bd::level_db::wrapper_leveldb::open_db(path, db_options) {
-        std::sync::Arc::new(db)
+    /// Reads a subject with all its stored events and signatures, to move it to
+    /// another node with `import_subject`.
+    #[tracing::instrument(level = "debug", skip_all, fields(subject_id = %subject_id.to_str()))]
+    pub fn export_subject(&self, subject_id: &DigestIdentifier) -> Result<SubjectBundle, DbError> {
+        let Some(subject) = self.read_subject(subject_id) else {
+            return Err(DbError::SubjectError(SubjectError::SubjectNotFound));
+        };
+        let mut events: Vec<Event> = self
+            .event_db
+            .partition(&subject_id.to_str())
+            .get_all()
+            .into_iter()
+            .map(|(_, event)| event)
+            .collect();
+        // The keys are the SN as strings, so the stored order is not the SN order
+        events.sort_by_key(|event| event.event_content.sn);
+        Ok(SubjectBundle {
+            subject,
+            events,
+            signatures: self.get_all_signatures(subject_id),
+        })
+    }
+
+    /// Stores a bundle returned by `export_subject` in a single write, so either the
+    /// subject, its events and its signatures are all stored or none of them is.
+    /// Entries with the same SN are replaced, other stored ones are kept.
+    pub fn import_subject(&self, bundle: SubjectBundle) -> Result<(), DbError> {
+        let SubjectBundle {
+            subject,
+            events,
+            signatures,
+        } = bundle;
+        let Some(subject_data) = subject.subject_data.as_ref() else {
+            return Err(DbError::SubjectError(SubjectError::SubjectHasNoData));
+        };
+        let subject_id = subject_data.subject_id.clone();
+        let new_namespace = subject_data.namespace.clone();
+        if events
+            .iter()
+            .any(|event| event.event_content.subject_id != subject_id)
+        {
+            return Err(DbError::MalformedDump(format!(
+                "Bundle of subject {} has events of other subjects",
+                subject_id.to_str()
+            )));
+        }
+        let id = subject_id.to_str();
+        self.with_subject_lock(&subject_id, || {
+            let mut batch = Writebatch::new();
+            for event in events.iter() {
+                self.batch_set_event(&mut batch, &subject_id, event)?;
+            }
+            let signatures_by_subject = self.signature_db.partition(&id);
+            for (sn, signatures) in signatures.iter() {
+                signatures_by_subject.batch_put(&mut batch, &sn.to_string(), signatures)?;
+            }
+            let old_namespace = match self._get_subject(&subject_id) {
+                Ok(old_subject) => old_subject.subject_data.map(|data| data.namespace),
+                Err(WrapperLevelDBErrors::EntryNotFoundError) => None,
+                Err(error) => return Err(DbError::DatabaseError(error)),
+            };
+            if let Some(old_namespace) = old_namespace {
+                self.namespace_index_db
+                    .partition(&old_namespace)
+                    .batch_delete(&mut batch, &id);
+            }
+            self.namespace_index_db
+                .partition(&new_namespace)
+                .batch_put(&mut batch, &id, &id)?;
+            self.subject_db.batch_put(&mut batch, &id, &subject)?;
+            self.subject_db.write_batch(&batch)?;
+            if let Some(cache) = self.subject_cache.as_ref() {
+                cache.lock().unwrap().pop(&subject_id);
+            }
+            Ok(())
+        })?;
+        self.log_operation("import_subject", Some(&subject_id), None);
+        Ok(())
+    }
+
+    /// Writes every entry of every table to `writer`. Each entry is written as its
+    /// table name, its key and its stored value, each of them prefixed by its
+    /// length as a little endian u32. Values are not deserialized, so `import`
+    /// restores them byte by byte. Tables are read as they are written, so the
+    /// database is never loaded in memory.
+    ///
+    /// The entries are preceded by `DUMP_MAGIC` and the `DUMP_VERSION` byte, and
+    /// followed by the CRC32 of the entries as a little endian u32.
+    pub fn export<W: Write>(&self, writer: &mut W) -> Result<(), DbError> {
+        writer.write_all(DUMP_MAGIC)?;
+        writer.write_all(&[DUMP_VERSION])?;
+        let mut writer = ChecksumWriter {
+            inner: writer,
+            crc: Crc32::new(),
+        };
+        write_dump_table(&mut writer, SIGNATURE_TABLE, self.signature_db.iter_raw())?;
+        write_dump_table(&mut writer, SUBJECT_TABLE, self.subject_db.iter_raw())?;
+        write_dump_table(&mut writer, EVENT_TABLE, self.event_db.iter_raw())?;
+        write_dump_table(
+            &mut writer,
+            EVENT_HASH_INDEX_TABLE,
+            self.event_hash_index_db.iter_raw(),
+        )?;
+        write_dump_table(&mut writer, REQUEST_TABLE, self.request_db.iter_raw())?;
+        write_dump_table(&mut writer, ID_TABLE, self.id_db.iter_raw())?;
+        write_dump_table(
+            &mut writer,
+            CONTROLLER_HISTORY_TABLE,
+            self.controller_history_db.iter_raw(),
+        )?;
+        write_dump_table(
+            &mut writer,
+            NAMESPACE_INDEX_TABLE,
+            self.namespace_index_db.iter_raw(),
+        )?;
+        write_dump_table(&mut writer, BLOB_TABLE, self.blob_db.iter_raw())?;
+        write_dump_table(
+            &mut writer,
+            APPROVAL_TIME_TABLE,
+            self.approval_time_db.iter_raw(),
+        )?;
+        write_dump_table(
+            &mut writer,
+            SYNC_MARKER_TABLE,
+            self.sync_marker_db.iter_raw(),
+        )?;
+        let crc = writer.crc.finish();
+        writer.inner.write_all(&crc.to_le_bytes())?;
+        Ok(writer.inner.flush()?)
+    }
+
+    /// Loads the entries written by `export`, overwriting the ones with the same key.
+    /// The dump is read as a stream and its entries are collected in a single batch,
+    /// which is only written once every entry has been parsed and the checksum
+    /// matches, so a corrupt or malformed dump leaves the database untouched.
+    pub fn import<R: Read>(&self, reader: &mut R) -> Result<(), DbError> {
+        let mut header = [0u8; DUMP_MAGIC.len() + 1];
+        if reader.read_exact(&mut header).is_err() || !header.starts_with(DUMP_MAGIC) {
+            return Err(DbError::CorruptExport("Missing header".into()));
+        }
+        if header[DUMP_MAGIC.len()] != DUMP_VERSION {
+            return Err(DbError::CorruptExport(format!(
+                "Unsupported version {}",
+                header[DUMP_MAGIC.len()]
+            )));
+        }
+        let mut reader = DumpReader {
+            inner: BufReader::new(reader),
+            tail: Vec::with_capacity(4),
+            crc: Crc32::new(),
+        };
+        let mut batch = Writebatch::new();
+        let parsed = self.read_dump_entries(&mut reader, &mut batch);
+        // A corrupt dump is reported as such even if the corruption made it malformed
+        std::io::copy(&mut reader, &mut std::io::sink())?;
+        if reader.tail.len() < 4 {
+            return Err(DbError::CorruptExport("Missing checksum".into()));
+        }
+        if reader.crc.finish().to_le_bytes()[..] != reader.tail[..] {
+            return Err(DbError::CorruptExport("Checksum mismatch".into()));
+        }
+        let index_complete = parsed?;
+        self.sync_marker_db.write_batch(&batch)?;
+        // Subjects have been written behind the cache
+        if let Some(cache) = self.subject_cache.as_ref() {
+            cache.lock().unwrap().clear();
+        }
+        // Dumps of databases whose index was not complete may bring unindexed subjects
+        if !index_complete {
+            self.reindex_namespaces()?;
+        }
+        Ok(())
+    }
+
+    /// Adds every entry of the dump to `batch`. Returns whether the dump carries the
+    /// mark of a complete namespace index.
+    fn read_dump_entries<R: Read>(
+        &self,
+        reader: &mut R,
+        batch: &mut Writebatch<StringKey>,
+    ) -> Result<bool, DbError> {
+        let mut index_complete = false;
+        // The dump can only end between entries
+        while let Some(table) = read_dump_field(reader)? {
+            let key = read_dump_field(reader)?;
+            let value = read_dump_field(reader)?;
+            let (Some(key), Some(value)) = (key, value) else {
+                return Err(DbError::MalformedDump("Truncated entry".into()));
+            };
+            let (Ok(table), Ok(key)) = (String::from_utf8(table), String::from_utf8(key)) else {
+                return Err(DbError::MalformedDump("Invalid UTF-8 name".into()));
+            };
+            match table.as_str() {
+                SIGNATURE_TABLE => self.signature_db.batch_put_raw(batch, &key, &value)?,
+                SUBJECT_TABLE => self.subject_db.batch_put_raw(batch, &key, &value)?,
+                EVENT_TABLE => self.event_db.batch_put_raw(batch, &key, &value)?,
+                EVENT_HASH_INDEX_TABLE => self
+                    .event_hash_index_db
+                    .batch_put_raw(batch, &key, &value)?,
+                REQUEST_TABLE => self.request_db.batch_put_raw(batch, &key, &value)?,
+                ID_TABLE => self.id_db.batch_put_raw(batch, &key, &value)?,
+                CONTROLLER_HISTORY_TABLE => self
+                    .controller_history_db
+                    .batch_put_raw(batch, &key, &value)?,
+                NAMESPACE_INDEX_TABLE => {
+                    self.namespace_index_db.batch_put_raw(batch, &key, &value)?
+                }
+                BLOB_TABLE => self.blob_db.batch_put_raw(batch, &key, &value)?,
+                APPROVAL_TIME_TABLE => self.approval_time_db.batch_put_raw(batch, &key, &value)?,
+                SYNC_MARKER_TABLE => {
+                    index_complete |= key == NAMESPACE_INDEX_MARKER;
+                    self.sync_marker_db.batch_put_raw(batch, &key, &value)?
+                }
+                _ => return Err(DbError::MalformedDump(format!("Unknown table {}", table))),
+            }
+        }
+        Ok(index_complete)
+    }
+}
+
+/// CRC-32 (IEEE) of the entries of a dump.
+struct Crc32(u32);
+
+impl Crc32 {
+    fn new() -> Self {
+        Self(0xFFFF_FFFF)
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u32;
+            for _ in 0..8 {
+                let mask = (self.0 & 1).wrapping_neg();
+                self.0 = (self.0 >> 1) ^ (0xEDB8_8320 & mask);
+            }
+        }
+    }
+
+    fn finish(&self) -> u32 {
+        !self.0
+    }
+}
+
+/// Computes the checksum of everything written through it.
+struct ChecksumWriter<'a, W: Write> {
+    inner: &'a mut W,
+    crc: Crc32,
+}
+
+impl<W: Write> Write for ChecksumWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.crc.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Reads the entries of a dump, holding back its last 4 bytes, which are the
+/// checksum of the entries, and computing the checksum of the bytes it returns.
+struct DumpReader<R: Read> {
+    inner: R,
+    tail: Vec<u8>,
+    crc: Crc32,
+}
+
+impl<R: Read> Read for DumpReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            let read = self.inner.read(buf)?;
+            if read == 0 {
+                return Ok(0);
+            }
+            let mut bytes = std::mem::take(&mut self.tail);
+            bytes.extend_from_slice(&buf[..read]);
+            // Never more than read, as the tail held at most 4 bytes
+            let Some(ready) = bytes.len().checked_sub(4).filter(|ready| *ready > 0) else {
+                self.tail = bytes;
+                continue;
+            };
+            buf[..ready].copy_from_slice(&bytes[..ready]);
+            self.tail = bytes.split_off(ready);
+            self.crc.update(&buf[..ready]);
+            return Ok(ready);
+        }
+    }
+}
+
+fn write_dump_table<W: Write>(
+    writer: &mut W,
+    table: &str,
+    entries: impl Iterator<Item = (String, Vec<u8>)>,
+) -> Result<(), DbError> {
+    for (key, value) in entries {
+        write_dump_field(writer, table.as_bytes())?;
+        write_dump_field(writer, key.as_bytes())?;
+        write_dump_field(writer, &value)?;
+    }
+    Ok(())
+}
+
+fn write_dump_field<W: Write>(writer: &mut W, field: &[u8]) -> Result<(), DbError> {
+    let Ok(len) = u32::try_from(field.len()) else {
+        return Err(DbError::MalformedDump("Entry too large".into()));
+    };
+    writer.write_all(&len.to_le_bytes())?;
+    Ok(writer.write_all(field)?)
+}
+
+/// Returns `None` if the reader is exhausted before the field starts. The length
+/// comes from the dump, so the field grows with the bytes actually read instead of
+/// being allocated up front, and a length beyond the end of the dump is rejected.
+fn read_dump_field<R: Read>(reader: &mut R) -> Result<Option<Vec<u8>>, DbError> {
+    let mut len = Vec::with_capacity(4);
+    reader.by_ref().take(4).read_to_end(&mut len)?;
+    match len.len() {
+        0 => return Ok(None),
+        4 => {}
+        _ => return Err(DbError::MalformedDump("Truncated entry".into())),
+    }
+    let len = u32::from_le_bytes([len[0], len[1], len[2], len[3]]) as u64;
+    let mut field = Vec::new();
+    reader.by_ref().take(len).read_to_end(&mut field)?;
+    if field.len() as u64 != len {
+        return Err(DbError::MalformedDump("Truncated entry".into()));
+    }
+    Ok(Some(field))
+}
+
+impl TapleDB for DB {
+    fn get_controller_id(&self) -> Result<Option<KeyIdentifier>, DbError> {
+        match self.id_db.get("") {
+            Ok(id) => KeyIdentifier::from_str(&id)
+                .map(Some)
+                .map_err(|_| DbError::InvalidControllerId(id)),
+            Err(WrapperLevelDBErrors::EntryNotFoundError) => Ok(None),
+            Err(error) => Err(DbError::DatabaseError(error)),
+        }
+    }
+
+    fn set_controller_id(&self, controller_id: &KeyIdentifier) -> Result<(), DbError> {
+        self.id_db.put("", controller_id.to_str())?;
+        self.log_operation("set_controller_id", None, None);
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "debug", skip(self, subject_id), fields(subject_id = %subject_id.to_str()))]
+    fn get_event(&self, subject_id: &DigestIdentifier, sn: u64) -> Result<Option<Event>, DbError> {
+        let id = subject_id.to_str();
+        let events_by_subject = self.event_db.partition(&id);
+        match events_by_subject.get(&sn.to_string()) {
+            Ok(event) => Ok(Some(event)),
+            Err(WrapperLevelDBErrors::EntryNotFoundError) => Ok(None),
+            Err(error) => {
+                tracing::error!(%error, "Could not read event");
+                Err(error.into())
+            }
+        }
+    }
+
+    fn get_event_before(
+        &self,
+        subject_id: &DigestIdentifier,
+        sn: u64,
+    ) -> Result<Option<Event>, DbError> {
+        // The event is read by its key, so the string order of the SNs does not matter
+        match sn.checked_sub(1) {
+            Some(sn) => self.get_event(subject_id, sn),
+            None => Ok(None),
+        }
+    }
+
+    fn get_genesis_event(&self, subject_id: &DigestIdentifier) -> Result<Option<Event>, DbError> {
+        let Some(event) = self.get_event(subject_id, 0)? else {
+            return Ok(None);
+        };
+        match event.event_content.event_request.request {
+            EventRequestType::Create(_) => Ok(Some(event)),
+            _ => {
+                tracing::error!(subject_id = %subject_id.to_str(), "Event 0 is not a create event");
+                Ok(None)
+            }
+        }
+    }
+
+    fn get_events_by_range(
+        &self,
+        subject_id: &DigestIdentifier,
+        from: Option<String>,
+        quantity: isize,
+    ) -> Vec<Event> {
+        let id = subject_id.to_str();
+        let events_by_subject = self.event_db.partition(&id);
+        let cursor = match from {
+            Some(value) => CursorIndex::FromKey(value),
+            None => {
+                if quantity < 0 {
+                    CursorIndex::FromEnding
+                } else {
+                    CursorIndex::FromBeginning
+                }
+            }
+        };
+        let quantity = match cursor {
+            CursorIndex::FromBeginning => quantity.abs(),
+            CursorIndex::FromEnding => {
+                if quantity > 0 {
+                    quantity * -1
+                } else {
+                    quantity
+                }
+            }
+            CursorIndex::FromKey(_) => quantity,
+        };
+        events_by_subject
+            .get_range(&cursor, quantity)
+            .into_iter()
+            .map(|x| x.1)
+            .collect()
+    }
+
+    fn get_events_between(
+        &self,
+        subject_id: &DigestIdentifier,
+        from_sn: u64,
+        to_sn: u64,
+    ) -> Vec<Event> {
+        let id = subject_id.to_str();
+        let events_by_subject = self.event_db.partition(&id);
+        let mut events: Vec<Event> = events_by_subject
+            .get_all()
+            .into_iter()
+            .map(|(_, event)| event)
+            .filter(|event| (from_sn..=to_sn).contains(&event.event_content.sn))
+            .collect();
+        // Keys are compared as strings, so the SN order has to be restored
+        events.sort_by_key(|event| event.event_content.sn);
+        events
+    }
+
+    #[tracing::instrument(
+        level = "debug",
+        skip(self, subject_id, event),
+        fields(subject_id = %subject_id.to_str(), sn = event.event_content.sn),
+        err(Display)
+    )]
+    fn set_event(&self, subject_id: &DigestIdentifier, event: Event) -> Result<(), DbError> {
+        let id = subject_id.to_str();
+        let events_by_subject = self.event_db.partition(&id);
+        let sn = event.event_content.sn;
+        self.index_event_hash(subject_id, &event)?;
+        events_by_subject.put(&sn.to_string(), event)?;
+        self.log_operation("set_event", Some(subject_id), Some(sn));
+        Ok(())
+    }
+
+    fn set_events(&self, subject_id: &DigestIdentifier, events: Vec<Event>) -> Result<(), DbError> {
+        // The whole batch is rejected if the SNs have gaps or are unordered
+        if let Some(first) = events.first() {
+            let first_sn = first.event_content.sn;
+            for (expected_sn, event) in (first_sn..).zip(events.iter()) {
+                if event.event_content.sn != expected_sn {
+                    return Err(DbError::SubjectError(
+                        SubjectError::EventSourcingNotInOrder(expected_sn, event.event_content.sn),
+                    ));
+                }
+            }
+        }
+        // The events and their hash index entries are written at once, so a crash
+        // leaves either all of them or none
+        let mut batch = Writebatch::new();
+        for event in events.iter() {
+            self.batch_set_event(&mut batch, subject_id, event)?;
+        }
+        self.event_db.write_batch(&batch)?;
+        for event in events.iter() {
+            self.log_operation("set_events", Some(subject_id), Some(event.event_content.sn));
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "debug", skip_all, fields(hash = %hash.to_str()))]
+    fn get_event_by_hash(&self, hash: &DigestIdentifier) -> Result<Option<Event>, DbError> {
+        let (subject_id, sn) = match self.event_hash_index_db.get(&hash.to_str()) {
+            Ok(entry) => entry,
+            Err(WrapperLevelDBErrors::EntryNotFoundError) => return Ok(None),
+            Err(error) => {
+                tracing::error!(%error, "Could not read event hash index");
+                return Err(error.into());
+            }
+        };
+        // The index is only a hint, the event found must have the hash asked for
+        let event = self
+            .get_event(&subject_id, sn)?
+            .filter(|event| &event.signature.content.event_content_hash == hash);
+        if event.is_none() {
+            tracing::warn!(
+                subject_id = %subject_id.to_str(),
+                sn,
+                "Event hash index points to an event which does not have the hash"
+            );
+        }
+        Ok(event)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self, subject_id), fields(subject_id = %subject_id.to_str()))]
+    fn has_event(&self, subject_id: &DigestIdentifier, sn: u64) -> Result<bool, DbError> {
+        let id = subject_id.to_str();
+        let events_by_subject = self.event_db.partition(&id);
+        match events_by_subject.exists(&sn.to_string()) {
+            Ok(exists) => Ok(exists),
+            Err(error) => {
+                tracing::error!(%error, "Could not check event");
+                Err(error.into())
+            }
+        }
+    }
+
+    fn event_count(&self, subject_id: &DigestIdentifier) -> usize {
+        let id = subject_id.to_str();
+        self.event_db.partition(&id).get_count()
+    }
+
+    fn iter_event_contents(
+        &self,
+        subject_id: &DigestIdentifier,
+    ) -> std::vec::IntoIter<EventContent> {
+        let id = subject_id.to_str();
+        let events_by_subject = self.event_db.partition(&id);
+        let mut event_contents: Vec<EventContent> = events_by_subject
+            .get_all()
+            .into_iter()
+            .map(|(_, event)| event.event_content)
+            .collect();
+        // The keys are the SN as strings, so the stored order is not the SN order
+        event_contents.sort_by_key(|event_content| event_content.sn);
+        event_contents.into_iter()
+    }
+
+    #[tracing::instrument(level = "debug", skip(self, subject_id), fields(subject_id = %subject_id.to_str()))]
+    fn get_signatures(&self, subject_id: &DigestIdentifier, sn: u64) -> Option<HashSet<Signature>> {
+        let id = subject_id.to_str();
+        let signatures_by_subject = self.signature_db.partition(&id);
+        match signatures_by_subject.get(&sn.to_string()) {
+            Ok(signatures) => Some(signatures),
+            Err(error) => match error {
+                WrapperLevelDBErrors::EntryNotFoundError => None,
+                _ => {
+                    tracing::error!(%error, "Could not read signatures");
+                    panic!("Not recoverable error get signatures")
+                }
+            },
+        }
+    }
+
+    fn get_signatures_count(&self, subject_id: &DigestIdentifier, sn: u64) -> usize {
+        match self.get_signatures(subject_id, sn) {
+            Some(signatures) => signatures.len(),
+            None => 0,
+        }
+    }
+
+    fn get_all_signatures(
+        &self,
+        subject_id: &DigestIdentifier,
+    ) -> BTreeMap<u64, HashSet<Signature>> {
+        let id = subject_id.to_str();
+        self.signature_db
+            .partition(&id)
+            .get_all()
+            .into_iter()
+            // The keys are the SN as strings, so they are parsed to sort them
+            .filter_map(|(key, signatures)| match key.0.parse::<u64>() {
+                Ok(sn) => Some((sn, signatures)),
+                Err(_) => {
+                    tracing::warn!(
+                        subject_id = %id,
+                        key = ?key.0,
+                        "Skipping signatures stored under malformed key"
+                    );
+                    None
+                }
+            })
+            .collect()
+    }
+
+    fn set_signatures(
+        &self,
+        subject_id: &DigestIdentifier,
+        sn: u64,
+        signatures: HashSet<Signature>,
+    ) -> Result<(), DbError> {
+        let id = subject_id.to_str();
+        let signatures_by_subject = self.signature_db.partition(&id);
+        let key = sn.to_string();
+        let stored_signatures = match signatures_by_subject.get(&key) {
+            Ok(other) => other,
+            Err(WrapperLevelDBErrors::EntryNotFoundError) => HashSet::new(),
+            Err(error) => return Err(DbError::DatabaseError(error)),
+        };
+        // Only one signature per signer is kept, the new ones take precedence
+        let mut total_signatures = SignatureSet::from(stored_signatures);
+        total_signatures.extend(signatures);
+        signatures_by_subject.put(&key, total_signatures.into())?;
+        self.log_operation("set_signatures", Some(subject_id), Some(sn));
+        Ok(())
+    }
+
+    fn set_approval_timestamp(
+        &self,
+        subject_id: &DigestIdentifier,
+        sn: u64,
+        approver: &KeyIdentifier,
+        timestamp: Option<i64>,
+    ) -> Result<(), DbError> {
+        let timestamp = timestamp.unwrap_or_else(|| Utc::now().timestamp_millis());
+        self.approval_time_db
+            .partition(&subject_id.to_str())
+            .partition(&sn.to_string())
+            .put(&approver.to_str(), timestamp)?;
+        self.log_operation("set_approval_timestamp", Some(subject_id), Some(sn));
+        Ok(())
+    }
+
+    fn get_approval_timeline(
+        &self,
+        subject_id: &DigestIdentifier,
+        sn: u64,
+    ) -> Vec<(KeyIdentifier, i64)> {
+        let mut timeline: Vec<(KeyIdentifier, i64)> = self
+            .approval_time_db
+            .partition(&subject_id.to_str())
+            .partition(&sn.to_string())
+            .get_all()
+            .into_iter()
+            .filter_map(|(key, timestamp)| match KeyIdentifier::from_str(&key.0) {
+                Ok(approver) => Some((approver, timestamp)),
+                Err(_) => {
+                    tracing::warn!(key = ?key.0, "Skipping approval time stored under malformed key");
+                    None
+                }
+            })
+            .collect();
+        timeline.sort_by_key(|(_, timestamp)| *timestamp);
+        timeline
+    }
+
+    #[tracing::instrument(level = "debug", skip_all, fields(subject_id = %subject_id.to_str()))]
+    fn get_subject(&self, subject_id: &DigestIdentifier) -> Option<Subject> {
+        let Some(cache) = self.subject_cache.as_ref() else {
+            return self.read_subject(subject_id);
+        };
+        // The lock is held while reading so a concurrent set_subject can not be
+        // followed by the insertion of the value it replaced
+        let mut cache = cache.lock().unwrap();
+        if let Some(subject) = cache.get(subject_id) {
+            return Some(subject.clone());
+        }
+        let subject = self.read_subject(subject_id)?;
+        cache.put(subject_id.clone(), subject.clone());
+        Some(subject)
+    }
+
+    fn get_subjects_by_namespace(&self, namespace: &str) -> Vec<Subject> {
+        if !self.namespace_index_complete() {
+            return self
+                .get_all_subjects()
+                .into_iter()
+                .filter(|subject| {
+                    subject
+                        .subject_data
+                        .as_ref()
+                        .is_some_and(|data| data.namespace == namespace)
+                })
+                .collect();
+        }
+        let mut result = Vec::new();
+        let subjects_by_namespace = self.namespace_index_db.partition(namespace);
+        for (_, subject_id) in subjects_by_namespace.get_all().iter() {
+            let Ok(subject_id) = DigestIdentifier::from_str(subject_id) else {
+                tracing::warn!(
+                    subject_id = ?subject_id,
+                    namespace,
+                    "Skipping malformed subject id"
+                );
+                continue;
+            };
+            if let Some(subject) = self.get_subject(&subject_id) {
+                result.push(subject);
+            }
+        }
+        result
+    }
+
+    fn list_namespaces(&self) -> HashSet<String> {
+        // Index keys are the namespace and the subject id, so values are not read
+        let separator = self.namespace_index_db.get_separator();
+        let namespaces: HashSet<String> = self
+            .namespace_index_db
+            .get_all_raw()
+            .into_iter()
+            .filter_map(|(key, _)| {
+                key.rsplit_once(separator)
+                    .map(|(namespace, _)| namespace.to_owned())
+            })
+            .collect();
+        if !namespaces.is_empty() && self.namespace_index_complete() {
+            return namespaces;
+        }
+        // Subjects stored before the index existed may not be indexed yet
+        self.get_all_subjects()
+            .into_iter()
+            .filter_map(|subject| subject.subject_data.map(|data| data.namespace))
+            .collect()
+    }
+
+    #[tracing::instrument(
+        level = "debug",
+        skip_all,
+        fields(subject_id = %subject_id.to_str(), sn = ?subject.subject_data.as_ref().map(|data| data.sn)),
+        err(Display)
+    )]
+    fn set_subject(&self, subject_id: &DigestIdentifier, subject: Subject) -> Result<(), DbError> {
+        let sn = subject.subject_data.as_ref().map(|data| data.sn);
+        self.with_subject_lock(subject_id, || self.store_subject(subject_id, subject))?;
+        self.log_operation("set_subject", Some(subject_id), sn);
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "debug", skip(self, subject_id, subject), fields(subject_id = %subject_id.to_str()))]
+    fn set_subject_if_sn(
+        &self,
+        subject_id: &DigestIdentifier,
+        subject: Subject,
+        expected_current_sn: u64,
+    ) -> Result<(), ConflictError> {
+        self.with_subject_lock(subject_id, || {
+            let current = match self._get_subject(subject_id) {
+                Ok(current) => current,
+                Err(WrapperLevelDBErrors::EntryNotFoundError) => {
+                    return Err(ConflictError::SubjectNotFound)
+                }
+                Err(error) => return Err(DbError::DatabaseError(error).into()),
+            };
+            let current_sn = current.subject_data.map(|data| data.sn);
+            if current_sn != Some(expected_current_sn) {
+                return Err(ConflictError::SnMismatch {
+                    expected: expected_current_sn,
+                    current: current_sn,
+                });
+            }
+            let sn = subject.subject_data.as_ref().map(|data| data.sn);
+            self.store_subject(subject_id, subject)?;
+            self.log_operation("set_subject_if_sn", Some(subject_id), sn);
+            Ok(())
+        })
+    }
+
+    #[tracing::instrument(
+        level = "debug",
+        skip_all,
+        fields(
+            subject_id = %event_content.subject_id.to_str(),
+            sn = event_content.sn
+        ),
+        err(Display)
+    )]
+    fn apply_event_sourcing(&self, event_content: EventContent) -> Result<(), DbError> {
+        let subject_id = event_content.subject_id.clone();
+        self.with_subject_lock(&subject_id, || {
+            let mut subject = self._get_subject(&subject_id)?;
+            subject.apply(event_content.clone())?;
+            // Persist the change
+            self.store_subject(&subject_id, subject)
+        })?;
+        let id = subject_id.to_str();
+        let signatures_by_subject = self.signature_db.partition(&id);
+        match signatures_by_subject.del(&(event_content.sn - 1).to_string()) {
+            Ok(_) => {}
+            Err(error) => match error {
+                WrapperLevelDBErrors::EntryNotFoundError => {}
+                _ => return Err(DbError::SubjectError(SubjectError::DeleteSignaturesFailed)),
+            },
+        }
+        self.log_operation(
+            "apply_event_sourcing",
+            Some(&subject_id),
+            Some(event_content.sn),
+        );
+        Ok(())
+    }
+
+    fn get_all_heads(&self) -> HashMap<DigestIdentifier, LedgerState> {
+        let mut result = HashMap::new();
+        for (key, subject) in self.subject_db.get_all().iter() {
+            // A corrupted key must not prevent the node from starting
+            let Ok(subject_id) = DigestIdentifier::from_str(&key.0) else {
+                tracing::warn!(key = ?key.0, "Skipping subject stored under malformed key");
+                continue;
+            };
+            result.insert(subject_id, subject.ledger_state.to_owned());
+        }
+        result
+    }
+
+    fn get_all_heads_sorted(&self) -> Vec<(DigestIdentifier, LedgerState)> {
+        let mut heads: Vec<(DigestIdentifier, LedgerState)> =
+            self.get_all_heads().into_iter().collect();
+        heads.sort_by_cached_key(|(subject_id, _)| subject_id.to_str());
+        heads
+    }
+
+    fn set_negociating_true(&self, subject_id: &DigestIdentifier) -> Result<(), DbError> {
+        self.with_subject_lock(subject_id, || {
+            let mut subject = match self._get_subject(subject_id) {
+                Ok(subject) => subject,
+                Err(WrapperLevelDBErrors::EntryNotFoundError) => {
+                    return Err(DbError::SubjectError(SubjectError::SubjectNotFound))
+                }
+                Err(error) => return Err(DbError::DatabaseError(error)),
+            };
+            subject.ledger_state.negociating_next = true;
+            // Persist the change
+            self.store_subject(subject_id, subject)
+        })?;
+        self.log_operation("set_negociating_true", Some(subject_id), None);
+        Ok(())
+    }
+
+    fn get_all_subjects(&self) -> Vec<Subject> {
+        let mut result = Vec::new();
+        for (_, subject) in self.subject_db.get_all().iter() {
+            result.push(subject.to_owned());
+        }
+        result
+    }
+
+    fn get_all_governances(&self) -> Vec<Subject> {
+        self.subject_db
+            .get_all()
+            .into_iter()
+            .map(|(_, subject)| subject)
+            .filter(Subject::is_governance)
+            .collect()
+    }
+
+    fn get_all_request(&self) -> Vec<EventRequest> {
+        let mut result = Vec::new();
+        for (_, request) in self.request_db.get_all().iter() {
+            result.push(request.to_owned());
+        }
+        result
+    }
+
+    fn request_count(&self, subject_id: &DigestIdentifier) -> usize {
+        let id = subject_id.to_str();
+        self.request_db.partition(&id).get_count()
+    }
+
+    fn get_requests(&self, subject_id: &DigestIdentifier) -> Vec<EventRequest> {
+        let id = subject_id.to_str();
+        self.request_db
+            .partition(&id)
+            .get_all()
+            .into_iter()
+            .map(|(_, request)| request)
+            .collect()
+    }
+
+    #[tracing::instrument(
+        level = "debug",
+        skip_all,
+        fields(subject_id = %subject_id.to_str(), request_id = %request_id.to_str())
+    )]
+    fn get_request(
+        &self,
+        subject_id: &DigestIdentifier,
+        request_id: &DigestIdentifier,
+    ) -> Option<EventRequest> {
+        let id = subject_id.to_str();
+        let requests_by_subject = self.request_db.partition(&id);
+        match requests_by_subject.get(&request_id.to_str()) {
+            Ok(request) => Some(request),
+            Err(error) => match error {
+                WrapperLevelDBErrors::EntryNotFoundError => None,
+                _ => {
+                    tracing::error!(%error, "Could not read request");
+                    panic!("Not recoverable error get request")
+                }
+            },
+        }
+    }
+
+    #[tracing::instrument(
+        level = "debug",
+        skip_all,
+        fields(subject_id = %subject_id.to_str(), request_id = %request_id.to_str())
+    )]
+    fn del_request(
+        &self,
+        subject_id: &DigestIdentifier,
+        request_id: &DigestIdentifier,
+    ) -> Option<EventRequest> {
+        let id = subject_id.to_str();
+        let requests_by_subject = self.request_db.partition(&id);
+        let request = match requests_by_subject.del(&request_id.to_str()) {
+            Ok(request) => request,
+            Err(error) => match error {
+                WrapperLevelDBErrors::EntryNotFoundError => None,
+                _ => {
+                    tracing::error!(%error, "Could not delete request");
+                    panic!("Not recoverable error del request")
+                }
+            },
+        };
+        self.log_operation("del_request", Some(subject_id), None);
+        request
+    }
+
+    fn set_request(
+        &self,
+        subject_id: &DigestIdentifier,
+        request: EventRequest,
+    ) -> Result<(), DbError> {
+        let id = subject_id.to_str();
+        let requests_by_subject = self.request_db.partition(&id);
+        let req_id = request.signature.content.event_content_hash.to_str();
+        requests_by_subject.put(&req_id, request)?;
+        self.log_operation("set_request", Some(subject_id), None);
+        Ok(())
+    }
+
+    fn put_blob(&self, bytes: &[u8]) -> Result<DigestIdentifier, DbError> {
+        // Blobs are keyed by their own digest, so storing the same content twice
+        // leaves a single copy
+        let blob_id = DigestIdentifier::new(
+            DigestDerivator::Blake3_256,
+            &DigestDerivator::Blake3_256.digest(bytes),
+        );
+        let key = blob_id.to_str();
+        match self.blob_db.get_bytes(&key) {
+            Ok(_) => {}
+            Err(WrapperLevelDBErrors::EntryNotFoundError) => {
+                self.blob_db.put(&key, bytes.to_vec())?
+            }
+            Err(error) => return Err(DbError::DatabaseError(error)),
+        }
+        Ok(blob_id)
+    }
+
+    #[tracing::instrument(level = "debug", skip_all, fields(blob_id = %blob_id.to_str()))]
+    fn get_blob(&self, blob_id: &DigestIdentifier) -> Option<Vec<u8>> {
+        match self.blob_db.get(&blob_id.to_str()) {
+            Ok(bytes) => Some(bytes),
+            Err(error) => match error {
+                WrapperLevelDBErrors::EntryNotFoundError => None,
+                _ => {
+                    tracing::error!(%error, "Could not read blob");
+                    panic!("Not recoverable error get blob")
+                }
+            },
+        }
+    }
+}
+
+use leveldb::options::Options as LevelDBOptions;
+
+pub fn open_db(path: &Path) -> std::sync::Arc<leveldb::database::Database<StringKey>> {
+    let mut db_options = LevelDBOptions::new();
+    db_options.create_if_missing = true;
+
+    if let Ok(db) = crate::bd::level_db::wrapper_leveldb::open_db(path, db_options) {
+        std::sync::Arc::new(db)
     } else {
         panic!("Could not open LevelDB connection")
     }
@@ -314,14 +1785,203 @@ pub fn open_db(path: &Path) -> std::sync::Arc<leveldb::database::Database<String
 #[cfg(test)]
 mod tests {
 
-    use std::str::FromStr;
+    use std::{
+        collections::{BTreeMap, HashSet},
+        io::Write,
+        panic::AssertUnwindSafe,
+        path::Path,
+        str::FromStr,
+        sync::{Arc, Mutex},
+    };
 
+    use chrono::Utc;
     use tempdir::TempDir;
     use tokio::runtime::Runtime;
 
-    use crate::{bd::TapleDB, identifier::DigestIdentifier, models::event::Event};
+    use crate::{
+        bd::{
+            level_db::{
+                error::WrapperLevelDBErrors,
+                metrics::TableMetrics,
+                wrapper_leveldb::{failpoint, StringKey, WrapperLevelDB},
+            },
+            TapleDB,
+        },
+        crypto::{Ed25519KeyPair, KeyGenerator, KeyMaterial, KeyPair, Payload, DSA},
+        errors::{AuditError, ChainError, ConflictError, DbError, SubjectError},
+        identifier::{Derivable, DigestIdentifier, KeyIdentifier, SignatureIdentifier},
+        models::{
+            event::Event,
+            event_request::{
+                CreateRequest, EventRequest, EventRequestType, RequestPayload, StateRequest,
+            },
+            governance::Governance,
+            signature::{Signature, SignatureContent},
+            state::{LedgerState, Subject, SubjectData},
+        },
+    };
+
+    use tracing_test::traced_test;
+
+    use super::{
+        open_db, write_dump_field, ChecksumWriter, Crc32, EventPage, OpLogEntry, SubjectBundle,
+        BLOB_TABLE, DB, DUMP_MAGIC, DUMP_VERSION, EVENT_HASH_INDEX_TABLE, EVENT_TABLE,
+        NAMESPACE_INDEX_MARKER, SIGNATURE_TABLE, SUBJECT_TABLE,
+    };
 
-    use super::{open_db, DB};
+    fn sign<T: borsh::BorshSerialize>(keys: &KeyPair, content: T, timestamp: i64) -> Signature {
+        let hash = DigestIdentifier::from_serializable_borsh(content).unwrap();
+        let signer = KeyIdentifier::new(keys.get_key_derivator(), &keys.public_key_bytes());
+        let signature = keys.sign(Payload::Buffer(hash.derivative())).unwrap();
+        Signature {
+            content: SignatureContent {
+                signer: signer.clone(),
+                event_content_hash: hash,
+                timestamp,
+            },
+            signature: SignatureIdentifier::new(signer.to_signature_derivator(), &signature),
+        }
+    }
+
+    fn create_event(
+        keys: &KeyPair,
+        subject_id: &DigestIdentifier,
+        sn: u64,
+        previous_hash: DigestIdentifier,
+    ) -> Event {
+        let mut event_content = Event::default().event_content;
+        let request = EventRequestType::State(StateRequest {
+            subject_id: subject_id.clone(),
+            payload: RequestPayload::Json(format!("{{\"sn\":{}}}", sn)),
+        });
+        let timestamp = sn as i64;
+        event_content.event_request.signature = sign(keys, (request.clone(), timestamp), timestamp);
+        event_content.event_request.request = request;
+        event_content.event_request.timestamp = timestamp;
+        event_content.subject_id = subject_id.clone();
+        event_content.sn = sn;
+        event_content.previous_hash = previous_hash;
+        let signature = sign(keys, event_content.clone(), timestamp);
+        Event::new(event_content, signature).unwrap()
+    }
+
+    fn create_request(keys: &KeyPair, request: EventRequestType, timestamp: i64) -> EventRequest {
+        EventRequest {
+            signature: sign(keys, (request.clone(), timestamp), timestamp),
+            request,
+            timestamp,
+            approvals: HashSet::new(),
+        }
+    }
+
+    /// Creates a subject with the first payload and applies the rest as state events
+    fn create_subject_history(
+        keys: &KeyPair,
+        payloads: &[String],
+        schema: &serde_json::Value,
+    ) -> (Subject, Vec<Event>) {
+        let request = create_request(
+            keys,
+            EventRequestType::Create(CreateRequest {
+                governance_id: DigestIdentifier::default(),
+                schema_id: "test".into(),
+                namespace: "namespace".into(),
+                payload: RequestPayload::Json(payloads[0].clone()),
+            }),
+            0,
+        );
+        let (mut subject, event) = request
+            .create_subject_from_request(0, schema, true)
+            .unwrap();
+        let mut events = vec![event];
+        // The owner invokes the subject on their own
+        let rule = serde_json::json!({"allowance": false, "approvalRequired": false});
+        let governance: Governance = serde_json::from_value(serde_json::json!({
+            "members": [],
+            "schemas": [],
+            "policies": [{
+                "id": "test",
+                "validation": {"quorum": 1.0, "validators": []},
+                "approval": {"quorum": 1.0, "approvers": []},
+                "invokation": {
+                    "owner": {"allowance": true, "approvalRequired": false},
+                    "set": {"allowance": false, "approvalRequired": false, "invokers": []},
+                    "all": rule,
+                    "external": rule
+                }
+            }]
+        }))
+        .unwrap();
+        for (timestamp, payload) in payloads.iter().enumerate().skip(1) {
+            let request = create_request(
+                keys,
+                EventRequestType::State(StateRequest {
+                    subject_id: subject.subject_data.as_ref().unwrap().subject_id.clone(),
+                    payload: RequestPayload::Json(payload.clone()),
+                }),
+                timestamp as i64,
+            );
+            let previous_hash = events
+                .last()
+                .unwrap()
+                .signature
+                .content
+                .event_content_hash
+                .clone();
+            let event = request
+                .get_event_from_state_request(
+                    &subject,
+                    events.len() as u64,
+                    previous_hash,
+                    &governance,
+                    0,
+                    schema,
+                    true,
+                )
+                .unwrap();
+            subject.apply(event.event_content.clone()).unwrap();
+            events.push(event);
+        }
+        (subject, events)
+    }
+
+    fn create_subject(keys: &KeyPair, seed: &str, namespace: &str) -> Subject {
+        let subject_id = DigestIdentifier::from_serializable_borsh(seed).unwrap();
+        let key_identifier = KeyIdentifier::new(keys.get_key_derivator(), &keys.public_key_bytes());
+        Subject {
+            subject_data: Some(SubjectData {
+                subject_id,
+                governance_id: DigestIdentifier::default(),
+                sn: 0,
+                public_key: key_identifier.clone(),
+                namespace: namespace.into(),
+                schema_id: "test".into(),
+                owner: key_identifier,
+                properties: "{}".into(),
+            }),
+            keys: None,
+            ledger_state: LedgerState::default(),
+        }
+    }
+
+    fn subject_ids(subjects: Vec<Subject>) -> HashSet<DigestIdentifier> {
+        subjects
+            .into_iter()
+            .map(|subject| subject.subject_data.unwrap().subject_id)
+            .collect()
+    }
+
+    fn create_event_chain(keys: &KeyPair, subject_id: &DigestIdentifier, len: u64) -> Vec<Event> {
+        let mut events: Vec<Event> = Vec::new();
+        for sn in 0..len {
+            let previous_hash = match events.last() {
+                Some(event) => event.signature.content.event_content_hash.clone(),
+                None => DigestIdentifier::default(),
+            };
+            events.push(create_event(keys, subject_id, sn, previous_hash));
+        }
+        events
+    }
 
     #[test]
     fn test_simple_insert() {
@@ -335,13 +1995,13 @@ mod tests {
                 // Open connection...
                 let db = DB::new(open_db(temp_dir.path()));
                 // Insert an event...
-                db.set_event(&subject_id, event.clone())
+                db.set_event(&subject_id, event.clone()).unwrap();
             }
             {
                 // We open it again
                 let db = DB::new(open_db(temp_dir.path()));
                 // Retrive the inserted event... (to check the persistence)
-                let ev0 = db.get_event(&subject_id, 1);
+                let ev0 = db.get_event(&subject_id, 1).unwrap();
                 assert!(ev0.is_some());
                 assert_eq!(ev0.unwrap(), event)
             }
@@ -359,7 +2019,1543 @@ mod tests {
         let _db4 = DB::new(pre_db.clone());
         let subject_id = DigestIdentifier::from_str("Ju536BiUXBqbuNdJsOBwYWnbzrKjsYtVEauI6IsMh3tM").unwrap();
         let event = Event::default();
-        db1.set_event(&subject_id, event.clone());
-        assert_eq!(db2.get_event(&subject_id, 1).unwrap(), event);
+        db1.set_event(&subject_id, event.clone()).unwrap();
+        assert_eq!(db2.get_event(&subject_id, 1).unwrap().unwrap(), event);
+    }
+
+    #[test]
+    fn test_verify_event_chain() {
+        let temp_dir = TempDir::new("test_verify_event_chain").unwrap();
+        let db = DB::new(open_db(temp_dir.path()));
+        let keys = KeyPair::Ed25519(Ed25519KeyPair::from_seed(&[]));
+        let subject_id = Event::default().event_content.subject_id;
+        // More than 10 events so the lexicographic key order differs from the SN order
+        for event in create_event_chain(&keys, &subject_id, 12) {
+            db.set_event(&subject_id, event).unwrap();
+        }
+        assert_eq!(db.verify_event_chain(&subject_id), Ok(()));
+    }
+
+    #[test]
+    fn test_has_event_and_event_count() {
+        let temp_dir = TempDir::new("test_has_event_and_event_count").unwrap();
+        let db = DB::new(open_db(temp_dir.path()));
+        let keys = KeyPair::Ed25519(Ed25519KeyPair::from_seed(&[]));
+        let subject_id = Event::default().event_content.subject_id;
+        for event in create_event_chain(&keys, &subject_id, 3) {
+            db.set_event(&subject_id, event).unwrap();
+        }
+        assert!(db.has_event(&subject_id, 0).unwrap());
+        assert!(db.has_event(&subject_id, 2).unwrap());
+        assert!(!db.has_event(&subject_id, 3).unwrap());
+        assert_eq!(3, db.event_count(&subject_id));
+
+        let other_id = DigestIdentifier::from_serializable_borsh("other").unwrap();
+        assert!(!db.has_event(&other_id, 0).unwrap());
+        assert_eq!(0, db.event_count(&other_id));
+    }
+
+    #[test]
+    fn test_flush() {
+        let temp_dir = TempDir::new("test_flush").unwrap();
+        let keys = KeyPair::Ed25519(Ed25519KeyPair::from_seed(&[]));
+        let subject_id = DigestIdentifier::from_serializable_borsh("subject").unwrap();
+        let events = create_event_chain(&keys, &subject_id, 10);
+        {
+            let db = DB::new(open_db(temp_dir.path()));
+            db.set_sync(false);
+            db.set_events(&subject_id, events.clone()).unwrap();
+            db.flush().unwrap();
+            assert!(db.sync_marker_db.get("last-flush").is_ok());
+            // Flushing does not make the following writes synchronous
+            assert!(!db.event_db.is_sync());
+        }
+
+        let db = DB::new(open_db(temp_dir.path()));
+        assert_eq!(10, db.event_count(&subject_id));
+        assert_eq!(
+            Some(events[9].clone()),
+            db.get_event(&subject_id, 9).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_bulk_import_session() {
+        let temp_dir = TempDir::new("test_bulk_import_session").unwrap();
+        let keys = KeyPair::Ed25519(Ed25519KeyPair::from_seed(&[]));
+        let subject_id = DigestIdentifier::from_serializable_borsh("subject").unwrap();
+        let events = create_event_chain(&keys, &subject_id, 201);
+        {
+            let db = DB::new(open_db(temp_dir.path()));
+            let session = db.bulk_import_session();
+            assert!(!db.event_db.is_sync());
+            for event in events[..200].iter() {
+                db.set_event(&subject_id, event.clone()).unwrap();
+            }
+            session.commit().unwrap();
+            assert!(db.event_db.is_sync());
+            assert!(db.sync_marker_db.get("last-flush").is_ok());
+        }
+        // Dropping the session commits it too
+        {
+            let db = DB::new(open_db(temp_dir.path()));
+            let _session = db.bulk_import_session();
+            db.set_event(&subject_id, events[200].clone()).unwrap();
+        }
+
+        // Everything is there once the database is opened again
+        let db = DB::new(open_db(temp_dir.path()));
+        assert!(db.event_db.is_sync());
+        assert_eq!(201, db.event_count(&subject_id));
+        assert!(db.verify_event_chain(&subject_id).is_ok());
+    }
+
+    #[test]
+    fn test_get_all_signatures() {
+        let temp_dir = TempDir::new("test_get_all_signatures").unwrap();
+        let db = DB::new(open_db(temp_dir.path()));
+        let keys = KeyPair::Ed25519(Ed25519KeyPair::from_seed(&[]));
+        let subject_id = DigestIdentifier::from_serializable_borsh("subject").unwrap();
+        let other_id = DigestIdentifier::from_serializable_borsh("other").unwrap();
+        assert!(db.get_all_signatures(&subject_id).is_empty());
+
+        let mut expected = BTreeMap::new();
+        // Enough SNs for their string order to differ from their numeric order
+        for sn in 0..12u64 {
+            let signatures = HashSet::from([sign(&keys, sn, sn as i64)]);
+            db.set_signatures(&subject_id, sn, signatures.clone())
+                .unwrap();
+            expected.insert(sn, signatures);
+        }
+        db.set_signatures(&other_id, 0, HashSet::from([sign(&keys, 99u64, 0)]))
+            .unwrap();
+
+        let all_signatures = db.get_all_signatures(&subject_id);
+        assert_eq!(expected, all_signatures);
+        assert_eq!(
+            (0..12).collect::<Vec<u64>>(),
+            all_signatures.keys().copied().collect::<Vec<u64>>()
+        );
+        assert_eq!(1, db.get_all_signatures(&other_id).len());
+    }
+
+    #[test]
+    fn test_prune_events() {
+        let temp_dir = TempDir::new("test_prune_events").unwrap();
+        let db = DB::new(open_db(temp_dir.path()));
+        let keys = KeyPair::Ed25519(Ed25519KeyPair::from_seed(&[]));
+        let subject_id = Event::default().event_content.subject_id;
+        // 12 events, so the SN order differs from the order of the keys
+        let events = create_event_chain(&keys, &subject_id, 12);
+        for event in events.clone() {
+            let sn = event.event_content.sn;
+            db.set_event(&subject_id, event).unwrap();
+            let signature = sign(&keys, sn, 0);
+            db.set_signatures(&subject_id, sn, HashSet::from([signature]))
+                .unwrap();
+        }
+        assert_eq!(8, db.prune_events(&subject_id, 3).unwrap());
+        assert_eq!(4, db.event_count(&subject_id));
+        for sn in [0, 9, 10, 11] {
+            assert!(db.has_event(&subject_id, sn).unwrap());
+            assert_eq!(1, db.get_signatures_count(&subject_id, sn));
+        }
+        for sn in 1..9 {
+            assert!(!db.has_event(&subject_id, sn).unwrap());
+            assert!(db.get_signatures(&subject_id, sn).is_none());
+        }
+        // Nothing else is left to prune
+        assert_eq!(0, db.prune_events(&subject_id, 3).unwrap());
+        for (sn, event) in events.iter().enumerate() {
+            let hash = &event.signature.content.event_content_hash;
+            let indexed = db.event_hash_index_db.exists(&hash.to_str()).unwrap();
+            assert_eq!(sn == 0 || sn >= 9, indexed);
+        }
+        assert_eq!(Ok(()), db.verify_event_chain(&subject_id));
+        // The head is kept, as the next event chains to it
+        assert_eq!(2, db.prune_events(&subject_id, 0).unwrap());
+        assert!(db.has_event(&subject_id, 0).unwrap());
+        assert!(db.has_event(&subject_id, 11).unwrap());
+        assert_eq!(2, db.event_count(&subject_id));
+        assert_eq!(Ok(()), db.verify_event_chain(&subject_id));
+    }
+
+    #[test]
+    fn test_get_event_before() {
+        let temp_dir = TempDir::new("test_get_event_before").unwrap();
+        let db = DB::new(open_db(temp_dir.path()));
+        let keys = KeyPair::Ed25519(Ed25519KeyPair::from_seed(&[]));
+        let subject_id = Event::default().event_content.subject_id;
+        let events = create_event_chain(&keys, &subject_id, 12);
+        for event in events.iter() {
+            db.set_event(&subject_id, event.clone()).unwrap();
+        }
+        // "9" sorts after "10" as a key
+        assert_eq!(
+            Some(events[9].clone()),
+            db.get_event_before(&subject_id, 10).unwrap()
+        );
+        assert_eq!(
+            Some(events[10].clone()),
+            db.get_event_before(&subject_id, 11).unwrap()
+        );
+        assert_eq!(None, db.get_event_before(&subject_id, 0).unwrap());
+        assert_eq!(None, db.get_event_before(&subject_id, 13).unwrap());
+    }
+
+    #[test]
+    fn test_get_event_by_hash() {
+        let temp_dir = TempDir::new("test_get_event_by_hash").unwrap();
+        let db = DB::new(open_db(temp_dir.path()));
+        let keys = KeyPair::Ed25519(Ed25519KeyPair::from_seed(&[]));
+        let subject_id = Event::default().event_content.subject_id;
+        let other_id = DigestIdentifier::from_serializable_borsh("other").unwrap();
+        let events = create_event_chain(&keys, &subject_id, 4);
+        let other_events = create_event_chain(&keys, &other_id, 2);
+        db.set_events(&subject_id, events[..3].to_vec()).unwrap();
+        db.set_event(&subject_id, events[3].clone()).unwrap();
+        db.set_events(&other_id, other_events.clone()).unwrap();
+        let hash = |event: &Event| event.signature.content.event_content_hash.clone();
+        for event in events.iter().chain(other_events.iter()) {
+            assert_eq!(
+                Some(event.clone()),
+                db.get_event_by_hash(&hash(event)).unwrap()
+            );
+        }
+        // The previous hash of an event leads to the event before it
+        assert_eq!(
+            Some(events[1].clone()),
+            db.get_event_by_hash(&events[2].event_content.previous_hash)
+                .unwrap()
+        );
+        let unknown = DigestIdentifier::from_serializable_borsh("unknown").unwrap();
+        assert_eq!(None, db.get_event_by_hash(&unknown).unwrap());
+
+        // Replaced and pruned events can no longer be found
+        let replacement = create_event(&keys, &subject_id, 3, hash(&events[1]));
+        db.set_event(&subject_id, replacement.clone()).unwrap();
+        assert_eq!(None, db.get_event_by_hash(&hash(&events[3])).unwrap());
+        assert_eq!(
+            Some(replacement.clone()),
+            db.get_event_by_hash(&hash(&replacement)).unwrap()
+        );
+        db.prune_events(&subject_id, 1).unwrap();
+        assert_eq!(None, db.get_event_by_hash(&hash(&events[1])).unwrap());
+        assert_eq!(
+            Some(events[0].clone()),
+            db.get_event_by_hash(&hash(&events[0])).unwrap()
+        );
+        assert!(db
+            .event_hash_index_db
+            .get(&hash(&events[2]).to_str())
+            .is_err());
+    }
+
+    #[test]
+    fn test_verify_event_chain_gap() {
+        let temp_dir = TempDir::new("test_verify_event_chain_gap").unwrap();
+        let db = DB::new(open_db(temp_dir.path()));
+        let keys = KeyPair::Ed25519(Ed25519KeyPair::from_seed(&[]));
+        let subject_id = Event::default().event_content.subject_id;
+        for event in create_event_chain(&keys, &subject_id, 4) {
+            if event.event_content.sn != 2 {
+                db.set_event(&subject_id, event).unwrap();
+            }
+        }
+        assert_eq!(
+            db.verify_event_chain(&subject_id),
+            Err(ChainError::MissingEvent(2))
+        );
+    }
+
+    #[test]
+    fn test_verify_event_chain_hash_mismatch() {
+        let temp_dir = TempDir::new("test_verify_event_chain_hash_mismatch").unwrap();
+        let db = DB::new(open_db(temp_dir.path()));
+        let keys = KeyPair::Ed25519(Ed25519KeyPair::from_seed(&[]));
+        let subject_id = Event::default().event_content.subject_id;
+        let mut events = create_event_chain(&keys, &subject_id, 4);
+        events[2] = create_event(&keys, &subject_id, 2, DigestIdentifier::default());
+        for event in events {
+            db.set_event(&subject_id, event).unwrap();
+        }
+        assert_eq!(
+            db.verify_event_chain(&subject_id),
+            Err(ChainError::PreviousHashMismatch(2))
+        );
+    }
+
+    #[test]
+    fn test_set_signatures_dedupe_by_signer() {
+        let temp_dir = TempDir::new("test_set_signatures_dedupe_by_signer").unwrap();
+        let db = DB::new(open_db(temp_dir.path()));
+        let keys = KeyPair::Ed25519(Ed25519KeyPair::from_seed(&[]));
+        let other_keys = KeyPair::Ed25519(Ed25519KeyPair::from_seed(&[1]));
+        let subject_id = Event::default().event_content.subject_id;
+        let old_signature = sign(&keys, "old content", 0);
+        let new_signature = sign(&keys, "new content", 1);
+        let other_signature = sign(&other_keys, "old content", 0);
+        assert_ne!(old_signature, new_signature);
+        db.set_signatures(
+            &subject_id,
+            1,
+            HashSet::from([old_signature, other_signature.clone()]),
+        )
+        .unwrap();
+        db.set_signatures(&subject_id, 1, HashSet::from([new_signature.clone()]))
+            .unwrap();
+        let signatures = db.get_signatures(&subject_id, 1).unwrap();
+        assert_eq!(signatures, HashSet::from([new_signature, other_signature]));
+    }
+
+    #[test]
+    fn test_get_signatures_count() {
+        let temp_dir = TempDir::new("test_get_signatures_count").unwrap();
+        let db = DB::new(open_db(temp_dir.path()));
+        let subject_id = Event::default().event_content.subject_id;
+        let signatures: HashSet<Signature> = (0..3u8)
+            .map(|seed| {
+                let keys = KeyPair::Ed25519(Ed25519KeyPair::from_seed(&[seed]));
+                sign(&keys, "content", 0)
+            })
+            .collect();
+        db.set_signatures(&subject_id, 1, signatures).unwrap();
+        assert_eq!(db.get_signatures_count(&subject_id, 1), 3);
+        assert_eq!(db.get_signatures_count(&subject_id, 2), 0);
+    }
+
+    #[test]
+    fn test_corrupted_entries_return_error() {
+        let temp_dir = TempDir::new("test_corrupted_entries_return_error").unwrap();
+        let pre_db = open_db(temp_dir.path());
+        let db = DB::new(pre_db.clone());
+        let subject_id = Event::default().event_content.subject_id;
+        // Store values that can not be decoded as the types expected by DB
+        let raw_subjects = WrapperLevelDB::<StringKey, String>::new(pre_db.clone(), SUBJECT_TABLE);
+        raw_subjects
+            .put(&subject_id.to_str(), "corrupted".into())
+            .unwrap();
+        let raw_signatures =
+            WrapperLevelDB::<StringKey, String>::new(pre_db.clone(), SIGNATURE_TABLE);
+        raw_signatures
+            .partition(&subject_id.to_str())
+            .put("1", "corrupted".into())
+            .unwrap();
+        assert!(matches!(
+            db.set_negociating_true(&subject_id),
+            Err(DbError::DatabaseError(
+                WrapperLevelDBErrors::DeserializeError
+            ))
+        ));
+        assert!(matches!(
+            db.set_signatures(&subject_id, 1, HashSet::new()),
+            Err(DbError::DatabaseError(
+                WrapperLevelDBErrors::DeserializeError
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_get_subjects_by_namespace() {
+        let temp_dir = TempDir::new("test_get_subjects_by_namespace").unwrap();
+        let db = DB::new(open_db(temp_dir.path()));
+        let keys = KeyPair::Ed25519(Ed25519KeyPair::from_seed(&[]));
+        let subject_a1 = create_subject(&keys, "a1", "namespace_a");
+        let subject_a2 = create_subject(&keys, "a2", "namespace_a");
+        let subject_b1 = create_subject(&keys, "b1", "namespace_b");
+        let empty_subject = Subject::new_empty(LedgerState::default());
+        for subject in [&subject_a1, &subject_a2, &subject_b1] {
+            let subject_id = subject.subject_data.as_ref().unwrap().subject_id.clone();
+            db.set_subject(&subject_id, subject.clone()).unwrap();
+        }
+        db.set_subject(&DigestIdentifier::default(), empty_subject)
+            .unwrap();
+        assert_eq!(
+            subject_ids(db.get_subjects_by_namespace("namespace_a")),
+            subject_ids(vec![subject_a1.clone(), subject_a2])
+        );
+        assert_eq!(
+            subject_ids(db.get_subjects_by_namespace("namespace_b")),
+            subject_ids(vec![subject_b1.clone()])
+        );
+        assert!(db.get_subjects_by_namespace("namespace").is_empty());
+        // Updating a subject in the same namespace does not duplicate it
+        let mut updated_b1 = subject_b1.clone();
+        updated_b1.subject_data.as_mut().unwrap().sn = 1;
+        let subject_id = subject_b1.subject_data.unwrap().subject_id;
+        db.set_subject(&subject_id, updated_b1).unwrap();
+        let subjects_b = db.get_subjects_by_namespace("namespace_b");
+        assert_eq!(subjects_b.len(), 1);
+        assert_eq!(subjects_b[0].subject_data.as_ref().unwrap().sn, 1);
+    }
+
+    #[test]
+    fn test_get_subjects_by_prefix_namespace() {
+        let temp_dir = TempDir::new("test_get_subjects_by_prefix_namespace").unwrap();
+        let db = DB::new(open_db(temp_dir.path()));
+        let keys = KeyPair::Ed25519(Ed25519KeyPair::from_seed(&[]));
+        // "ns_a" sorts before the keys of "ns", whose separator comes last
+        let subject_ns = create_subject(&keys, "ns1", "ns");
+        let subject_ns_a = create_subject(&keys, "a1", "ns_a");
+        for subject in [&subject_ns, &subject_ns_a] {
+            let subject_id = subject.subject_data.as_ref().unwrap().subject_id.clone();
+            db.set_subject(&subject_id, subject.clone()).unwrap();
+        }
+        assert_eq!(
+            subject_ids(db.get_subjects_by_namespace("ns")),
+            subject_ids(vec![subject_ns])
+        );
+        assert_eq!(
+            subject_ids(db.get_subjects_by_namespace("ns_a")),
+            subject_ids(vec![subject_ns_a])
+        );
+        // A malformed subject id in the index is skipped
+        db.namespace_index_db
+            .partition("ns")
+            .put("malformed", "malformed".into())
+            .unwrap();
+        assert_eq!(1, db.get_subjects_by_namespace("ns").len());
+    }
+
+    #[test]
+    fn test_reindex_namespaces() {
+        let temp_dir = TempDir::new("test_reindex_namespaces").unwrap();
+        let leveldb = open_db(temp_dir.path());
+        let db = DB::new(leveldb.clone());
+        let keys = KeyPair::Ed25519(Ed25519KeyPair::from_seed(&[]));
+        let subject = create_subject(&keys, "a1", "namespace_a");
+        let subject_id = subject.subject_data.as_ref().unwrap().subject_id.clone();
+        // A subject stored before the index existed
+        db.sync_marker_db.del(NAMESPACE_INDEX_MARKER).unwrap();
+        db.subject_db
+            .put(&subject_id.to_str(), subject.clone())
+            .unwrap();
+        assert_eq!(0, db.namespace_index_db.get_all_raw().len());
+        assert_eq!(
+            subject_ids(db.get_subjects_by_namespace("namespace_a")),
+            subject_ids(vec![subject.clone()])
+        );
+        assert_eq!(
+            HashSet::from(["namespace_a".to_owned()]),
+            db.list_namespaces()
+        );
+
+        // Opening the database again indexes it
+        let db = DB::new(leveldb);
+        assert!(db.namespace_index_complete());
+        assert_eq!(1, db.namespace_index_db.get_all_raw().len());
+        assert_eq!(
+            subject_ids(db.get_subjects_by_namespace("namespace_a")),
+            subject_ids(vec![subject.clone()])
+        );
+
+        // Storing it again without changing its namespace indexes it too
+        db.namespace_index_db
+            .partition("namespace_a")
+            .del(&subject_id.to_str())
+            .unwrap();
+        db.set_subject(&subject_id, subject.clone()).unwrap();
+        assert_eq!(
+            subject_ids(db.get_subjects_by_namespace("namespace_a")),
+            subject_ids(vec![subject])
+        );
+    }
+
+    #[test]
+    fn test_list_namespaces() {
+        let temp_dir = TempDir::new("test_list_namespaces").unwrap();
+        let db = DB::new(open_db(temp_dir.path()));
+        assert!(db.list_namespaces().is_empty());
+        let keys = KeyPair::Ed25519(Ed25519KeyPair::from_seed(&[]));
+        let subjects = [
+            create_subject(&keys, "a1", "namespace_a"),
+            create_subject(&keys, "a2", "namespace_a"),
+            create_subject(&keys, "b1", "namespace_b"),
+            create_subject(&keys, "c1", "namespace_c"),
+            create_subject(&keys, "c2", "namespace_c"),
+        ];
+        for subject in subjects.iter() {
+            let subject_id = subject.subject_data.as_ref().unwrap().subject_id.clone();
+            db.set_subject(&subject_id, subject.clone()).unwrap();
+        }
+        let expected = HashSet::from([
+            "namespace_a".to_owned(),
+            "namespace_b".to_owned(),
+            "namespace_c".to_owned(),
+        ]);
+        assert_eq!(expected, db.list_namespaces());
+
+        // Without the index the subjects are scanned
+        for (key, _) in db.namespace_index_db.get_all_raw() {
+            let (namespace, id) = key.rsplit_once(char::MAX).unwrap();
+            db.namespace_index_db.partition(namespace).del(id).unwrap();
+        }
+        assert_eq!(expected, db.list_namespaces());
+    }
+
+    /// Sink whose contents can still be read after being moved into a `DB`.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_oplog() {
+        let temp_dir = TempDir::new("test_oplog").unwrap();
+        let buffer = SharedBuffer::default();
+        let db = DB::new(open_db(temp_dir.path())).with_oplog(buffer.clone());
+        let keys = KeyPair::Ed25519(Ed25519KeyPair::from_seed(&[]));
+        let subject = create_subject(&keys, "subject", "namespace");
+        let subject_id = subject.subject_data.as_ref().unwrap().subject_id.clone();
+        let events = create_event_chain(&keys, &subject_id, 2);
+        db.set_subject(&subject_id, subject).unwrap();
+        db.set_event(&subject_id, events[1].clone()).unwrap();
+        db.set_negociating_true(&subject_id).unwrap();
+        // Failed mutations are not logged
+        assert!(db
+            .set_negociating_true(&DigestIdentifier::default())
+            .is_err());
+
+        let entries: Vec<OpLogEntry> =
+            serde_json::Deserializer::from_slice(&buffer.0.lock().unwrap())
+                .into_iter()
+                .map(Result::unwrap)
+                .collect();
+        let logged: Vec<(&str, Option<&DigestIdentifier>, Option<u64>)> = entries
+            .iter()
+            .map(|entry| {
+                (
+                    entry.operation.as_str(),
+                    entry.subject_id.as_ref(),
+                    entry.sn,
+                )
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                ("set_subject", Some(&subject_id), Some(0)),
+                ("set_event", Some(&subject_id), Some(1)),
+                ("set_negociating_true", Some(&subject_id), None),
+            ],
+            logged
+        );
+        assert!(entries[0].timestamp <= entries[2].timestamp);
+    }
+
+    #[test]
+    fn test_set_subject_if_sn() {
+        let temp_dir = TempDir::new("test_set_subject_if_sn").unwrap();
+        let db = DB::new(open_db(temp_dir.path()));
+        let keys = KeyPair::Ed25519(Ed25519KeyPair::from_seed(&[]));
+        let subject = create_subject(&keys, "subject", "namespace");
+        let subject_id = subject.subject_data.as_ref().unwrap().subject_id.clone();
+        assert!(matches!(
+            db.set_subject_if_sn(&subject_id, subject.clone(), 0),
+            Err(ConflictError::SubjectNotFound)
+        ));
+        db.set_subject(&subject_id, subject.clone()).unwrap();
+        db.set_negociating_true(&subject_id).unwrap();
+        assert!(db.subject_locks.lock().unwrap().is_empty());
+
+        // Both writers expect SN 0, so only the first one to take the lock succeeds
+        let results: Vec<Result<(), ConflictError>> = std::thread::scope(|scope| {
+            let writers: Vec<_> = (1..=2)
+                .map(|sn| {
+                    let mut next = subject.clone();
+                    next.subject_data.as_mut().unwrap().sn = sn;
+                    let (db, subject_id) = (&db, &subject_id);
+                    scope.spawn(move || db.set_subject_if_sn(subject_id, next, 0))
+                })
+                .collect();
+            writers
+                .into_iter()
+                .map(|writer| writer.join().unwrap())
+                .collect()
+        });
+        assert_eq!(1, results.iter().filter(|result| result.is_ok()).count());
+        let stored_sn = db.get_subject(&subject_id).unwrap().get_sn();
+        assert!(results.iter().any(|result| matches!(
+            result,
+            Err(ConflictError::SnMismatch { expected: 0, current: Some(sn) }) if *sn == stored_sn
+        )));
+        assert!(db.subject_locks.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_iter_event_contents() {
+        let temp_dir = TempDir::new("test_iter_event_contents").unwrap();
+        let db = DB::new(open_db(temp_dir.path()));
+        let keys = KeyPair::Ed25519(Ed25519KeyPair::from_seed(&[]));
+        let schema = serde_json::json!({"type": "object"});
+        let payloads: Vec<String> = (0..12).map(|i| format!("{{\"value\":{}}}", i)).collect();
+        let (subject, events) = create_subject_history(&keys, &payloads, &schema);
+        let subject_data = subject.subject_data.unwrap();
+        for event in events {
+            db.set_event(&subject_data.subject_id, event).unwrap();
+        }
+        // Replay the stored history to rebuild the subject
+        let mut event_contents = db.iter_event_contents(&subject_data.subject_id);
+        let mut rebuilt = Subject::new(
+            &event_contents.next().unwrap(),
+            subject_data.public_key.clone(),
+            None,
+            &schema,
+        )
+        .unwrap();
+        for event_content in event_contents {
+            rebuilt.apply(event_content).unwrap();
+        }
+        assert_eq!(rebuilt.subject_data.unwrap(), subject_data);
+        assert_eq!(
+            db.iter_event_contents(&DigestIdentifier::default()).count(),
+            0
+        );
+    }
+
+    /// Opens the database at `db_path` and runs `operation` with a crash armed after
+    /// `entries` entries are added to a batch. The database is closed as the crash
+    /// would leave it, so it can be opened again to look for torn writes. Returns
+    /// whether the operation crashed.
+    fn simulate_crash<F: FnOnce(&DB)>(db_path: &Path, entries: usize, operation: F) -> bool {
+        let db = DB::new(open_db(db_path));
+        failpoint::crash_after(entries);
+        let crashed = std::panic::catch_unwind(AssertUnwindSafe(|| operation(&db))).is_err();
+        failpoint::disarm();
+        drop(db);
+        crashed
+    }
+
+    #[test]
+    fn test_set_events_crash_recovery() {
+        let temp_dir = TempDir::new("test_set_events_crash_recovery").unwrap();
+        let keys = KeyPair::Ed25519(Ed25519KeyPair::from_seed(&[]));
+        let subject_id = DigestIdentifier::from_serializable_borsh("subject").unwrap();
+        let events = create_event_chain(&keys, &subject_id, 8);
+        {
+            let db = DB::new(open_db(temp_dir.path()));
+            db.set_events(&subject_id, events[..3].to_vec()).unwrap();
+        }
+
+        // Each event adds its hash index entry and itself to the batch
+        for entries in [0, 1, 2, 5, 9] {
+            assert!(simulate_crash(temp_dir.path(), entries, |db| {
+                db.set_events(&subject_id, events[3..].to_vec()).unwrap();
+            }));
+            let db = DB::new(open_db(temp_dir.path()));
+            assert_eq!(3, db.event_count(&subject_id));
+            assert_eq!(
+                events[..3].to_vec(),
+                db.get_events_between(&subject_id, 0, u64::MAX)
+            );
+            for event in events[3..].iter() {
+                let hash = &event.signature.content.event_content_hash;
+                assert!(!db.event_hash_index_db.exists(&hash.to_str()).unwrap());
+            }
+            assert_eq!(Ok(()), db.verify_event_chain(&subject_id));
+        }
+
+        // A crash once the batch is written can not undo any of it
+        assert!(simulate_crash(temp_dir.path(), 10, |db| {
+            failpoint::crash_after_write();
+            db.set_events(&subject_id, events[3..].to_vec()).unwrap();
+        }));
+        {
+            let db = DB::new(open_db(temp_dir.path()));
+            assert_eq!(events, db.get_events_between(&subject_id, 0, u64::MAX));
+            for event in events[3..].iter() {
+                let hash = &event.signature.content.event_content_hash;
+                assert_eq!(Some(event.clone()), db.get_event_by_hash(hash).unwrap());
+            }
+            assert_eq!(Ok(()), db.verify_event_chain(&subject_id));
+        }
+
+        // With room for every entry the batch is written whole
+        assert!(!simulate_crash(temp_dir.path(), 10, |db| {
+            db.set_events(&subject_id, events[3..].to_vec()).unwrap();
+        }));
+        let db = DB::new(open_db(temp_dir.path()));
+        assert_eq!(events, db.get_events_between(&subject_id, 0, u64::MAX));
+        assert_eq!(
+            Some(events[7].clone()),
+            db.get_event_by_hash(&events[7].signature.content.event_content_hash)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_table_metrics() {
+        let temp_dir = TempDir::new("test_table_metrics").unwrap();
+        let db = DB::new(open_db(temp_dir.path())).with_metrics();
+        let keys = KeyPair::Ed25519(Ed25519KeyPair::from_seed(&[]));
+        let subject_id = DigestIdentifier::from_serializable_borsh("subject").unwrap();
+        let event = create_event_chain(&keys, &subject_id, 1).remove(0);
+        // The event it replaces is read to update the hash index
+        db.set_event(&subject_id, event).unwrap();
+        assert!(db.get_event(&subject_id, 0).unwrap().is_some());
+        assert_eq!(1, db.event_count(&subject_id));
+
+        let metrics = db.table_metrics();
+        assert_eq!(
+            TableMetrics {
+                gets: 2,
+                puts: 1,
+                dels: 0,
+                scans: 1,
+            },
+            metrics[EVENT_TABLE]
+        );
+        assert_eq!(1, metrics[EVENT_HASH_INDEX_TABLE].puts);
+        assert_eq!(TableMetrics::default(), metrics[SUBJECT_TABLE]);
+        let total = metrics
+            .into_values()
+            .fold(TableMetrics::default(), |total, table| total + table);
+        assert_eq!(2, total.puts);
+
+        let temp_dir = TempDir::new("test_table_metrics_disabled").unwrap();
+        let db = DB::new(open_db(temp_dir.path()));
+        db.set_event(&subject_id, Event::default()).unwrap();
+        assert!(db
+            .table_metrics()
+            .values()
+            .all(|table| *table == TableMetrics::default()));
+    }
+
+    #[test]
+    fn test_get_genesis_event() {
+        let temp_dir = TempDir::new("test_get_genesis_event").unwrap();
+        let db = DB::new(open_db(temp_dir.path()));
+        let keys = KeyPair::Ed25519(Ed25519KeyPair::from_seed(&[]));
+        let schema = serde_json::json!({"type": "object"});
+        let payloads: Vec<String> = (0..2).map(|i| format!("{{\"value\":{}}}", i)).collect();
+        let (subject, events) = create_subject_history(&keys, &payloads, &schema);
+        let subject_id = subject.subject_data.unwrap().subject_id;
+        // Without SN 0 there is no genesis event
+        db.set_event(&subject_id, events[1].clone()).unwrap();
+        assert_eq!(None, db.get_genesis_event(&subject_id).unwrap());
+        db.set_event(&subject_id, events[0].clone()).unwrap();
+        assert_eq!(
+            Some(events[0].clone()),
+            db.get_genesis_event(&subject_id).unwrap()
+        );
+        assert_eq!(
+            None,
+            db.get_genesis_event(&DigestIdentifier::default()).unwrap()
+        );
+
+        // An SN 0 that is not a create event is not a genesis event
+        let other_id = DigestIdentifier::from_serializable_borsh("other").unwrap();
+        let chain = create_event_chain(&keys, &other_id, 1);
+        db.set_events(&other_id, chain.clone()).unwrap();
+        assert_eq!(Some(chain[0].clone()), db.get_event(&other_id, 0).unwrap());
+        assert_eq!(None, db.get_genesis_event(&other_id).unwrap());
+    }
+
+    #[test]
+    fn test_upgrade_untagged() {
+        let temp_dir = TempDir::new("test_upgrade_untagged").unwrap();
+        let db = DB::new(open_db(temp_dir.path()));
+        let keys = KeyPair::Ed25519(Ed25519KeyPair::from_seed(&[]));
+        let subject = create_subject(&keys, "subject", "namespace");
+        let subject_id = subject.subject_data.as_ref().unwrap().subject_id.clone();
+        db.set_subject(&subject_id, subject.clone()).unwrap();
+        db.subject_db
+            .put_raw(&subject_id.to_str(), &bincode::serialize(&subject).unwrap())
+            .unwrap();
+
+        assert_eq!(1, db.upgrade_untagged().unwrap());
+        assert_eq!(0, db.upgrade_untagged().unwrap());
+        let stored = db.get_subject(&subject_id).unwrap();
+        assert_eq!(stored.subject_data, subject.subject_data);
+    }
+
+    #[test]
+    fn test_rebuild_subject() {
+        let temp_dir = TempDir::new("test_rebuild_subject").unwrap();
+        let db = DB::new(open_db(temp_dir.path()));
+        let keys = KeyPair::Ed25519(Ed25519KeyPair::from_seed(&[]));
+        let schema = serde_json::json!({"type": "object"});
+        let payloads: Vec<String> = (0..4).map(|i| format!("{{\"value\":{}}}", i)).collect();
+        let (subject, events) = create_subject_history(&keys, &payloads, &schema);
+        let subject_id = subject.subject_data.as_ref().unwrap().subject_id.clone();
+        for event in events {
+            db.set_event(&subject_id, event).unwrap();
+        }
+        db.set_subject(&subject_id, subject.clone()).unwrap();
+        // The subject row is lost but its events survive
+        db.subject_db.del(&subject_id.to_str()).unwrap();
+        assert!(db.get_subject(&subject_id).is_none());
+
+        let rebuilt = db.rebuild_subject(&subject_id, &schema).unwrap();
+        assert_eq!(rebuilt.subject_data, subject.subject_data);
+        assert_eq!(rebuilt.ledger_state, subject.ledger_state);
+        let stored = db.get_subject(&subject_id).unwrap();
+        assert_eq!(stored.subject_data, subject.subject_data);
+
+        assert!(matches!(
+            db.rebuild_subject(&DigestIdentifier::default(), &schema),
+            Err(SubjectError::SubjectNotFound)
+        ));
+
+        // The keys and the ledger state of a stored subject are not lost
+        let mut stored = subject.clone();
+        stored.keys = Some(keys.clone());
+        stored.ledger_state.head_sn = Some(3);
+        stored.ledger_state.head_candidate_sn = Some(4);
+        db.set_subject(&subject_id, stored.clone()).unwrap();
+        db.rebuild_subject(&subject_id, &schema).unwrap();
+        let rebuilt = db.get_subject(&subject_id).unwrap();
+        assert_eq!(rebuilt.subject_data, subject.subject_data);
+        assert_eq!(rebuilt.ledger_state, stored.ledger_state);
+        assert_eq!(
+            rebuilt.keys.unwrap().secret_key_bytes(),
+            keys.secret_key_bytes()
+        );
+
+        // Once pruned, the history is not enough to rebuild the subject
+        assert_eq!(2, db.prune_events(&subject_id, 1).unwrap());
+        db.subject_db.del(&subject_id.to_str()).unwrap();
+        assert!(matches!(
+            db.rebuild_subject(&subject_id, &schema),
+            Err(SubjectError::HistoryPruned(2))
+        ));
+    }
+
+    #[test]
+    fn test_next_event_context() {
+        let temp_dir = TempDir::new("test_next_event_context").unwrap();
+        let db = DB::new(open_db(temp_dir.path()));
+        let keys = KeyPair::Ed25519(Ed25519KeyPair::from_seed(&[]));
+        let schema = serde_json::json!({"type": "object"});
+        let payloads: Vec<String> = (0..4).map(|i| format!("{{\"value\":{}}}", i)).collect();
+        let (subject, events) = create_subject_history(&keys, &payloads[..1], &schema);
+        let subject_id = subject.subject_data.as_ref().unwrap().subject_id.clone();
+        assert_eq!(
+            Err(SubjectError::SubjectNotFound),
+            db.next_event_context(&subject_id)
+        );
+        db.set_subject(&subject_id, subject).unwrap();
+        // The subject exists but its head event is missing
+        assert_eq!(
+            Err(SubjectError::EventAlreadyAppliedNotFound),
+            db.next_event_context(&subject_id)
+        );
+        db.set_event(&subject_id, events[0].clone()).unwrap();
+        assert_eq!(
+            Ok((1, events[0].signature.content.event_content_hash.clone())),
+            db.next_event_context(&subject_id)
+        );
+
+        let (subject, events) = create_subject_history(&keys, &payloads, &schema);
+        for event in events.iter() {
+            db.set_event(&subject_id, event.clone()).unwrap();
+        }
+        db.set_subject(&subject_id, subject).unwrap();
+        assert_eq!(
+            Ok((4, events[3].signature.content.event_content_hash.clone())),
+            db.next_event_context(&subject_id)
+        );
+    }
+
+    #[test]
+    fn test_get_events_page() {
+        let temp_dir = TempDir::new("test_get_events_page").unwrap();
+        let db = DB::new(open_db(temp_dir.path()));
+        let keys = KeyPair::Ed25519(Ed25519KeyPair::from_seed(&[]));
+        let subject_id = DigestIdentifier::from_serializable_borsh("subject").unwrap();
+        let events = create_event_chain(&keys, &subject_id, 5);
+        db.set_events(&subject_id, events.clone()).unwrap();
+
+        let first_page = db.get_events_page(&subject_id, None, 3).unwrap();
+        assert_eq!(events[..3].to_vec(), first_page.events);
+        assert_eq!(Some("3".to_owned()), first_page.next_cursor);
+        let second_page = db
+            .get_events_page(&subject_id, first_page.next_cursor, 3)
+            .unwrap();
+        assert_eq!(
+            EventPage {
+                events: events[3..].to_vec(),
+                next_cursor: None,
+            },
+            second_page
+        );
+        // A page that ends exactly at the last event has no next cursor
+        assert_eq!(
+            None,
+            db.get_events_page(&subject_id, None, 5)
+                .unwrap()
+                .next_cursor
+        );
+        assert_eq!(
+            EventPage {
+                events: vec![],
+                next_cursor: None,
+            },
+            db.get_events_page(&DigestIdentifier::default(), None, 3)
+                .unwrap()
+        );
+        assert!(matches!(
+            db.get_events_page(&subject_id, None, 0),
+            Err(DbError::EmptyPageLimit)
+        ));
+
+        // Pages follow the order of the keys, but every event is returned once
+        let events = create_event_chain(&keys, &subject_id, 12);
+        db.set_events(&subject_id, events.clone()).unwrap();
+        let mut paged = Vec::new();
+        let mut cursor = None;
+        loop {
+            let page = db.get_events_page(&subject_id, cursor, 5).unwrap();
+            paged.extend(page.events.into_iter().map(|event| event.event_content.sn));
+            cursor = page.next_cursor;
+            if cursor.is_none() {
+                break;
+            }
+        }
+        assert_eq!(vec![0, 1, 10, 11, 2, 3, 4, 5, 6, 7, 8, 9], paged);
+    }
+
+    #[test]
+    fn test_get_events_between() {
+        let temp_dir = TempDir::new("test_get_events_between").unwrap();
+        let db = DB::new(open_db(temp_dir.path()));
+        let keys = KeyPair::Ed25519(Ed25519KeyPair::from_seed(&[]));
+        let subject_id = DigestIdentifier::from_serializable_borsh("subject").unwrap();
+        let events = create_event_chain(&keys, &subject_id, 25);
+        db.set_events(&subject_id, events.clone()).unwrap();
+
+        // "10" to "19" are stored between "1" and "2"
+        assert_eq!(
+            events[5..=20].to_vec(),
+            db.get_events_between(&subject_id, 5, 20)
+        );
+        assert_eq!(
+            events[9..=11].to_vec(),
+            db.get_events_between(&subject_id, 9, 11)
+        );
+        assert_eq!(
+            events[20..].to_vec(),
+            db.get_events_between(&subject_id, 20, u64::MAX)
+        );
+        assert_eq!(
+            vec![events[2].clone()],
+            db.get_events_between(&subject_id, 2, 2)
+        );
+        assert!(db.get_events_between(&subject_id, 20, 5).is_empty());
+        assert!(db.get_events_between(&subject_id, 25, 30).is_empty());
+        assert!(db
+            .get_events_between(&DigestIdentifier::default(), 0, 5)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_audit_subject() {
+        let temp_dir = TempDir::new("test_audit_subject").unwrap();
+        let db = DB::new(open_db(temp_dir.path()));
+        let keys = KeyPair::Ed25519(Ed25519KeyPair::from_seed(&[]));
+        let schema = serde_json::json!({"type": "object"});
+        let payloads: Vec<String> = (0..3).map(|i| format!("{{\"value\":{}}}", i)).collect();
+        let (subject, events) = create_subject_history(&keys, &payloads, &schema);
+        let subject_id = subject.subject_data.as_ref().unwrap().subject_id.clone();
+        assert_eq!(
+            Err(AuditError::SubjectNotFound),
+            db.audit_subject(&subject_id)
+        );
+        db.set_events(&subject_id, events).unwrap();
+        db.set_subject(&subject_id, subject.clone()).unwrap();
+        assert_eq!(Ok(()), db.audit_subject(&subject_id));
+
+        // The head is advanced past the stored events
+        let mut advanced = subject.clone();
+        advanced.ledger_state.head_sn = Some(3);
+        db.set_subject(&subject_id, advanced).unwrap();
+        assert_eq!(
+            Err(AuditError::HeadSnMismatch {
+                head_sn: Some(3),
+                stored_sn: Some(2),
+            }),
+            db.audit_subject(&subject_id)
+        );
+
+        // The subject data is behind the head
+        let mut behind = subject;
+        behind.subject_data.as_mut().unwrap().sn = 1;
+        db.set_subject(&subject_id, behind).unwrap();
+        assert_eq!(
+            Err(AuditError::SubjectSnMismatch {
+                subject_sn: 1,
+                head_sn: Some(2),
+            }),
+            db.audit_subject(&subject_id)
+        );
+    }
+
+    #[test]
+    fn test_set_events() {
+        let temp_dir = TempDir::new("test_set_events").unwrap();
+        let db = DB::new(open_db(temp_dir.path()));
+        let keys = KeyPair::Ed25519(Ed25519KeyPair::from_seed(&[]));
+        let subject_id = DigestIdentifier::from_serializable_borsh("subject").unwrap();
+        let events = create_event_chain(&keys, &subject_id, 5);
+
+        db.set_events(&subject_id, events[..3].to_vec()).unwrap();
+        assert_eq!(3, db.event_count(&subject_id));
+        assert!(db.verify_event_chain(&subject_id).is_ok());
+
+        // SN 3 is missing, so nothing is written
+        let batch = vec![events[2].clone(), events[4].clone()];
+        assert!(matches!(
+            db.set_events(&subject_id, batch),
+            Err(DbError::SubjectError(
+                SubjectError::EventSourcingNotInOrder(3, 4)
+            ))
+        ));
+        assert_eq!(3, db.event_count(&subject_id));
+        assert!(!db.has_event(&subject_id, 4).unwrap());
+
+        db.set_events(&subject_id, events[3..].to_vec()).unwrap();
+        assert_eq!(5, db.event_count(&subject_id));
+        assert!(db.verify_event_chain(&subject_id).is_ok());
+    }
+
+    #[test]
+    fn test_blob_storage() {
+        let temp_dir = TempDir::new("test_blob_storage").unwrap();
+        let db = DB::new(open_db(temp_dir.path()));
+        let content = b"large content referenced by hash".to_vec();
+        let blob_id = db.put_blob(&content).unwrap();
+        assert_eq!(blob_id, db.put_blob(&content).unwrap());
+        assert_eq!(1, db.blob_db.get_count());
+        assert_eq!(Some(content), db.get_blob(&blob_id));
+
+        let other_id = db.put_blob(b"other content").unwrap();
+        assert_ne!(blob_id, other_id);
+        assert_eq!(2, db.blob_db.get_count());
+        assert_eq!(Some(b"other content".to_vec()), db.get_blob(&other_id));
+        assert_eq!(None, db.get_blob(&DigestIdentifier::default()));
+    }
+
+    #[test]
+    fn test_requests_by_subject() {
+        let temp_dir = TempDir::new("test_requests_by_subject").unwrap();
+        let db = DB::new(open_db(temp_dir.path()));
+        let keys = KeyPair::Ed25519(Ed25519KeyPair::from_seed(&[]));
+        let subject_a = DigestIdentifier::from_serializable_borsh("a").unwrap();
+        let subject_b = DigestIdentifier::from_serializable_borsh("b").unwrap();
+        let state_request = |subject_id: &DigestIdentifier, timestamp: i64| {
+            create_request(
+                &keys,
+                EventRequestType::State(StateRequest {
+                    subject_id: subject_id.clone(),
+                    payload: RequestPayload::Json("{}".into()),
+                }),
+                timestamp,
+            )
+        };
+        let requests_a: Vec<EventRequest> = (0..3).map(|i| state_request(&subject_a, i)).collect();
+        let request_b = state_request(&subject_b, 3);
+        for request in requests_a.iter() {
+            db.set_request(&subject_a, request.clone()).unwrap();
+        }
+        db.set_request(&subject_b, request_b.clone()).unwrap();
+
+        assert_eq!(3, db.request_count(&subject_a));
+        assert_eq!(1, db.request_count(&subject_b));
+        let request_hash =
+            |request: &EventRequest| request.signature.content.event_content_hash.clone();
+        let stored_a: HashSet<DigestIdentifier> = db
+            .get_requests(&subject_a)
+            .iter()
+            .map(request_hash)
+            .collect();
+        assert_eq!(
+            requests_a.iter().map(request_hash).collect::<HashSet<_>>(),
+            stored_a
+        );
+        assert_eq!(vec![request_b.clone()], db.get_requests(&subject_b));
+
+        db.del_request(&subject_b, &request_hash(&request_b));
+        assert_eq!(0, db.request_count(&subject_b));
+        assert!(db.get_requests(&subject_b).is_empty());
+        assert_eq!(3, db.request_count(&subject_a));
+    }
+
+    #[test]
+    #[traced_test]
+    fn test_failures_are_logged() {
+        let temp_dir = TempDir::new("test_failures_are_logged").unwrap();
+        let db = DB::new(open_db(temp_dir.path()));
+        let subject_id = DigestIdentifier::from_serializable_borsh("logged").unwrap();
+        let id = subject_id.to_str();
+
+        // An event that can not be deserialized
+        db.event_db.partition(&id).put_raw("0", &[]).unwrap();
+        // The error is returned instead of being taken as a missing event
+        assert!(matches!(
+            db.get_event(&subject_id, 0),
+            Err(DbError::DatabaseError(_))
+        ));
+        assert!(logs_contain(&format!(
+            "get_event{{sn=0 subject_id={}}}: commons::bd::db: Could not read event",
+            id
+        )));
+
+        // An index entry that points to an event that does not exist
+        let hash = DigestIdentifier::from_serializable_borsh("stale").unwrap();
+        db.event_hash_index_db
+            .put(&hash.to_str(), (subject_id.clone(), 7))
+            .unwrap();
+        assert_eq!(None, db.get_event_by_hash(&hash).unwrap());
+        assert!(logs_contain(&format!(
+            "get_event_by_hash{{hash={}}}: commons::bd::db: \
+             Event hash index points to an event which does not have the hash subject_id={} sn=7",
+            hash.to_str(),
+            id
+        )));
+    }
+
+    #[test]
+    fn test_approval_timeline() {
+        let temp_dir = TempDir::new("test_approval_timeline").unwrap();
+        let db = DB::new(open_db(temp_dir.path()));
+        let subject_id = DigestIdentifier::from_serializable_borsh("subject").unwrap();
+        let approvers: Vec<KeyIdentifier> = (0..3u8)
+            .map(|seed| {
+                let keys = KeyPair::Ed25519(Ed25519KeyPair::from_seed(&[seed]));
+                KeyIdentifier::new(keys.get_key_derivator(), &keys.public_key_bytes())
+            })
+            .collect();
+        assert!(db.get_approval_timeline(&subject_id, 1).is_empty());
+
+        db.set_approval_timestamp(&subject_id, 1, &approvers[0], Some(300))
+            .unwrap();
+        db.set_approval_timestamp(&subject_id, 1, &approvers[1], Some(100))
+            .unwrap();
+        db.set_approval_timestamp(&subject_id, 1, &approvers[2], Some(200))
+            .unwrap();
+        db.set_approval_timestamp(&subject_id, 2, &approvers[0], Some(50))
+            .unwrap();
+        assert_eq!(
+            vec![
+                (approvers[1].clone(), 100),
+                (approvers[2].clone(), 200),
+                (approvers[0].clone(), 300)
+            ],
+            db.get_approval_timeline(&subject_id, 1)
+        );
+        assert_eq!(
+            vec![(approvers[0].clone(), 50)],
+            db.get_approval_timeline(&subject_id, 2)
+        );
+
+        // Without a timestamp, the current time is recorded
+        let before = Utc::now().timestamp_millis();
+        db.set_approval_timestamp(&subject_id, 3, &approvers[0], None)
+            .unwrap();
+        let after = Utc::now().timestamp_millis();
+        let timeline = db.get_approval_timeline(&subject_id, 3);
+        assert_eq!(1, timeline.len());
+        assert!((before..=after).contains(&timeline[0].1));
+    }
+
+    #[test]
+    fn test_approval_timeline_prefix_sn() {
+        let temp_dir = TempDir::new("test_approval_timeline_prefix_sn").unwrap();
+        let db = DB::new(open_db(temp_dir.path()));
+        let subject_id = DigestIdentifier::from_serializable_borsh("subject").unwrap();
+        let keys = KeyPair::Ed25519(Ed25519KeyPair::from_seed(&[0]));
+        let approver = KeyIdentifier::new(keys.get_key_derivator(), &keys.public_key_bytes());
+        // The keys of SN 10 sort before the ones of SN 1
+        db.set_approval_timestamp(&subject_id, 1, &approver, Some(100))
+            .unwrap();
+        db.set_approval_timestamp(&subject_id, 10, &approver, Some(1000))
+            .unwrap();
+        assert_eq!(
+            vec![(approver.clone(), 100)],
+            db.get_approval_timeline(&subject_id, 1)
+        );
+        assert_eq!(
+            vec![(approver, 1000)],
+            db.get_approval_timeline(&subject_id, 10)
+        );
+    }
+
+    #[test]
+    fn test_get_subject_or_default() {
+        let temp_dir = TempDir::new("test_get_subject_or_default").unwrap();
+        let db = DB::new(open_db(temp_dir.path()));
+        let keys = KeyPair::Ed25519(Ed25519KeyPair::from_seed(&[]));
+        let stored = create_subject(&keys, "stored", "namespace");
+        let stored_id = stored.subject_data.as_ref().unwrap().subject_id.clone();
+        db.set_subject(&stored_id, stored.clone()).unwrap();
+        let default = create_subject(&keys, "default", "namespace");
+
+        assert_eq!(
+            stored,
+            db.get_subject_or_default(&stored_id, || default.clone())
+        );
+        let missing_id = DigestIdentifier::from_serializable_borsh("missing").unwrap();
+        assert_eq!(
+            default,
+            db.get_subject_or_default(&missing_id, || default.clone())
+        );
+        assert!(db.get_subject(&missing_id).is_none());
+        assert_eq!(1, db.subject_db.get_count());
+    }
+
+    #[test]
+    fn test_subject_cache() {
+        let temp_dir = TempDir::new("test_subject_cache").unwrap();
+        let db = DB::new(open_db(temp_dir.path())).with_subject_cache(2);
+        let keys = KeyPair::Ed25519(Ed25519KeyPair::from_seed(&[]));
+        let subject = create_subject(&keys, "cached", "namespace");
+        let subject_id = subject.subject_data.as_ref().unwrap().subject_id.clone();
+        db.set_subject(&subject_id, subject.clone()).unwrap();
+        assert_eq!(
+            subject.subject_data,
+            db.get_subject(&subject_id).unwrap().subject_data
+        );
+
+        // Out of band changes are not seen while the subject is cached
+        let mut modified = subject.clone();
+        modified.subject_data.as_mut().unwrap().sn = 5;
+        db.subject_db
+            .put(&subject_id.to_str(), modified.clone())
+            .unwrap();
+        assert_eq!(0, db.get_subject(&subject_id).unwrap().get_sn());
+
+        // Writing through the DB invalidates the cached value
+        modified.subject_data.as_mut().unwrap().sn = 6;
+        db.set_subject(&subject_id, modified).unwrap();
+        assert_eq!(6, db.get_subject(&subject_id).unwrap().get_sn());
+
+        // Least recently used subjects are evicted
+        for seed in ["other0", "other1"] {
+            let other = create_subject(&keys, seed, "namespace");
+            let other_id = other.subject_data.as_ref().unwrap().subject_id.clone();
+            db.set_subject(&other_id, other).unwrap();
+            db.get_subject(&other_id).unwrap();
+        }
+        let mut modified = subject.clone();
+        modified.subject_data.as_mut().unwrap().sn = 7;
+        db.subject_db.put(&subject_id.to_str(), modified).unwrap();
+        assert_eq!(7, db.get_subject(&subject_id).unwrap().get_sn());
+        assert!(db.get_subject(&DigestIdentifier::default()).is_none());
+    }
+
+    #[test]
+    fn test_get_all_governances() {
+        let temp_dir = TempDir::new("test_get_all_governances").unwrap();
+        let db = DB::new(open_db(temp_dir.path()));
+        let keys = KeyPair::Ed25519(Ed25519KeyPair::from_seed(&[]));
+        let governance = create_subject(&keys, "governance", "");
+        let governance_id = governance.subject_data.as_ref().unwrap().subject_id.clone();
+        db.set_subject(&governance_id, governance).unwrap();
+        let mut subject = create_subject(&keys, "subject", "");
+        subject.subject_data.as_mut().unwrap().governance_id = governance_id.clone();
+        let subject_id = subject.subject_data.as_ref().unwrap().subject_id.clone();
+        db.set_subject(&subject_id, subject).unwrap();
+
+        assert_eq!(2, db.get_all_subjects().len());
+        assert_eq!(
+            HashSet::from([governance_id]),
+            subject_ids(db.get_all_governances())
+        );
+    }
+
+    #[test]
+    fn test_controller_id() {
+        let temp_dir = TempDir::new("test_controller_id").unwrap();
+        let db = DB::new(open_db(temp_dir.path()));
+        assert_eq!(None, db.get_controller_id().unwrap());
+        let keys = KeyPair::Ed25519(Ed25519KeyPair::from_seed(&[]));
+        let controller_id = KeyIdentifier::new(keys.get_key_derivator(), &keys.public_key_bytes());
+        db.set_controller_id(&controller_id).unwrap();
+        assert_eq!(Some(controller_id), db.get_controller_id().unwrap());
+
+        db.id_db.put("", "controller".to_owned()).unwrap();
+        assert!(matches!(
+            db.get_controller_id(),
+            Err(DbError::InvalidControllerId(id)) if id == "controller"
+        ));
+    }
+
+    #[test]
+    fn test_rotate_controller() {
+        let temp_dir = TempDir::new("test_rotate_controller").unwrap();
+        let db = DB::new(open_db(temp_dir.path()));
+        let ids: Vec<KeyIdentifier> = (0..3u8)
+            .map(|seed| {
+                let keys = KeyPair::Ed25519(Ed25519KeyPair::from_seed(&[seed]));
+                KeyIdentifier::new(keys.get_key_derivator(), &keys.public_key_bytes())
+            })
+            .collect();
+        // There is nothing to record for the first controller id
+        assert_eq!(None, db.rotate_controller(&ids[0]).unwrap());
+        assert!(db.get_controller_history().unwrap().is_empty());
+        assert_eq!(Some(ids[0].clone()), db.rotate_controller(&ids[1]).unwrap());
+        assert_eq!(Some(ids[1].clone()), db.rotate_controller(&ids[2]).unwrap());
+        assert_eq!(Some(ids[2].clone()), db.get_controller_id().unwrap());
+        assert_eq!(ids[..2].to_vec(), db.get_controller_history().unwrap());
+    }
+
+    #[test]
+    fn test_get_all_heads_skips_malformed_keys() {
+        let temp_dir = TempDir::new("test_get_all_heads_skips_malformed_keys").unwrap();
+        let db = DB::new(open_db(temp_dir.path()));
+        let keys = KeyPair::Ed25519(Ed25519KeyPair::from_seed(&[]));
+        let subject = create_subject(&keys, "subject", "");
+        let subject_id = subject.subject_data.as_ref().unwrap().subject_id.clone();
+        db.set_subject(&subject_id, subject.clone()).unwrap();
+        db.subject_db.put("not a digest", subject).unwrap();
+
+        let heads = db.get_all_heads();
+        assert_eq!(1, heads.len());
+        assert!(heads.contains_key(&subject_id));
+    }
+
+    #[test]
+    fn test_get_all_heads_sorted() {
+        let temp_dir = TempDir::new("test_get_all_heads_sorted").unwrap();
+        let db = DB::new(open_db(temp_dir.path()));
+        let keys = KeyPair::Ed25519(Ed25519KeyPair::from_seed(&[]));
+        for seed in ["a", "b", "c", "d", "e"] {
+            let subject = create_subject(&keys, seed, "");
+            let subject_id = subject.subject_data.as_ref().unwrap().subject_id.clone();
+            db.set_subject(&subject_id, subject).unwrap();
+        }
+
+        let heads = db.get_all_heads_sorted();
+        assert_eq!(5, heads.len());
+        assert!(heads
+            .windows(2)
+            .all(|pair| pair[0].0.to_str() < pair[1].0.to_str()));
+        assert_eq!(heads, db.get_all_heads_sorted());
+        let map = db.get_all_heads();
+        assert!(heads.iter().all(|(id, state)| map.get(id) == Some(state)));
+    }
+
+    #[test]
+    fn test_export_import_subject() {
+        let keys = KeyPair::Ed25519(Ed25519KeyPair::from_seed(&[]));
+        let schema = serde_json::json!({"type": "object"});
+        let payloads: Vec<String> = (0..12).map(|i| format!("{{\"value\":{}}}", i)).collect();
+        let (subject, events) = create_subject_history(&keys, &payloads, &schema);
+        let subject_id = subject.subject_data.as_ref().unwrap().subject_id.clone();
+
+        let temp_dir = TempDir::new("test_export_subject").unwrap();
+        let db = DB::new(open_db(temp_dir.path()));
+        assert!(matches!(
+            db.export_subject(&subject_id),
+            Err(DbError::SubjectError(SubjectError::SubjectNotFound))
+        ));
+        for event in events.iter() {
+            db.set_event(&subject_id, event.clone()).unwrap();
+            db.set_signatures(
+                &subject_id,
+                event.event_content.sn,
+                HashSet::from([event.signature.clone()]),
+            )
+            .unwrap();
+        }
+        db.set_subject(&subject_id, subject).unwrap();
+        let other = create_subject(&keys, "other", "namespace");
+        let other_id = other.subject_data.as_ref().unwrap().subject_id.clone();
+        db.set_subject(&other_id, other).unwrap();
+
+        let bundle = db.export_subject(&subject_id).unwrap();
+        let sns: Vec<u64> = bundle
+            .events
+            .iter()
+            .map(|event| event.event_content.sn)
+            .collect();
+        assert_eq!((0..12).collect::<Vec<u64>>(), sns);
+        // The bundle is sent to the other node serialized
+        let bundle: SubjectBundle =
+            serde_json::from_slice(&serde_json::to_vec(&bundle).unwrap()).unwrap();
+
+        let temp_dir = TempDir::new("test_import_subject").unwrap();
+        let imported = DB::new(open_db(temp_dir.path())).with_subject_cache(4);
+        imported.import_subject(bundle).unwrap();
+        let (expected, subject) = (
+            db.get_subject(&subject_id).unwrap(),
+            imported.get_subject(&subject_id).unwrap(),
+        );
+        assert_eq!(expected.subject_data, subject.subject_data);
+        assert_eq!(expected.ledger_state, subject.ledger_state);
+        assert_eq!(
+            db.get_events_by_range(&subject_id, None, 100),
+            imported.get_events_by_range(&subject_id, None, 100)
+        );
+        assert_eq!(
+            db.get_all_signatures(&subject_id),
+            imported.get_all_signatures(&subject_id)
+        );
+        assert_eq!(
+            Some(events[3].clone()),
+            imported
+                .get_event_by_hash(&events[3].signature.content.event_content_hash)
+                .unwrap()
+        );
+        assert_eq!(1, imported.get_subjects_by_namespace("namespace").len());
+        assert!(imported.get_subject(&other_id).is_none());
+
+        // Events of other subjects are rejected without writing anything
+        let mut bundle = db.export_subject(&other_id).unwrap();
+        bundle.events = events.clone();
+        assert!(matches!(
+            imported.import_subject(bundle),
+            Err(DbError::MalformedDump(_))
+        ));
+        assert!(imported.get_subject(&other_id).is_none());
+    }
+
+    #[test]
+    fn test_export_import() {
+        let keys = KeyPair::Ed25519(Ed25519KeyPair::from_seed(&[]));
+        let schema = serde_json::json!({"type": "object"});
+        let payloads: Vec<String> = (0..3).map(|i| format!("{{\"value\":{}}}", i)).collect();
+        let (subject, events) = create_subject_history(&keys, &payloads, &schema);
+        let subject_id = subject.subject_data.as_ref().unwrap().subject_id.clone();
+
+        let temp_dir = TempDir::new("test_export").unwrap();
+        let db = DB::new(open_db(temp_dir.path()));
+        for event in events.iter() {
+            db.set_event(&subject_id, event.clone()).unwrap();
+            db.set_signatures(
+                &subject_id,
+                event.event_content.sn,
+                HashSet::from([event.signature.clone()]),
+            )
+            .unwrap();
+        }
+        db.set_subject(&subject_id, subject).unwrap();
+        db.set_request(&subject_id, events[1].event_content.event_request.clone())
+            .unwrap();
+        let controller_id = KeyIdentifier::new(keys.get_key_derivator(), &keys.public_key_bytes());
+        db.set_controller_id(&controller_id).unwrap();
+        db.rotate_controller(&controller_id).unwrap();
+        db.put_blob(b"blob").unwrap();
+        db.set_approval_timestamp(&subject_id, 1, &controller_id, None)
+            .unwrap();
+        db.bulk_import_session().commit().unwrap();
+        let mut dump = Vec::new();
+        db.export(&mut dump).unwrap();
+
+        let other_dir = TempDir::new("test_import").unwrap();
+        let imported = DB::new(open_db(other_dir.path()));
+        imported.import(&mut dump.as_slice()).unwrap();
+        let raw_tables = |db: &DB| {
+            vec![
+                db.signature_db.get_all_raw(),
+                db.subject_db.get_all_raw(),
+                db.event_db.get_all_raw(),
+                db.event_hash_index_db.get_all_raw(),
+                db.request_db.get_all_raw(),
+                db.id_db.get_all_raw(),
+                db.controller_history_db.get_all_raw(),
+                db.namespace_index_db.get_all_raw(),
+                db.blob_db.get_all_raw(),
+                db.approval_time_db.get_all_raw(),
+                db.sync_marker_db.get_all_raw(),
+            ]
+        };
+        assert!(raw_tables(&db).iter().all(|table| !table.is_empty()));
+        assert_eq!(raw_tables(&db), raw_tables(&imported));
+        assert_eq!(Some(controller_id), imported.get_controller_id().unwrap());
+        assert_eq!(1, imported.get_subjects_by_namespace("namespace").len());
+        assert!(imported.verify_event_chain(&subject_id).is_ok());
+
+        // A truncated dump is rejected
+        assert!(matches!(
+            imported.import(&mut &dump[..dump.len() - 1]),
+            Err(DbError::CorruptExport(_))
+        ));
+    }
+
+    #[test]
+    fn test_import_corrupt_export() {
+        let temp_dir = TempDir::new("test_corrupt_export").unwrap();
+        let db = DB::new(open_db(temp_dir.path()));
+        db.put_blob(b"blob").unwrap();
+        let mut dump = Vec::new();
+        db.export(&mut dump).unwrap();
+        assert!(dump.starts_with(DUMP_MAGIC));
+
+        let other_dir = TempDir::new("test_import_corrupt_export").unwrap();
+        let imported = DB::new(open_db(other_dir.path()));
+        // Every flipped byte is detected, be it in the header, the entries or the checksum
+        for position in [0, DUMP_MAGIC.len(), DUMP_MAGIC.len() + 6, dump.len() - 1] {
+            let mut corrupt = dump.clone();
+            corrupt[position] ^= 0x01;
+            assert!(matches!(
+                imported.import(&mut corrupt.as_slice()),
+                Err(DbError::CorruptExport(_))
+            ));
+        }
+        assert_eq!(0, imported.blob_db.get_count());
+        assert!(matches!(
+            imported.import(&mut &b""[..]),
+            Err(DbError::CorruptExport(_))
+        ));
+
+        imported.import(&mut dump.as_slice()).unwrap();
+        assert_eq!(db.blob_db.get_all_raw(), imported.blob_db.get_all_raw());
+    }
+
+    #[test]
+    fn test_import_malformed_dump() {
+        let temp_dir = TempDir::new("test_import_malformed_dump").unwrap();
+        let db = DB::new(open_db(temp_dir.path()));
+        let dump = |entries: &[&[u8]]| {
+            let mut dump = DUMP_MAGIC.to_vec();
+            dump.push(DUMP_VERSION);
+            let mut writer = ChecksumWriter {
+                inner: &mut dump,
+                crc: Crc32::new(),
+            };
+            for field in entries {
+                writer.write_all(field).unwrap();
+            }
+            let crc = writer.crc.finish();
+            dump.extend_from_slice(&crc.to_le_bytes());
+            dump
+        };
+        let mut blob = Vec::new();
+        for field in [BLOB_TABLE.as_bytes(), b"key", b"value"] {
+            write_dump_field(&mut blob, field).unwrap();
+        }
+        let mut unknown = Vec::new();
+        for field in [&b"unknown"[..], b"key", b"value"] {
+            write_dump_field(&mut unknown, field).unwrap();
+        }
+        // The valid entry before the unknown table is not written either
+        assert!(matches!(
+            db.import(&mut dump(&[&blob, &unknown]).as_slice()),
+            Err(DbError::MalformedDump(_))
+        ));
+        // A length beyond the end of the dump is rejected without allocating it
+        assert!(matches!(
+            db.import(&mut dump(&[&blob, &u32::MAX.to_le_bytes()]).as_slice()),
+            Err(DbError::MalformedDump(_))
+        ));
+        assert_eq!(0, db.blob_db.get_count());
+
+        db.import(&mut dump(&[&blob]).as_slice()).unwrap();
+        assert_eq!(b"value".to_vec(), db.blob_db.get_all_raw()[0].1);
+    }
+
+    #[test]
+    fn test_crc32() {
+        let mut crc = Crc32::new();
+        crc.update(b"123456789");
+        assert_eq!(0xCBF4_3926, crc.finish());
     }
 }
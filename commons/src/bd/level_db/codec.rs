@@ -0,0 +1,70 @@
+//! Encodings of the values stored by a `WrapperLevelDB`
+use bincode::Options;
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::error::WrapperLevelDBErrors;
+
+/// Tag of the values encoded with bincode 1 and its default configuration.
+pub(crate) const FORMAT_BINCODE_V1: u8 = 0xB1;
+/// Tag of the values encoded as JSON.
+pub(crate) const FORMAT_JSON_V1: u8 = 0x4A;
+
+/// Encoding of the values of a `WrapperLevelDB`. The wrapper prefixes every value
+/// with the `FORMAT_TAG` of its codec, so each codec needs its own tag for the
+/// values written by another one to be told apart.
+pub trait Codec {
+    const FORMAT_TAG: u8;
+
+    fn encode<V: Serialize>(value: &V) -> Result<Vec<u8>, WrapperLevelDBErrors>;
+
+    fn decode<V: DeserializeOwned>(bytes: &[u8]) -> Result<V, WrapperLevelDBErrors>;
+
+    /// Decodes a value stored without format tag, as they were before tags existed.
+    /// Codecs added after the tags never find one.
+    fn decode_untagged<V: DeserializeOwned>(_bytes: &[u8]) -> Option<V> {
+        None
+    }
+}
+
+/// Default codec, compact but not readable.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BincodeCodec;
+
+impl Codec for BincodeCodec {
+    const FORMAT_TAG: u8 = FORMAT_BINCODE_V1;
+
+    fn encode<V: Serialize>(value: &V) -> Result<Vec<u8>, WrapperLevelDBErrors> {
+        bincode::serialize(value).map_err(|_| WrapperLevelDBErrors::SerializeError)
+    }
+
+    /// The whole payload must be consumed. `bincode::deserialize` ignores trailing
+    /// bytes, so an untagged value that starts with the tag could be taken for a
+    /// shorter tagged one.
+    fn decode<V: DeserializeOwned>(bytes: &[u8]) -> Result<V, WrapperLevelDBErrors> {
+        bincode::DefaultOptions::new()
+            .with_fixint_encoding()
+            .deserialize(bytes)
+            .map_err(|_| WrapperLevelDBErrors::DeserializeError)
+    }
+
+    /// Values written before the tag existed are plain bincode.
+    fn decode_untagged<V: DeserializeOwned>(bytes: &[u8]) -> Option<V> {
+        bincode::deserialize(bytes).ok()
+    }
+}
+
+/// Codec that keeps the values readable, to inspect the database while developing.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonCodec;
+
+impl Codec for JsonCodec {
+    const FORMAT_TAG: u8 = FORMAT_JSON_V1;
+
+    fn encode<V: Serialize>(value: &V) -> Result<Vec<u8>, WrapperLevelDBErrors> {
+        serde_json::to_vec(value).map_err(|_| WrapperLevelDBErrors::SerializeError)
+    }
+
+    fn decode<V: DeserializeOwned>(bytes: &[u8]) -> Result<V, WrapperLevelDBErrors> {
+        serde_json::from_slice(bytes).map_err(|_| WrapperLevelDBErrors::DeserializeError)
+    }
+}
@@ -12,10 +12,29 @@ pub enum WrapperLevelDBErrors {
     SerializeError,
     #[error("Error while deserializing")]
     DeserializeError,
+    #[error("Stored value has the unknown format tag {0:#04x}")]
+    UnknownFormatTag(u8),
     #[error("No table selected. Call select_table first")]
     TableNotSelectedError,
     #[error("There was an attempt to update an unexistent entry in DB")]
     EntryNotFoundError,
     #[error("There was an attempt to insert in an already existent entry in DB")]
     EntryAlreadyExists,
+    #[error("Stored data does not match its checksum: {0}")]
+    ChecksumError(String),
+    #[error("Serialized value takes {size} bytes, more than the limit of {max}")]
+    ValueTooLarge { size: usize, max: usize },
+}
+
+impl WrapperLevelDBErrors {
+    /// Whether the operation may succeed if it is attempted again. Only the I/O
+    /// errors reported by LevelDB are, which include failing to take its lock.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            WrapperLevelDBErrors::LevelDBError { source } => {
+                source.to_string().contains("IO error")
+            }
+            _ => false,
+        }
+    }
 }
@@ -0,0 +1,70 @@
+//! Counters of the operations made on the tables of a `WrapperLevelDB`
+use std::{
+    ops::Add,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+/// Operations made on a table, counted from the moment the handle was attached
+/// with `WrapperLevelDB::with_metrics`. Partitions of the table share its handle.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    gets: AtomicU64,
+    puts: AtomicU64,
+    dels: AtomicU64,
+    scans: AtomicU64,
+}
+
+impl Metrics {
+    pub(crate) fn record_get(&self) {
+        self.gets.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_puts(&self, count: u64) {
+        self.puts.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_dels(&self, count: u64) {
+        self.dels.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_scan(&self) {
+        self.scans.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Values of the counters. They are read one by one, so operations made
+    /// meanwhile may be counted in some of them only.
+    pub fn snapshot(&self) -> TableMetrics {
+        TableMetrics {
+            gets: self.gets.load(Ordering::Relaxed),
+            puts: self.puts.load(Ordering::Relaxed),
+            dels: self.dels.load(Ordering::Relaxed),
+            scans: self.scans.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Values of the counters of a [Metrics] handle.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TableMetrics {
+    /// Keys read one by one
+    pub gets: u64,
+    /// Keys written, each key of a batch counted apart
+    pub puts: u64,
+    /// Keys deleted, each key of a batch or a range counted apart
+    pub dels: u64,
+    /// Iterations over the keys of the table
+    pub scans: u64,
+}
+
+impl Add for TableMetrics {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            gets: self.gets + other.gets,
+            puts: self.puts + other.puts,
+            dels: self.dels + other.dels,
+            scans: self.scans + other.scans,
+        }
+    }
+}
@@ -1,2 +1,4 @@
+pub mod codec;
 pub mod error;
+pub mod metrics;
 pub mod wrapper_leveldb;
@@ -1,4 +1,5 @@
 use std::cell::Cell;
+use std::time::Duration;
 
 use leveldb::database::Database as LevelDataBase;
 use std::sync::Arc as core_Arc;
@@ -60,35 +61,156 @@ unsafe impl<T> Sync for SyncCell<T> {}
 
 use serde::{de::DeserializeOwned, Serialize};
 use std::marker::PhantomData;
-pub struct WrapperLevelDB<K: db_key::Key, V: Serialize + DeserializeOwned> {
+
+use super::codec::{BincodeCodec, Codec};
+use super::metrics::{Metrics, TableMetrics};
+
+/// Keys deleted per write by `delete_range`.
+const DELETE_RANGE_BATCH: usize = 1024;
+
+/// Failpoints of the tests that simulate a crash while a batch is being built,
+/// after some of its entries were added but before it is written, or right after
+/// LevelDB wrote it, before the caller goes on.
+#[cfg(test)]
+pub(crate) mod failpoint {
+    use std::cell::Cell;
+
+    thread_local! {
+        static CRASH_AFTER: Cell<Option<usize>> = const { Cell::new(None) };
+        static CRASH_AFTER_WRITE: Cell<bool> = const { Cell::new(false) };
+    }
+
+    /// Makes the current thread panic, as if the process crashed, when an entry
+    /// is added to a batch after `entries` more have been.
+    pub(crate) fn crash_after(entries: usize) {
+        CRASH_AFTER.with(|crash_after| crash_after.set(Some(entries)));
+    }
+
+    /// Makes the current thread panic once the next batch is written.
+    pub(crate) fn crash_after_write() {
+        CRASH_AFTER_WRITE.with(|crash_after_write| crash_after_write.set(true));
+    }
+
+    pub(crate) fn disarm() {
+        CRASH_AFTER.with(|crash_after| crash_after.set(None));
+        CRASH_AFTER_WRITE.with(|crash_after_write| crash_after_write.set(false));
+    }
+
+    /// Called before every entry added to a batch.
+    pub(crate) fn hit() {
+        CRASH_AFTER.with(|crash_after| match crash_after.get() {
+            Some(0) => {
+                crash_after.set(None);
+                panic!("Simulated crash while building a batch");
+            }
+            Some(entries) => crash_after.set(Some(entries - 1)),
+            None => {}
+        });
+    }
+
+    /// Called after every batch written.
+    pub(crate) fn written() {
+        if CRASH_AFTER_WRITE.with(|crash_after_write| crash_after_write.replace(false)) {
+            panic!("Simulated crash after writing a batch");
+        }
+    }
+}
+
+/// Table of values of type `V`, stored encoded with the codec `C` and prefixed
+/// by its format tag.
+pub struct WrapperLevelDB<K: db_key::Key, V: Serialize + DeserializeOwned, C: Codec = BincodeCodec>
+{
     db: LevelDBShared<K>,
     selected_table: String,
     read_options: SyncCell<Option<ReadOptions>>,
     write_options: SyncCell<Option<options::WriteOptions>>,
+    scan_fill_cache: SyncCell<Option<bool>>,
     separator: char,
-    phantom: PhantomData<V>,
+    max_value_bytes: Option<usize>,
+    metrics: Option<core_Arc<Metrics>>,
+    phantom: PhantomData<(V, C)>,
 }
 
-impl<K, V> WrapperLevelDB<K, V>
+impl<K, V, C> WrapperLevelDB<K, V, C>
 where
     K: db_key::Key,
     V: Serialize + DeserializeOwned,
+    C: Codec,
 {
     fn deserialize(bytes: Vec<u8>) -> Result<V, error::WrapperLevelDBErrors> {
-        let result = bincode::deserialize::<V>(bytes.as_slice());
-        if let Ok(value) = result {
-            return Ok(value);
-        } else {
-            return Err(error::WrapperLevelDBErrors::DeserializeError);
+        Self::decode(&bytes).map(|(value, _)| value)
+    }
+
+    /// Deserializes an entry read by a scan. An entry that can not be deserialized
+    /// is skipped with a warning, so that it does not make the whole table unreadable.
+    fn deserialize_entry(key: StringKey, bytes: Vec<u8>) -> Option<(StringKey, V)> {
+        match Self::deserialize(bytes) {
+            Ok(value) => Some((key, value)),
+            Err(error) => {
+                tracing::warn!(%error, key = ?key.0, "Skipping entry that can not be deserialized");
+                None
+            }
         }
     }
 
-    fn serialize(value: V) -> Result<Vec<u8>, error::WrapperLevelDBErrors> {
-        if let Ok(bytes) = bincode::serialize(&value) {
-            return Ok(bytes);
-        } else {
-            return Err(error::WrapperLevelDBErrors::SerializeError);
-        };
+    /// Decodes a stored value and tells whether it was stored without format tag.
+    /// Values written before the tag existed are decoded with `decode_untagged`
+    /// of the codec when the tagged decoding fails.
+    fn decode(bytes: &[u8]) -> Result<(V, bool), error::WrapperLevelDBErrors> {
+        if let Some((&tag, payload)) = bytes.split_first() {
+            if tag == C::FORMAT_TAG {
+                if let Ok(value) = C::decode::<V>(payload) {
+                    return Ok((value, false));
+                }
+            }
+        }
+        match C::decode_untagged::<V>(bytes) {
+            Some(value) => Ok((value, true)),
+            None => match bytes.first() {
+                Some(&tag) if tag != C::FORMAT_TAG => {
+                    Err(error::WrapperLevelDBErrors::UnknownFormatTag(tag))
+                }
+                _ => Err(error::WrapperLevelDBErrors::DeserializeError),
+            },
+        }
+    }
+
+    fn serialize(value: &V) -> Result<Vec<u8>, error::WrapperLevelDBErrors> {
+        let mut bytes = vec![C::FORMAT_TAG];
+        bytes.extend(C::encode(value)?);
+        Ok(bytes)
+    }
+}
+
+/// How `put_with_retry` and `get_with_retry` retry transient errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Attempts made in total, the first one included
+    pub max_attempts: u32,
+    /// Wait before the second attempt. It doubles before each of the following ones
+    pub backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Runs `operation` until it succeeds, fails with an error that is not
+    /// transient or runs out of attempts. The last error is returned.
+    pub fn run<T, F>(&self, mut operation: F) -> Result<T, error::WrapperLevelDBErrors>
+    where
+        F: FnMut() -> Result<T, error::WrapperLevelDBErrors>,
+    {
+        let mut backoff = self.backoff;
+        let mut attempt = 1;
+        loop {
+            match operation() {
+                Err(error) if error.is_transient() && attempt < self.max_attempts => {
+                    log::debug!("Retrying transient LevelDB error: {}", error);
+                    std::thread::sleep(backoff);
+                    backoff = backoff.saturating_mul(2);
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
     }
 }
 
@@ -100,19 +222,25 @@ pub enum CursorIndex {
 }
 
 use super::error;
+use leveldb::batch::{Batch, Writebatch};
+use leveldb::compaction::Compaction;
 use leveldb::iterator::{Iterable, LevelDBIterator};
 use leveldb::{database::options, kv::KV};
-impl<V> WrapperLevelDB<StringKey, V>
+impl<V, C> WrapperLevelDB<StringKey, V, C>
 where
     V: Serialize + DeserializeOwned,
+    C: Codec,
 {
-    pub fn new(db: LevelDBShared<StringKey>, table_name: &str) -> WrapperLevelDB<StringKey, V> {
+    pub fn new(db: LevelDBShared<StringKey>, table_name: &str) -> Self {
         WrapperLevelDB {
             db: db.clone(),
             selected_table: String::from(table_name),
             read_options: SyncCell(Cell::new(None)),
             write_options: SyncCell(Cell::new(None)),
+            scan_fill_cache: SyncCell(Cell::new(None)),
             separator: char::MAX,
+            max_value_bytes: None,
+            metrics: None,
             phantom: PhantomData::default(),
         }
     }
@@ -125,11 +253,72 @@ where
             selected_table: table_name.0,
             read_options: SyncCell(self.read_options.0.clone()),
             write_options: SyncCell(self.write_options.0.clone()),
+            scan_fill_cache: SyncCell(self.scan_fill_cache.0.clone()),
+            separator: self.separator,
+            max_value_bytes: self.max_value_bytes,
+            metrics: self.metrics.clone(),
+            phantom: PhantomData::default(),
+        }
+    }
+
+    /// Equivalent to chaining `partition` for each of the segments, in order.
+    pub fn partition_path<S: AsRef<str>>(&self, segments: &[S]) -> Self {
+        let mut table_name = self.selected_table.clone();
+        for segment in segments {
+            table_name.push(self.separator);
+            table_name.push_str(segment.as_ref());
+        }
+        WrapperLevelDB {
+            db: self.db.clone(),
+            selected_table: table_name,
+            read_options: SyncCell(self.read_options.0.clone()),
+            write_options: SyncCell(self.write_options.0.clone()),
+            scan_fill_cache: SyncCell(self.scan_fill_cache.0.clone()),
             separator: self.separator,
+            max_value_bytes: self.max_value_bytes,
+            metrics: self.metrics.clone(),
             phantom: PhantomData::default(),
         }
     }
 
+    /// Rejects with `ValueTooLarge` the writes of values that take more than
+    /// `max_value_bytes` once serialized. Partitions inherit the limit.
+    pub fn with_max_value_bytes(mut self, max_value_bytes: usize) -> Self {
+        self.max_value_bytes = Some(max_value_bytes);
+        self
+    }
+
+    /// Counts the operations made on the table, partitions created afterwards
+    /// included, in `metrics`. Without a handle nothing is counted.
+    pub fn with_metrics(mut self, metrics: core_Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Counters of the metrics handle of the table, all zero if it has none.
+    pub fn metrics_snapshot(&self) -> TableMetrics {
+        self.metrics
+            .as_ref()
+            .map(|metrics| metrics.snapshot())
+            .unwrap_or_default()
+    }
+
+    fn record<F: FnOnce(&Metrics)>(&self, record: F) {
+        if let Some(metrics) = &self.metrics {
+            record(metrics);
+        }
+    }
+
+    fn check_value_size(&self, bytes: &[u8]) -> Result<(), error::WrapperLevelDBErrors> {
+        match self.max_value_bytes {
+            Some(max) if bytes.len() > max => Err(error::WrapperLevelDBErrors::ValueTooLarge {
+                size: bytes.len(),
+                max,
+            }),
+            _ => Ok(()),
+        }
+    }
+
     fn create_last_key(&self) -> String {
         let mut last_key = self.selected_table.clone();
         last_key.push(self.separator);
@@ -145,6 +334,23 @@ where
         }
     }
 
+    /// Read options of the methods that iterate over the table.
+    fn get_scan_options(&self) -> options::ReadOptions<StringKey> {
+        let mut options = self.get_read_options();
+        if let Some(fill_cache) = self.scan_fill_cache.0.get() {
+            options.fill_cache = fill_cache;
+        }
+        options
+    }
+
+    /// Sets whether the blocks read by `get_all`, `get_range` and the other methods
+    /// that iterate over the table are kept in the LevelDB cache. Disabling it keeps
+    /// large scans from evicting the blocks of frequently read keys. By default they
+    /// follow the read options. Partitions created afterwards inherit the setting
+    pub fn set_scan_fill_cache(&self, fill_cache: bool) {
+        self.scan_fill_cache.0.replace(Some(fill_cache));
+    }
+
     pub fn set_read_options(&mut self, options: options::ReadOptions<StringKey>) {
         self.read_options
             .0
@@ -165,6 +371,20 @@ where
         self.write_options.0.replace(Some(options));
     }
 
+    /// Sets whether `put`, `update` and `del` wait for LevelDB to fsync the write.
+    /// It is enabled by default. Disabling it speeds up bulk loads, but the last
+    /// writes may be lost if the machine crashes (a crash of the process alone does
+    /// not lose them). Partitions created afterwards inherit the setting
+    pub fn set_sync(&self, sync: bool) {
+        let mut write_options = self.get_write_options();
+        write_options.sync = sync;
+        self.set_write_options(write_options);
+    }
+
+    pub fn is_sync(&self) -> bool {
+        self.get_write_options().sync
+    }
+
     fn build_key(&self, key: &str) -> StringKey {
         let table_name = self.selected_table.clone();
         let mut key_builder = String::with_capacity(table_name.len() + key.len() + 1);
@@ -185,9 +405,28 @@ where
         key_builder
     }
 
+    pub fn get_separator(&self) -> char {
+        self.separator
+    }
+
+    /// Splits a raw key of the database into the path of partitions below this
+    /// table and the logical key. Returns `None` if the key is not in the table
+    pub fn decode_raw_key(&self, raw: &str) -> Option<(Vec<String>, String)> {
+        let table_name = self.get_table_name();
+        let relative_key = raw.strip_prefix(&table_name)?;
+        let mut components: Vec<String> = relative_key
+            .split(self.separator)
+            .map(String::from)
+            .collect();
+        let key = components.pop()?;
+        Some((components, key))
+    }
+
     pub fn put(&self, key: &str, value: V) -> Result<(), error::WrapperLevelDBErrors> {
         let key = self.build_key(key);
-        let value = WrapperLevelDB::<StringKey, V>::serialize(value)?;
+        let value = Self::serialize(&value)?;
+        self.check_value_size(&value)?;
+        self.record(|metrics| metrics.record_puts(1));
 
         Ok({
             self.db
@@ -195,11 +434,68 @@ where
         })
     }
 
+    /// Stores all the entries in a single write, so either all of them are
+    /// written or none is.
+    pub fn put_batch(&self, entries: Vec<(String, V)>) -> Result<(), error::WrapperLevelDBErrors> {
+        let mut batch = Writebatch::new();
+        let entries_count = entries.len() as u64;
+        for (key, value) in entries {
+            let value = Self::serialize(&value)?;
+            self.check_value_size(&value)?;
+            #[cfg(test)]
+            failpoint::hit();
+            batch.put(self.build_key(&key), value.as_slice());
+        }
+        self.record(|metrics| metrics.record_puts(entries_count));
+        self.db.write(self.get_write_options(), &batch)?;
+        #[cfg(test)]
+        failpoint::written();
+        Ok(())
+    }
+
+    /// Adds the put of `value` under `key` to `batch`. The batch may gather writes
+    /// of several tables of the same database, written at once by `write_batch`.
+    /// The put is counted in the metrics of this table when it is added.
+    pub fn batch_put(
+        &self,
+        batch: &mut Writebatch<StringKey>,
+        key: &str,
+        value: &V,
+    ) -> Result<(), error::WrapperLevelDBErrors> {
+        let value = Self::serialize(value)?;
+        self.check_value_size(&value)?;
+        #[cfg(test)]
+        failpoint::hit();
+        batch.put(self.build_key(key), value.as_slice());
+        self.record(|metrics| metrics.record_puts(1));
+        Ok(())
+    }
+
+    /// Adds the delete of `key` to `batch`.
+    pub fn batch_delete(&self, batch: &mut Writebatch<StringKey>, key: &str) {
+        #[cfg(test)]
+        failpoint::hit();
+        batch.delete(self.build_key(key));
+        self.record(|metrics| metrics.record_dels(1));
+    }
+
+    /// Writes every operation of `batch`, or none of them.
+    pub fn write_batch(
+        &self,
+        batch: &Writebatch<StringKey>,
+    ) -> Result<(), error::WrapperLevelDBErrors> {
+        self.db.write(self.get_write_options(), batch)?;
+        #[cfg(test)]
+        failpoint::written();
+        Ok(())
+    }
+
     pub fn get_bytes(
         &self,
         key: &str,
     ) -> Result<leveldb::database::bytes::Bytes, error::WrapperLevelDBErrors> {
         let key = self.build_key(key);
+        self.record(Metrics::record_get);
         let result = { self.db.get_bytes(self.get_read_options(), key)? };
         if let Some(bytes) = result {
             return Ok(bytes);
@@ -208,13 +504,94 @@ where
         }
     }
 
+    /// Same as `put`, but transient errors are retried as `policy` says.
+    pub fn put_with_retry(
+        &self,
+        key: &str,
+        value: V,
+        policy: &RetryPolicy,
+    ) -> Result<(), error::WrapperLevelDBErrors> {
+        let value = Self::serialize(&value)?;
+        self.check_value_size(&value)?;
+        policy.run(|| {
+            self.record(|metrics| metrics.record_puts(1));
+            Ok(self
+                .db
+                .put(self.get_write_options(), self.build_key(key), &value)?)
+        })
+    }
+
+    /// Same as `get`, but transient errors are retried as `policy` says.
+    pub fn get_with_retry(
+        &self,
+        key: &str,
+        policy: &RetryPolicy,
+    ) -> Result<V, error::WrapperLevelDBErrors> {
+        policy.run(|| self.get(key))
+    }
+
+    /// Checks whether `key` is stored without deserializing its value.
+    pub fn exists(&self, key: &str) -> Result<bool, error::WrapperLevelDBErrors> {
+        match self.get_bytes(key) {
+            Ok(_) => Ok(true),
+            Err(error::WrapperLevelDBErrors::EntryNotFoundError) => Ok(false),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Values stored without format tag are read too, `upgrade_untagged` rewrites
+    /// them with it.
     pub fn get(&self, key: &str) -> Result<V, error::WrapperLevelDBErrors> {
+        self.record(Metrics::record_get);
+        let result = { self.db.get(self.get_read_options(), self.build_key(key))? };
+        let Some(bytes) = result else {
+            return Err(error::WrapperLevelDBErrors::EntryNotFoundError);
+        };
+        Self::deserialize(bytes)
+    }
+
+    /// Rewrites with the format tag the values of the table, partitions included,
+    /// stored before the tag existed, in a single write. Returns how many were
+    /// rewritten. Meant to be run once, before serving requests, after upgrading
+    /// a database created by an older version.
+    pub fn upgrade_untagged(&self) -> Result<usize, error::WrapperLevelDBErrors> {
+        let mut batch = Writebatch::new();
+        let mut upgraded = 0;
+        for (key, bytes) in self.get_all_raw() {
+            let (value, untagged) = Self::decode(&bytes)?;
+            if untagged {
+                self.batch_put(&mut batch, &key, &value)?;
+                upgraded += 1;
+            }
+        }
+        if upgraded > 0 {
+            self.write_batch(&batch)?;
+        }
+        Ok(upgraded)
+    }
+
+    /// Same as `get`, but LevelDB verifies the checksums of the data it reads,
+    /// whatever the read options of the wrapper are. Corrupted data is reported
+    /// as `ChecksumError`.
+    pub fn get_verified(&self, key: &str) -> Result<V, error::WrapperLevelDBErrors> {
         let key = self.build_key(key);
-        let result = { self.db.get(self.get_read_options(), key)? };
+        let mut read_options = self.get_read_options();
+        read_options.verify_checksums = true;
+        self.record(Metrics::record_get);
+        let result = match self.db.get(read_options, key) {
+            Ok(result) => result,
+            // LevelDB reports failed checksums as a Corruption status
+            Err(error) if error.to_string().contains("Corruption") => {
+                return Err(error::WrapperLevelDBErrors::ChecksumError(
+                    error.to_string(),
+                ))
+            }
+            Err(error) => return Err(error.into()),
+        };
         if let Some(bytes) = result {
-            return Ok(WrapperLevelDB::<StringKey, V>::deserialize(bytes)?);
+            Self::deserialize(bytes)
         } else {
-            return Err(error::WrapperLevelDBErrors::EntryNotFoundError);
+            Err(error::WrapperLevelDBErrors::EntryNotFoundError)
         }
     }
 
@@ -223,17 +600,40 @@ where
         let old_value = self.get(key)?;
         // If it exists, we modify it
         let key = self.build_key(key);
-        let value = if let Ok(bytes) = bincode::serialize(&value) {
-            bytes
-        } else {
-            return Err(error::WrapperLevelDBErrors::SerializeError);
-        };
+        let value = Self::serialize(&value)?;
+        self.check_value_size(&value)?;
         // Update
+        self.record(|metrics| metrics.record_puts(1));
         self.db
             .put(self.get_write_options(), key, value.as_slice())?;
         Ok(old_value)
     }
 
+    /// Returns the value stored under `key` or, if there is none, stores and returns
+    /// the one computed by `f`. Any other error, such as a value that cannot be
+    /// deserialized, is returned without calling `f` so nothing is overwritten.
+    pub fn get_or_insert_with<F: FnOnce() -> V>(
+        &self,
+        key: &str,
+        f: F,
+    ) -> Result<V, error::WrapperLevelDBErrors> {
+        match self.get(key) {
+            Err(error::WrapperLevelDBErrors::EntryNotFoundError) => {
+                let value = f();
+                let bytes = Self::serialize(&value)?;
+                self.check_value_size(&bytes)?;
+                self.record(|metrics| metrics.record_puts(1));
+                self.db.put(
+                    self.get_write_options(),
+                    self.build_key(key),
+                    bytes.as_slice(),
+                )?;
+                Ok(value)
+            }
+            result => result,
+        }
+    }
+
     pub fn del(&self, key: &str) -> Result<Option<V>, error::WrapperLevelDBErrors> {
         let old_value = if let Ok(value) = self.get(key) {
             Some(value)
@@ -242,15 +642,125 @@ where
         };
         let key = self.build_key(key);
         let write_opts = self.get_write_options();
+        self.record(|metrics| metrics.record_dels(1));
         self.db.delete(write_opts, key)?;
         Ok(old_value)
     }
 
+    /// Deletes the keys of the table whose logical key is in `[from, to)`, compared
+    /// byte by byte, and returns how many were deleted. The keys of partitions of the
+    /// table are left untouched. Deletes are written in batches of `DELETE_RANGE_BATCH`.
+    pub fn delete_range(&self, from: &str, to: &str) -> Result<usize, error::WrapperLevelDBErrors> {
+        if from >= to {
+            return Ok(0);
+        }
+        let table_name = self.get_table_name();
+        let last_key = self.build_key(to).0;
+        self.record(Metrics::record_scan);
+        let iter = self.db.keys_iter(self.get_scan_options());
+        iter.seek(&self.build_key(from));
+        let keys: Vec<StringKey> = iter
+            .take_while(|key| key.0 < last_key)
+            .filter(|key| {
+                key.0
+                    .strip_prefix(&table_name)
+                    .is_some_and(|key| !key.contains(self.separator))
+            })
+            .collect();
+        self.record(|metrics| metrics.record_dels(keys.len() as u64));
+        for chunk in keys.chunks(DELETE_RANGE_BATCH) {
+            let mut batch = Writebatch::new();
+            for key in chunk {
+                batch.delete(StringKey(key.0.clone()));
+            }
+            self.db.write(self.get_write_options(), &batch)?;
+        }
+        Ok(keys.len())
+    }
+
     pub fn get_all(&self) -> Vec<(StringKey, V)> {
-        let iter = self.db.iter(self.get_read_options());
+        self.record(Metrics::record_scan);
+        let iter = self.db.iter(self.get_scan_options());
+        let table_name = self.get_table_name();
+
+        // With the separator, so that the keys of tables whose name starts with
+        // this one, which may sort first, are not taken as the end of the table
+        iter.seek(&StringKey(table_name.clone()));
+        iter.map_while(|(key, bytes)| {
+            // Stop when it returns None
+            if key.0.starts_with(&table_name) {
+                let key = {
+                    let StringKey(value) = key;
+                    // Remove the table name from the key
+                    StringKey(value.replace(&table_name, ""))
+                };
+                Some(Self::deserialize_entry(key, bytes))
+            } else {
+                None
+            }
+        })
+        .flatten()
+        .collect()
+    }
+
+    /// Returns every entry of the table, partitions included, with the key relative
+    /// to the table and the value exactly as it is stored.
+    pub fn get_all_raw(&self) -> Vec<(String, Vec<u8>)> {
+        self.iter_raw().collect()
+    }
+
+    /// Same as `get_all_raw`, but the entries are read from the DB as the iterator
+    /// advances instead of being loaded all at once.
+    pub fn iter_raw(&self) -> impl Iterator<Item = (String, Vec<u8>)> + '_ {
+        self.record(Metrics::record_scan);
+        let iter = self.db.iter(self.get_scan_options());
         let table_name = self.get_table_name();
 
-        iter.seek(&StringKey(self.selected_table.clone()));
+        iter.seek(&StringKey(table_name.clone()));
+        iter.map_while(move |(key, bytes)| {
+            key.0
+                .strip_prefix(&table_name)
+                .map(|key| (key.to_owned(), bytes))
+        })
+    }
+
+    /// Stores already serialized bytes under `key`, as returned by `get_all_raw`.
+    pub fn put_raw(&self, key: &str, bytes: &[u8]) -> Result<(), error::WrapperLevelDBErrors> {
+        self.check_value_size(bytes)?;
+        let key = self.build_key(key);
+        self.record(|metrics| metrics.record_puts(1));
+        Ok(self.db.put(self.get_write_options(), key, bytes)?)
+    }
+
+    /// Same as `put_raw`, but the put is added to `batch` instead of being written.
+    pub fn batch_put_raw(
+        &self,
+        batch: &mut Writebatch<StringKey>,
+        key: &str,
+        bytes: &[u8],
+    ) -> Result<(), error::WrapperLevelDBErrors> {
+        self.check_value_size(bytes)?;
+        #[cfg(test)]
+        failpoint::hit();
+        batch.put(self.build_key(key), bytes);
+        self.record(|metrics| metrics.record_puts(1));
+        Ok(())
+    }
+
+    pub fn get_all_rev(&self) -> Vec<(StringKey, V)> {
+        self.record(Metrics::record_scan);
+        let mut iter = self.db.iter(self.get_scan_options()).reverse();
+        let table_name = self.get_table_name();
+
+        iter.seek(&StringKey(self.create_last_key()));
+        if iter.valid() {
+            // The cursor is on the first key after the table. The first advance
+            // only marks the iterator as started, so the next step goes backwards
+            iter.advance();
+        } else {
+            // There is nothing after the table, so its last key is the last one of the DB
+            iter.seek_to_last();
+        }
         iter.map_while(|(key, bytes)| {
             // Stop when it returns None
             if key.0.starts_with(&table_name) {
@@ -259,18 +769,64 @@ where
                     // Remove the table name from the key
                     StringKey(value.replace(&table_name, ""))
                 };
-                // Perform deserialization to obtain the stored structure from bytes
-                let value = WrapperLevelDB::<StringKey, V>::deserialize(bytes).unwrap();
-                Some((key, value))
+                Some(Self::deserialize_entry(key, bytes))
             } else {
                 None
             }
         })
+        .flatten()
         .collect()
     }
 
     pub fn get_range(&self, cursor: &CursorIndex, quantity: isize) -> Vec<(StringKey, V)> {
-        let iter = self.db.iter(self.get_read_options());
+        self.try_get_range(cursor, quantity).unwrap()
+    }
+
+    /// Same as `get_range`, but a value that can not be deserialized is returned
+    /// as an error instead of panicking.
+    pub fn try_get_range(
+        &self,
+        cursor: &CursorIndex,
+        quantity: isize,
+    ) -> Result<Vec<(StringKey, V)>, error::WrapperLevelDBErrors> {
+        self.get_range_raw(cursor, true, quantity)
+            .into_iter()
+            .map(|(key, bytes)| Ok((key, Self::deserialize(bytes)?)))
+            .collect()
+    }
+
+    /// Same as `get_range`, but if `inclusive_start` is false the entry stored
+    /// under the `FromKey` cursor is left out, so the last key of a page can be
+    /// used as the cursor of the next one.
+    pub fn get_range_bounded(
+        &self,
+        cursor: &CursorIndex,
+        inclusive_start: bool,
+        quantity: isize,
+    ) -> Vec<(StringKey, V)> {
+        self.get_range_raw(cursor, inclusive_start, quantity)
+            .into_iter()
+            .filter_map(|(key, bytes)| Self::deserialize_entry(key, bytes))
+            .collect()
+    }
+
+    /// Same as `get_range`, but the values are returned exactly as they are stored.
+    pub fn get_range_bytes(
+        &self,
+        cursor: &CursorIndex,
+        quantity: isize,
+    ) -> Vec<(StringKey, Vec<u8>)> {
+        self.get_range_raw(cursor, true, quantity)
+    }
+
+    fn get_range_raw(
+        &self,
+        cursor: &CursorIndex,
+        inclusive_start: bool,
+        quantity: isize,
+    ) -> Vec<(StringKey, Vec<u8>)> {
+        self.record(Metrics::record_scan);
+        let iter = self.db.iter(self.get_scan_options());
         let table_name = self.get_table_name();
         let mut count = 0usize;
         let closure = |value: (StringKey, Vec<u8>)| {
@@ -283,10 +839,8 @@ where
                     // Remove the table name from the key
                     StringKey(value.replace(&table_name, ""))
                 };
-                // Perform deserialization to obtain the stored structure from bytes
-                let value = WrapperLevelDB::<StringKey, V>::deserialize(bytes).unwrap();
                 count += 1;
-                return Some((key, value));
+                return Some((key, bytes));
             } else {
                 None
             }
@@ -297,26 +851,47 @@ where
             CursorIndex::FromEnding => StringKey(self.create_last_key()),
             CursorIndex::FromKey(key) => self.build_key(&key),
         };
+        let excluded = match cursor {
+            CursorIndex::FromKey(key) if !inclusive_start => Some(self.build_key(key)),
+            _ => None,
+        };
+        let is_excluded = |value: &(StringKey, Vec<u8>)| Some(&value.0) == excluded.as_ref();
         if quantity < 0 {
             let mut iter = iter.reverse();
             iter.seek(&key);
             if cursor == &CursorIndex::FromEnding {
-                iter.advance();
+                if iter.valid() {
+                    // The cursor is on the first key after the table, skip it
+                    iter.advance();
+                } else {
+                    // There is nothing after the table, so its last key is the last one of the DB
+                    iter.seek_to_last();
+                }
             }
-            iter.map_while(closure).collect()
+            iter.skip_while(is_excluded).map_while(closure).collect()
         } else {
             if cursor == &CursorIndex::FromEnding {
-                let temp_iter = self.db.iter(self.get_read_options()).reverse();
+                // Modify the marker for the last key of the table, if it has any
+                let mut temp_iter = self.db.iter(self.get_scan_options()).reverse();
                 temp_iter.seek(&key);
-                key = temp_iter.skip(1).next().unwrap().0; // Modify the marker for the real one.
+                if temp_iter.valid() {
+                    temp_iter.advance();
+                } else {
+                    temp_iter.seek_to_last();
+                }
+                match temp_iter.next() {
+                    Some((last_key, _)) if last_key.0.starts_with(&table_name) => key = last_key,
+                    _ => return Vec::new(),
+                }
             }
             iter.seek(&key);
-            iter.map_while(closure).collect()
+            iter.skip_while(is_excluded).map_while(closure).collect()
         }
     }
 
     pub fn get_count(&self) -> usize {
-        let mut iter = self.db.keys_iter(self.get_read_options());
+        self.record(Metrics::record_scan);
+        let mut iter = self.db.keys_iter(self.get_scan_options());
         let first_key = StringKey(self.get_table_name());
         let mut count = 0;
         iter.seek(&first_key);
@@ -331,18 +906,111 @@ where
         });
         count
     }
+
+    /// Whether the table, partitions included, has no entries. Unlike `get_count`,
+    /// only the first key from the start of the table is read.
+    pub fn is_empty(&self) -> bool {
+        self.record(Metrics::record_scan);
+        let mut iter = self.db.keys_iter(self.get_scan_options());
+        let table_name = self.get_table_name();
+        iter.seek(&StringKey(table_name.clone()));
+        match iter.next() {
+            Some(key) => !key.0.starts_with(&table_name),
+            None => true,
+        }
+    }
+
+    /// Names of the partitions directly below the table that have entries, in key
+    /// order. The entries of each partition are skipped with a seek instead of read.
+    pub fn list_partitions(&self) -> Vec<String> {
+        let table_name = self.get_table_name();
+        let mut partitions: Vec<String> = Vec::new();
+        let mut from = table_name.clone();
+        self.record(Metrics::record_scan);
+        loop {
+            let iter = self.db.keys_iter(self.get_scan_options());
+            iter.seek(&StringKey(from));
+            let last = partitions.last();
+            // The direct keys of the table, and the keys of the last partition left
+            // past the seek, are skipped
+            let next = iter
+                .map_while(|key| key.0.strip_prefix(&table_name).map(String::from))
+                .find_map(|relative| {
+                    relative
+                        .split_once(self.separator)
+                        .map(|(partition, _)| partition.to_owned())
+                        .filter(|partition| Some(partition) != last)
+                });
+            let Some(partition) = next else {
+                return partitions;
+            };
+            // Past every key of the partition, as in `create_last_key`
+            from = format!(
+                "{}{}{}{}",
+                table_name, partition, self.separator, self.separator
+            );
+            partitions.push(partition);
+        }
+    }
+
+    /// Counts the values of the table, partitions included, that match `predicate`.
+    /// Values that can not be deserialized are skipped.
+    pub fn count_matching<F: Fn(&V) -> bool>(&self, predicate: F) -> usize {
+        self.record(Metrics::record_scan);
+        let iter = self.db.iter(self.get_scan_options());
+        let table_name = self.get_table_name();
+
+        iter.seek(&StringKey(table_name.clone()));
+        iter.take_while(|(key, _)| key.0.starts_with(&table_name))
+            .filter_map(|(_, bytes)| Self::deserialize(bytes).ok())
+            .filter(|value| predicate(value))
+            .count()
+    }
+
+    /// Compacts the key range of the table so that LevelDB reclaims the space
+    /// used by deleted or overwritten entries
+    pub fn compact_range(&self) {
+        let start = StringKey(self.get_table_name());
+        let limit = StringKey(self.create_last_key());
+        self.db.compact(&start, &limit);
+    }
+
+    /// Estimates the size in bytes of the table. The leveldb crate does not expose
+    /// `GetApproximateSizes`, so the size of the stored keys and values is added up
+    pub fn approximate_size(&self) -> u64 {
+        self.record(Metrics::record_scan);
+        let iter = self.db.iter(self.get_scan_options());
+        let table_name = self.get_table_name();
+
+        // Seeks past the tables whose name starts with this one, as `get_all`
+        iter.seek(&StringKey(table_name.clone()));
+        iter.map_while(|(key, bytes)| {
+            // Stop when it returns None
+            if key.0.starts_with(&table_name) {
+                Some((key.0.len() + bytes.len()) as u64)
+            } else {
+                None
+            }
+        })
+        .sum()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use std::sync::Arc;
+    use std::time::Duration;
 
-    use crate::bd::level_db::wrapper_leveldb::{open_db, CursorIndex};
+    use crate::bd::level_db::{
+        codec::{BincodeCodec, JsonCodec, FORMAT_BINCODE_V1},
+        metrics::{Metrics, TableMetrics},
+        wrapper_leveldb::{open_db, CursorIndex},
+    };
     use leveldb::options::Options;
     use serde::{Deserialize, Serialize};
     use tempdir::TempDir;
 
-    use super::{StringKey, WrapperLevelDB};
+    use super::{error::WrapperLevelDBErrors, RetryPolicy, StringKey, WrapperLevelDB};
 
     const TABLE_NAME1: &str = "TESTS";
     const TABLE_NAME2: &str = "PRUEBA";
@@ -837,6 +1505,944 @@ mod tests {
         });
     }
 
+    #[test]
+    fn test_try_get_range_from_ending() {
+        let temp_dir = TempDir::new("test_try_get_range_from_ending").unwrap();
+        let mut db_options = LevelDBOptions::new();
+        db_options.create_if_missing = true;
+        let db = Arc::new(
+            crate::bd::level_db::wrapper_leveldb::open_db::<StringKey>(
+                temp_dir.path(),
+                db_options,
+            )
+            .unwrap(),
+        );
+
+        let wrapper0 = WrapperLevelDB::<StringKey, u64>::new(db.clone(), EJEMPLO_TABLE);
+        let wrapper1 = WrapperLevelDB::<StringKey, u64>::new(db.clone(), PRUEBA_TABLE);
+        let wrapper2 = WrapperLevelDB::<StringKey, u64>::new(db.clone(), TEST_TABLE);
+        let from_ending = |wrapper: &WrapperLevelDB<StringKey, u64>| {
+            [
+                wrapper.try_get_range(&CursorIndex::FromEnding, 2).unwrap(),
+                wrapper.try_get_range(&CursorIndex::FromEnding, -2).unwrap(),
+            ]
+        };
+        // Empty database
+        let empty: [Vec<(StringKey, u64)>; 2] = [vec![], vec![]];
+        assert_eq!(empty, from_ending(&wrapper1));
+        // Single element in the last table of the database
+        wrapper1.put("a", 1).unwrap();
+        let single = || vec![(StringKey("a".to_string()), 1)];
+        assert_eq!([single(), single()], from_ending(&wrapper1));
+        // Empty tables before and after a table with entries
+        assert_eq!(empty, from_ending(&wrapper0));
+        assert_eq!(empty, from_ending(&wrapper2));
+        wrapper2.put("b", 2).unwrap();
+        assert_eq!([single(), single()], from_ending(&wrapper1));
+
+        wrapper0.put_raw("a", &[FORMAT_BINCODE_V1]).unwrap();
+        assert!(wrapper0
+            .try_get_range(&CursorIndex::FromEnding, -1)
+            .is_err());
+    }
+
+    #[test]
+    fn test_get_all_rev() {
+        let temp_dir = TempDir::new("test_get_all_rev").unwrap();
+        let mut db_options = LevelDBOptions::new();
+        db_options.create_if_missing = true;
+        let db = Arc::new(
+            crate::bd::level_db::wrapper_leveldb::open_db::<StringKey>(
+                temp_dir.path(),
+                db_options,
+            )
+            .unwrap(),
+        );
+
+        let wrapper0 = WrapperLevelDB::<StringKey, u64>::new(db.clone(), EJEMPLO_TABLE);
+        let wrapper1 = WrapperLevelDB::<StringKey, u64>::new(db.clone(), PRUEBA_TABLE);
+        let wrapper2 = WrapperLevelDB::<StringKey, u64>::new(db.clone(), TEST_TABLE);
+        let wrapper_empty = WrapperLevelDB::<StringKey, u64>::new(db.clone(), "EMPTY");
+
+        set_up_entries(wrapper0, wrapper1, wrapper2);
+
+        let wrapper1 = WrapperLevelDB::<StringKey, u64>::new(db.clone(), PRUEBA_TABLE);
+        let wrapper2 = WrapperLevelDB::<StringKey, u64>::new(db.clone(), TEST_TABLE);
+        // Table followed by other entries
+        let mut expected = wrapper1.get_all();
+        expected.reverse();
+        assert_eq!(expected, wrapper1.get_all_rev());
+        // Last table of the DB
+        let mut expected = wrapper2.get_all();
+        expected.reverse();
+        assert_eq!(expected, wrapper2.get_all_rev());
+        assert_eq!(vec![] as Vec<(StringKey, u64)>, wrapper_empty.get_all_rev());
+    }
+
+    #[test]
+    fn test_scans_skip_undecodable() {
+        let temp_dir = TempDir::new("test_scans_skip_undecodable").unwrap();
+        let mut db_options = LevelDBOptions::new();
+        db_options.create_if_missing = true;
+        let db = Arc::new(
+            crate::bd::level_db::wrapper_leveldb::open_db::<StringKey>(temp_dir.path(), db_options)
+                .unwrap(),
+        );
+
+        let wrapper0 = WrapperLevelDB::<StringKey, u64>::new(db.clone(), EJEMPLO_TABLE);
+        wrapper0.put("a", 1).unwrap();
+        // A single byte can not be decoded as an u64
+        wrapper0.put_raw("b", &[1]).unwrap();
+        wrapper0.put("c", 3).unwrap();
+
+        let expected = || {
+            vec![
+                (StringKey("a".to_string()), 1),
+                (StringKey("c".to_string()), 3),
+            ]
+        };
+        assert_eq!(expected(), wrapper0.get_all());
+        let mut reversed = expected();
+        reversed.reverse();
+        assert_eq!(reversed, wrapper0.get_all_rev());
+        assert_eq!(
+            expected(),
+            wrapper0.get_range_bounded(&CursorIndex::FromBeginning, true, 3)
+        );
+    }
+
+    #[test]
+    fn test_get_range_exclusive_start() {
+        let temp_dir = TempDir::new("test_get_range_exclusive_start").unwrap();
+        let mut db_options = LevelDBOptions::new();
+        db_options.create_if_missing = true;
+        let db = Arc::new(
+            crate::bd::level_db::wrapper_leveldb::open_db::<StringKey>(
+                temp_dir.path(),
+                db_options,
+            )
+            .unwrap(),
+        );
+
+        let wrapper0 = WrapperLevelDB::<StringKey, u64>::new(db.clone(), EJEMPLO_TABLE);
+        let wrapper1 = WrapperLevelDB::<StringKey, u64>::new(db.clone(), PRUEBA_TABLE);
+        let wrapper2 = WrapperLevelDB::<StringKey, u64>::new(db.clone(), TEST_TABLE);
+        set_up_entries(wrapper0, wrapper1, wrapper2);
+
+        let wrapper1 = WrapperLevelDB::<StringKey, u64>::new(db.clone(), PRUEBA_TABLE);
+
+        let cursor = CursorIndex::FromKey("0a".into());
+        assert_eq!(
+            vec![
+                (StringKey("0a".to_string()), 14),
+                (StringKey("a".to_string()), 11)
+            ],
+            wrapper1.get_range_bounded(&cursor, true, 2)
+        );
+        assert_eq!(
+            vec![
+                (StringKey("a".to_string()), 11),
+                (StringKey("b".to_string()), 10)
+            ],
+            wrapper1.get_range_bounded(&cursor, false, 2)
+        );
+        assert_eq!(
+            vec![
+                (StringKey("0a".to_string()), 14),
+                (StringKey("00".to_string()), 13)
+            ],
+            wrapper1.get_range_bounded(&cursor, true, -2)
+        );
+        assert_eq!(
+            vec![
+                (StringKey("00".to_string()), 13),
+                (StringKey("0".to_string()), 12)
+            ],
+            wrapper1.get_range_bounded(&cursor, false, -2)
+        );
+        // A cursor that is not stored behaves the same in both modes
+        let cursor = CursorIndex::FromKey("01".into());
+        assert_eq!(
+            wrapper1.get_range_bounded(&cursor, true, 2),
+            wrapper1.get_range_bounded(&cursor, false, 2)
+        );
+        // The flag only affects key cursors
+        assert_eq!(
+            wrapper1.get_range(&CursorIndex::FromBeginning, 5),
+            wrapper1.get_range_bounded(&CursorIndex::FromBeginning, false, 5)
+        );
+        assert_eq!(
+            wrapper1.get_range(&CursorIndex::FromEnding, -5),
+            wrapper1.get_range_bounded(&CursorIndex::FromEnding, false, -5)
+        );
+        // Paging with the last key never repeats an entry
+        let mut pages = Vec::new();
+        let mut cursor = CursorIndex::FromBeginning;
+        loop {
+            let page = wrapper1.get_range_bounded(&cursor, false, 2);
+            let Some((StringKey(last), _)) = page.last() else {
+                break;
+            };
+            cursor = CursorIndex::FromKey(last.clone());
+            pages.extend(page);
+        }
+        assert_eq!(wrapper1.get_all(), pages);
+    }
+
+    #[test]
+    fn test_partition_path() {
+        let temp_dir = TempDir::new("test_partition_path").unwrap();
+        let mut db_options = LevelDBOptions::new();
+        db_options.create_if_missing = true;
+        let db = Arc::new(
+            crate::bd::level_db::wrapper_leveldb::open_db::<StringKey>(
+                temp_dir.path(),
+                db_options,
+            )
+            .unwrap(),
+        );
+
+        let wrapper0 = WrapperLevelDB::<StringKey, u64>::new(db.clone(), EJEMPLO_TABLE);
+        let chained = wrapper0.partition("a").partition("b");
+        let path = wrapper0.partition_path(&["a", "b"]);
+        assert_eq!(chained.get_table_name(), path.get_table_name());
+        let segments = vec![String::from("a"), String::from("b")];
+        assert_eq!(
+            chained.get_table_name(),
+            wrapper0.partition_path(&segments).get_table_name()
+        );
+        let no_segments: [&str; 0] = [];
+        assert_eq!(
+            wrapper0.get_table_name(),
+            wrapper0.partition_path(&no_segments).get_table_name()
+        );
+        path.put("c", 1).unwrap();
+        assert_eq!(1, chained.get("c").unwrap());
+    }
+
+    #[test]
+    fn test_decode_raw_key() {
+        use leveldb::iterator::Iterable;
+
+        let temp_dir = TempDir::new("test_decode_raw_key").unwrap();
+        let mut db_options = LevelDBOptions::new();
+        db_options.create_if_missing = true;
+        let db = Arc::new(
+            crate::bd::level_db::wrapper_leveldb::open_db::<StringKey>(
+                temp_dir.path(),
+                db_options,
+            )
+            .unwrap(),
+        );
+
+        let wrapper0 = WrapperLevelDB::<StringKey, u64>::new(db.clone(), EJEMPLO_TABLE);
+        let wrapper1 = WrapperLevelDB::<StringKey, u64>::new(db.clone(), PRUEBA_TABLE);
+        let wrapper00 = wrapper0.partition("SUB0");
+        let wrapper001 = wrapper00.partition("SUB1");
+        wrapper0.put("a", 0).unwrap();
+        wrapper00.put("b", 1).unwrap();
+        wrapper001.put("c", 2).unwrap();
+        wrapper1.put("d", 3).unwrap();
+
+        let separator = wrapper0.get_separator();
+        let raw_keys: Vec<String> = db
+            .as_ref()
+            .keys_iter(leveldb::options::ReadOptions::new())
+            .map(|key| key.0)
+            .collect();
+        let decoded: Vec<(Vec<String>, String)> = raw_keys
+            .iter()
+            .filter_map(|raw| wrapper0.decode_raw_key(raw))
+            .collect();
+        assert_eq!(
+            vec![
+                (
+                    vec!["SUB0".to_string(), "SUB1".to_string()],
+                    "c".to_string()
+                ),
+                (vec!["SUB0".to_string()], "b".to_string()),
+                (vec![], "a".to_string()),
+            ],
+            decoded
+        );
+        // Paths are relative to the wrapper that decodes
+        assert_eq!(
+            Some((vec!["SUB1".to_string()], "c".to_string())),
+            wrapper00.decode_raw_key(&format!(
+                "{}{}SUB0{}SUB1{}c",
+                EJEMPLO_TABLE, separator, separator, separator
+            ))
+        );
+        assert_eq!(
+            None,
+            wrapper00.decode_raw_key(&format!("{}{}d", PRUEBA_TABLE, separator))
+        );
+        assert_eq!(
+            None,
+            wrapper0.decode_raw_key(&format!("{}{}d", PRUEBA_TABLE, separator))
+        );
+    }
+
+    #[test]
+    fn test_set_sync() {
+        let temp_dir = TempDir::new("test_set_sync").unwrap();
+        let mut db_options = LevelDBOptions::new();
+        db_options.create_if_missing = true;
+        let db = Arc::new(
+            crate::bd::level_db::wrapper_leveldb::open_db::<StringKey>(
+                temp_dir.path(),
+                db_options,
+            )
+            .unwrap(),
+        );
+
+        let wrapper0 = WrapperLevelDB::<StringKey, u64>::new(db.clone(), EJEMPLO_TABLE);
+        wrapper0.set_sync(false);
+        let partition = wrapper0.partition("SUB");
+        for i in 0..100u64 {
+            wrapper0.put(&i.to_string(), i).unwrap();
+            partition.put(&i.to_string(), i).unwrap();
+        }
+        assert_eq!(0, wrapper0.update("0", 1000).unwrap());
+        assert_eq!(Some(99), wrapper0.del("99").unwrap());
+        // The writes are visible from any handle of the same process
+        let reader = WrapperLevelDB::<StringKey, u64>::new(db.clone(), EJEMPLO_TABLE);
+        assert_eq!(1000, reader.get("0").unwrap());
+        assert_eq!(50, reader.get("50").unwrap());
+        assert!(reader.get("99").is_err());
+        assert_eq!(100, reader.partition("SUB").get_count());
+        wrapper0.set_sync(true);
+        wrapper0.put("99", 99).unwrap();
+        assert_eq!(99, reader.get("99").unwrap());
+    }
+
+    #[test]
+    fn test_put_batch() {
+        let temp_dir = TempDir::new("test_put_batch").unwrap();
+        let mut db_options = LevelDBOptions::new();
+        db_options.create_if_missing = true;
+        let db = Arc::new(
+            crate::bd::level_db::wrapper_leveldb::open_db::<StringKey>(
+                temp_dir.path(),
+                db_options,
+            )
+            .unwrap(),
+        );
+
+        let wrapper0 = WrapperLevelDB::<StringKey, u64>::new(db.clone(), EJEMPLO_TABLE);
+        wrapper0.put("a", 0).unwrap();
+        wrapper0
+            .put_batch(vec![("a".into(), 1), ("b".into(), 2)])
+            .unwrap();
+        assert_eq!(
+            vec![
+                (StringKey("a".to_string()), 1),
+                (StringKey("b".to_string()), 2)
+            ],
+            wrapper0.get_all()
+        );
+        wrapper0.put_batch(vec![]).unwrap();
+        assert_eq!(2, wrapper0.get_count());
+    }
+
+    #[test]
+    fn test_codecs() {
+        let temp_dir = TempDir::new("test_codecs").unwrap();
+        let mut db_options = LevelDBOptions::new();
+        db_options.create_if_missing = true;
+        let db = Arc::new(
+            crate::bd::level_db::wrapper_leveldb::open_db::<StringKey>(
+                temp_dir.path(),
+                db_options,
+            )
+            .unwrap(),
+        );
+
+        let value = vec![String::from("a"), String::from("b")];
+        let bincode =
+            WrapperLevelDB::<StringKey, Vec<String>, BincodeCodec>::new(db.clone(), EJEMPLO_TABLE);
+        let json =
+            WrapperLevelDB::<StringKey, Vec<String>, JsonCodec>::new(db.clone(), PRUEBA_TABLE);
+        bincode.put("key", value.clone()).unwrap();
+        json.put("key", value.clone()).unwrap();
+        assert_eq!(value, bincode.get("key").unwrap());
+        assert_eq!(value, json.get("key").unwrap());
+        assert_eq!(
+            vec![(StringKey("key".to_string()), value.clone())],
+            json.get_all()
+        );
+        // JSON values are readable after the tag
+        let stored = json.get_bytes("key").unwrap().to_vec();
+        assert_eq!(b"J[\"a\",\"b\"]".to_vec(), stored);
+        // The default codec is bincode
+        let default = WrapperLevelDB::<StringKey, Vec<String>>::new(db.clone(), EJEMPLO_TABLE);
+        assert_eq!(value, default.get("key").unwrap());
+        // Values written with another codec are not misread
+        let mixed = WrapperLevelDB::<StringKey, Vec<String>>::new(db.clone(), PRUEBA_TABLE);
+        assert!(matches!(
+            mixed.get("key"),
+            Err(WrapperLevelDBErrors::UnknownFormatTag(b'J'))
+        ));
+        let mixed = WrapperLevelDB::<StringKey, Vec<String>, JsonCodec>::new(db, EJEMPLO_TABLE);
+        assert!(matches!(
+            mixed.get("key"),
+            Err(WrapperLevelDBErrors::UnknownFormatTag(FORMAT_BINCODE_V1))
+        ));
+    }
+
+    #[test]
+    fn test_metrics() {
+        let temp_dir = TempDir::new("test_metrics").unwrap();
+        let mut db_options = LevelDBOptions::new();
+        db_options.create_if_missing = true;
+        let db = Arc::new(
+            crate::bd::level_db::wrapper_leveldb::open_db::<StringKey>(
+                temp_dir.path(),
+                db_options,
+            )
+            .unwrap(),
+        );
+
+        let metrics = Arc::new(Metrics::default());
+        let wrapper0 = WrapperLevelDB::<StringKey, u64>::new(db.clone(), EJEMPLO_TABLE)
+            .with_metrics(metrics.clone());
+        let untracked = WrapperLevelDB::<StringKey, u64>::new(db.clone(), PRUEBA_TABLE);
+        assert_eq!(TableMetrics::default(), wrapper0.metrics_snapshot());
+
+        wrapper0.put("a", 0).unwrap();
+        wrapper0
+            .put_batch(vec![("b".into(), 1), ("c".into(), 2)])
+            .unwrap();
+        wrapper0.get("a").unwrap();
+        wrapper0.get("missing").unwrap_err();
+        assert!(wrapper0.exists("b").unwrap());
+        // Reads the old value before deleting it
+        wrapper0.del("c").unwrap();
+        // Partitions count in the handle of their table
+        wrapper0.partition("SUB").put("a", 0).unwrap();
+        wrapper0.get_all();
+        wrapper0.get_count();
+        wrapper0.get_range(&CursorIndex::FromBeginning, 2);
+        assert_eq!(2, wrapper0.delete_range("a", "c").unwrap());
+        untracked.put("a", 0).unwrap();
+        untracked.get("a").unwrap();
+
+        let expected = TableMetrics {
+            gets: 4,
+            puts: 4,
+            dels: 3,
+            scans: 4,
+        };
+        assert_eq!(expected, wrapper0.metrics_snapshot());
+        assert_eq!(expected, metrics.snapshot());
+        assert_eq!(TableMetrics::default(), untracked.metrics_snapshot());
+    }
+
+    #[test]
+    fn test_list_partitions() {
+        let temp_dir = TempDir::new("test_list_partitions").unwrap();
+        let mut db_options = LevelDBOptions::new();
+        db_options.create_if_missing = true;
+        let db = Arc::new(
+            crate::bd::level_db::wrapper_leveldb::open_db::<StringKey>(
+                temp_dir.path(),
+                db_options,
+            )
+            .unwrap(),
+        );
+
+        let wrapper0 = WrapperLevelDB::<StringKey, u64>::new(db.clone(), EJEMPLO_TABLE);
+        let sibling = WrapperLevelDB::<StringKey, u64>::new(db.clone(), PRUEBA_TABLE);
+        assert!(wrapper0.list_partitions().is_empty());
+        for (i, key) in ["a", "b", "c"].iter().enumerate() {
+            wrapper0.put(key, i as u64).unwrap();
+            wrapper0.partition("SUB1").put(key, i as u64).unwrap();
+            wrapper0.partition("SUB0").put(key, i as u64).unwrap();
+            sibling.partition("OTHER").put(key, i as u64).unwrap();
+        }
+        wrapper0
+            .partition_path(&["SUB2", "INNER"])
+            .put("a", 0)
+            .unwrap();
+        // Direct keys after every partition
+        wrapper0.put("z", 0).unwrap();
+        // Partition with an empty name inside a partition
+        wrapper0.partition_path(&["SUB1", ""]).put("a", 0).unwrap();
+        assert_eq!(
+            vec!["SUB0".to_string(), "SUB1".to_string(), "SUB2".to_string()],
+            wrapper0.list_partitions()
+        );
+        assert_eq!(
+            vec!["".to_string(), "INNER".to_string()],
+            [
+                wrapper0.partition("SUB1").list_partitions(),
+                wrapper0.partition("SUB2").list_partitions()
+            ]
+            .concat()
+        );
+        assert_eq!(vec!["OTHER".to_string()], sibling.list_partitions());
+        assert!(wrapper0.partition("SUB0").list_partitions().is_empty());
+    }
+
+    #[test]
+    fn test_delete_range() {
+        let temp_dir = TempDir::new("test_delete_range").unwrap();
+        let mut db_options = LevelDBOptions::new();
+        db_options.create_if_missing = true;
+        let db = Arc::new(
+            crate::bd::level_db::wrapper_leveldb::open_db::<StringKey>(
+                temp_dir.path(),
+                db_options,
+            )
+            .unwrap(),
+        );
+
+        let wrapper0 = WrapperLevelDB::<StringKey, u64>::new(db.clone(), EJEMPLO_TABLE);
+        let partition = wrapper0.partition("c");
+        let sibling = WrapperLevelDB::<StringKey, u64>::new(db.clone(), PRUEBA_TABLE);
+        for (i, key) in ["a", "b", "c", "d", "e"].iter().enumerate() {
+            wrapper0.put(key, i as u64).unwrap();
+            partition.put(key, i as u64).unwrap();
+            sibling.put(key, i as u64).unwrap();
+        }
+        assert_eq!(3, wrapper0.delete_range("b", "e").unwrap());
+        let surviving = |wrapper: &WrapperLevelDB<StringKey, u64>| -> Vec<&str> {
+            ["a", "b", "c", "d", "e"]
+                .into_iter()
+                .filter(|key| wrapper.exists(key).unwrap())
+                .collect()
+        };
+        assert_eq!(vec!["a", "e"], surviving(&wrapper0));
+        // Partitions and other tables inside the range survive
+        assert_eq!(5, partition.get_count());
+        assert_eq!(5, sibling.get_count());
+        assert_eq!(0, wrapper0.delete_range("b", "e").unwrap());
+        assert_eq!(0, wrapper0.delete_range("e", "a").unwrap());
+        // Keys of a partition are deleted through the partition
+        assert_eq!(2, partition.delete_range("d", "z").unwrap());
+        assert_eq!(3, partition.get_count());
+        assert_eq!(vec!["a", "b", "c"], surviving(&partition));
+        assert_eq!(1, wrapper0.delete_range("e", "f").unwrap());
+        assert_eq!(vec!["a"], surviving(&wrapper0));
+    }
+
+    #[test]
+    fn test_get_range_bytes() {
+        let temp_dir = TempDir::new("test_get_range_bytes").unwrap();
+        let mut db_options = LevelDBOptions::new();
+        db_options.create_if_missing = true;
+        let db = Arc::new(
+            crate::bd::level_db::wrapper_leveldb::open_db::<StringKey>(
+                temp_dir.path(),
+                db_options,
+            )
+            .unwrap(),
+        );
+
+        let wrapper0 = WrapperLevelDB::<StringKey, u64>::new(db.clone(), EJEMPLO_TABLE);
+        let wrapper1 = WrapperLevelDB::<StringKey, u64>::new(db.clone(), PRUEBA_TABLE);
+        let wrapper2 = WrapperLevelDB::<StringKey, u64>::new(db.clone(), TEST_TABLE);
+        set_up_entries(wrapper0, wrapper1, wrapper2);
+
+        let wrapper1 = WrapperLevelDB::<StringKey, u64>::new(db.clone(), PRUEBA_TABLE);
+        for (cursor, quantity) in [
+            (CursorIndex::FromBeginning, 3),
+            (CursorIndex::FromEnding, -3),
+            (CursorIndex::FromKey("0a".into()), 2),
+        ] {
+            let expected: Vec<(StringKey, Vec<u8>)> = wrapper1
+                .get_range(&cursor, quantity)
+                .into_iter()
+                .map(|(key, value)| {
+                    let bytes = WrapperLevelDB::<StringKey, u64>::serialize(&value).unwrap();
+                    (key, bytes)
+                })
+                .collect();
+            assert_eq!(expected.len(), quantity.unsigned_abs());
+            assert_eq!(expected, wrapper1.get_range_bytes(&cursor, quantity));
+        }
+    }
+
+    #[test]
+    fn test_set_scan_fill_cache() {
+        let temp_dir = TempDir::new("test_set_scan_fill_cache").unwrap();
+        let mut db_options = LevelDBOptions::new();
+        db_options.create_if_missing = true;
+        let db = Arc::new(
+            crate::bd::level_db::wrapper_leveldb::open_db::<StringKey>(
+                temp_dir.path(),
+                db_options,
+            )
+            .unwrap(),
+        );
+
+        let wrapper0 = WrapperLevelDB::<StringKey, u64>::new(db.clone(), EJEMPLO_TABLE);
+        for i in 0..10u64 {
+            wrapper0.put(&i.to_string(), i).unwrap();
+        }
+        let all = wrapper0.get_all();
+        let range = wrapper0.get_range(&CursorIndex::FromKey("3".into()), 4);
+        wrapper0.set_scan_fill_cache(false);
+        assert!(!wrapper0.get_scan_options().fill_cache);
+        // Single key reads are not affected
+        assert!(wrapper0.get_read_options().fill_cache);
+        assert!(!wrapper0.partition("inner").get_scan_options().fill_cache);
+
+        assert_eq!(10, all.len());
+        assert_eq!(all, wrapper0.get_all());
+        assert_eq!(
+            range,
+            wrapper0.get_range(&CursorIndex::FromKey("3".into()), 4)
+        );
+        assert_eq!(10, wrapper0.get_count());
+        assert_eq!(5, wrapper0.get("5").unwrap());
+    }
+
+    #[test]
+    fn test_count_matching() {
+        let temp_dir = TempDir::new("test_count_matching").unwrap();
+        let mut db_options = LevelDBOptions::new();
+        db_options.create_if_missing = true;
+        let db = Arc::new(
+            crate::bd::level_db::wrapper_leveldb::open_db::<StringKey>(
+                temp_dir.path(),
+                db_options,
+            )
+            .unwrap(),
+        );
+
+        let wrapper0 = WrapperLevelDB::<StringKey, u64>::new(db.clone(), EJEMPLO_TABLE);
+        for i in 0..10u64 {
+            wrapper0.put(&i.to_string(), i).unwrap();
+        }
+        wrapper0.partition("inner").put("10", 10).unwrap();
+        // A single byte can not be decoded as an u64
+        wrapper0.put_raw("corrupt", &[1]).unwrap();
+        let other = WrapperLevelDB::<StringKey, u64>::new(db.clone(), "other");
+        other.put("12", 12).unwrap();
+
+        assert_eq!(6, wrapper0.count_matching(|value| value % 2 == 0));
+        assert_eq!(11, wrapper0.count_matching(|_| true));
+        assert_eq!(0, wrapper0.count_matching(|value| *value > 10));
+    }
+
+    #[test]
+    fn test_is_empty() {
+        let temp_dir = TempDir::new("test_is_empty").unwrap();
+        let mut db_options = LevelDBOptions::new();
+        db_options.create_if_missing = true;
+        let db = Arc::new(
+            crate::bd::level_db::wrapper_leveldb::open_db::<StringKey>(
+                temp_dir.path(),
+                db_options,
+            )
+            .unwrap(),
+        );
+
+        let wrapper0 = WrapperLevelDB::<StringKey, u64>::new(db.clone(), EJEMPLO_TABLE);
+        assert!(wrapper0.is_empty());
+        // Tables sorted before and after this one do not count
+        let before = WrapperLevelDB::<StringKey, u64>::new(db.clone(), "A");
+        let after = WrapperLevelDB::<StringKey, u64>::new(db.clone(), "z");
+        before.put("1", 1).unwrap();
+        after.put("2", 2).unwrap();
+        assert!(wrapper0.is_empty());
+        assert!(wrapper0.partition("inner").is_empty());
+
+        wrapper0.partition("inner").put("3", 3).unwrap();
+        assert!(!wrapper0.is_empty());
+        assert!(!wrapper0.partition("inner").is_empty());
+        assert!(wrapper0.partition("other").is_empty());
+        wrapper0.partition("inner").del("3").unwrap();
+        assert!(wrapper0.is_empty());
+    }
+
+    #[test]
+    fn test_retry_policy() {
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            backoff: Duration::from_millis(1),
+        };
+        let leveldb_error = |message: &str| WrapperLevelDBErrors::LevelDBError {
+            source: leveldb::database::error::Error::new(message.into()),
+        };
+        assert!(leveldb_error("IO error: lock LOCK: already held by process").is_transient());
+        assert!(!leveldb_error("Corruption: bad block").is_transient());
+        assert!(!WrapperLevelDBErrors::DeserializeError.is_transient());
+
+        // A transient error is retried until the operation succeeds
+        let mut attempts = 0;
+        let result = policy.run(|| {
+            attempts += 1;
+            if attempts < 3 {
+                Err(leveldb_error("IO error: Resource temporarily unavailable"))
+            } else {
+                Ok(attempts)
+            }
+        });
+        assert_eq!(3, result.unwrap());
+        // and gives up once it runs out of attempts
+        let mut attempts = 0;
+        let result: Result<(), _> = policy.run(|| {
+            attempts += 1;
+            Err(leveldb_error("IO error: Resource temporarily unavailable"))
+        });
+        assert!(matches!(
+            result,
+            Err(WrapperLevelDBErrors::LevelDBError { .. })
+        ));
+        assert_eq!(3, attempts);
+        // Other errors are not retried
+        let mut attempts = 0;
+        let result: Result<(), _> = policy.run(|| {
+            attempts += 1;
+            Err(WrapperLevelDBErrors::DeserializeError)
+        });
+        assert!(matches!(
+            result,
+            Err(WrapperLevelDBErrors::DeserializeError)
+        ));
+        assert_eq!(1, attempts);
+
+        let temp_dir = TempDir::new("test_retry_policy").unwrap();
+        let mut db_options = LevelDBOptions::new();
+        db_options.create_if_missing = true;
+        let db = Arc::new(
+            crate::bd::level_db::wrapper_leveldb::open_db::<StringKey>(
+                temp_dir.path(),
+                db_options,
+            )
+            .unwrap(),
+        );
+        let wrapper0 = WrapperLevelDB::<StringKey, u64>::new(db.clone(), EJEMPLO_TABLE);
+        wrapper0.put_with_retry("a", 1, &policy).unwrap();
+        assert_eq!(1, wrapper0.get_with_retry("a", &policy).unwrap());
+        assert!(matches!(
+            wrapper0.get_with_retry("b", &policy),
+            Err(WrapperLevelDBErrors::EntryNotFoundError)
+        ));
+    }
+
+    #[test]
+    fn test_format_tag() {
+        let temp_dir = TempDir::new("test_format_tag").unwrap();
+        let mut db_options = LevelDBOptions::new();
+        db_options.create_if_missing = true;
+        let db = Arc::new(
+            crate::bd::level_db::wrapper_leveldb::open_db::<StringKey>(
+                temp_dir.path(),
+                db_options,
+            )
+            .unwrap(),
+        );
+
+        let wrapper0 = WrapperLevelDB::<StringKey, u64>::new(db.clone(), EJEMPLO_TABLE);
+        wrapper0.put("tagged", 7).unwrap();
+        let mut expected = vec![FORMAT_BINCODE_V1];
+        expected.extend(bincode::serialize(&7u64).unwrap());
+        assert_eq!(expected, wrapper0.get_bytes("tagged").unwrap().as_ref());
+        assert_eq!(7, wrapper0.get("tagged").unwrap());
+
+        wrapper0.put_raw("unknown", &[0xff, 1]).unwrap();
+        assert!(matches!(
+            wrapper0.get("unknown"),
+            Err(WrapperLevelDBErrors::UnknownFormatTag(0xff))
+        ));
+
+        // Values stored before the tag existed are read, and tagged by the upgrade
+        wrapper0
+            .put_raw("legacy", &bincode::serialize(&42u64).unwrap())
+            .unwrap();
+        assert_eq!(42, wrapper0.get("legacy").unwrap());
+        assert_eq!(
+            bincode::serialize(&42u64).unwrap(),
+            wrapper0.get_bytes("legacy").unwrap().as_ref()
+        );
+        wrapper0.del("unknown").unwrap();
+        assert_eq!(1, wrapper0.upgrade_untagged().unwrap());
+        assert_eq!(
+            Some(&FORMAT_BINCODE_V1),
+            wrapper0.get_bytes("legacy").unwrap().as_ref().first()
+        );
+        assert_eq!(42, wrapper0.get("legacy").unwrap());
+        assert_eq!(0, wrapper0.upgrade_untagged().unwrap());
+
+        // A legacy value starting with the tag is not taken for a shorter tagged
+        // one: the rest of this one decodes as an empty vector with trailing bytes
+        let wrapper1 = WrapperLevelDB::<StringKey, Vec<u8>>::new(db.clone(), "vectors");
+        let legacy = bincode::serialize(&vec![0u8; 0xB1]).unwrap();
+        assert_eq!(FORMAT_BINCODE_V1, legacy[0]);
+        wrapper1.put_raw("legacy", &legacy).unwrap();
+        assert_eq!(vec![0u8; 0xB1], wrapper1.get("legacy").unwrap());
+    }
+
+    #[test]
+    fn test_max_value_bytes() {
+        let temp_dir = TempDir::new("test_max_value_bytes").unwrap();
+        let mut db_options = LevelDBOptions::new();
+        db_options.create_if_missing = true;
+        let db = Arc::new(
+            crate::bd::level_db::wrapper_leveldb::open_db::<StringKey>(
+                temp_dir.path(),
+                db_options,
+            )
+            .unwrap(),
+        );
+
+        // A Vec<u8> is stored as the format tag, its u64 length and its bytes
+        let wrapper0 = WrapperLevelDB::<StringKey, Vec<u8>>::new(db.clone(), EJEMPLO_TABLE)
+            .with_max_value_bytes(13);
+        wrapper0.put("a", vec![0; 4]).unwrap();
+        assert!(matches!(
+            wrapper0.put("b", vec![0; 5]),
+            Err(WrapperLevelDBErrors::ValueTooLarge { size: 14, max: 13 })
+        ));
+        assert!(!wrapper0.exists("b").unwrap());
+        assert!(matches!(
+            wrapper0.update("a", vec![0; 5]),
+            Err(WrapperLevelDBErrors::ValueTooLarge { .. })
+        ));
+        assert_eq!(vec![0; 4], wrapper0.get("a").unwrap());
+        // The batch is rejected as a whole
+        assert!(matches!(
+            wrapper0.put_batch(vec![("c".into(), vec![0; 1]), ("d".into(), vec![0; 5])]),
+            Err(WrapperLevelDBErrors::ValueTooLarge { .. })
+        ));
+        assert!(!wrapper0.exists("c").unwrap());
+        // Partitions keep the limit
+        assert!(matches!(
+            wrapper0.partition("p").put("a", vec![0; 5]),
+            Err(WrapperLevelDBErrors::ValueTooLarge { .. })
+        ));
+    }
+
+    #[test]
+    fn test_get_or_insert_with() {
+        let temp_dir = TempDir::new("test_get_or_insert_with").unwrap();
+        let mut db_options = LevelDBOptions::new();
+        db_options.create_if_missing = true;
+        let db = Arc::new(
+            crate::bd::level_db::wrapper_leveldb::open_db::<StringKey>(
+                temp_dir.path(),
+                db_options,
+            )
+            .unwrap(),
+        );
+
+        let wrapper0 = WrapperLevelDB::<StringKey, u64>::new(db.clone(), EJEMPLO_TABLE);
+        // Missing entry: the value is computed and stored
+        assert_eq!(7, wrapper0.get_or_insert_with("a", || 7).unwrap());
+        assert_eq!(7, wrapper0.get("a").unwrap());
+        // Existing entry: the closure is not called
+        wrapper0.put("b", 1).unwrap();
+        assert_eq!(
+            1,
+            wrapper0
+                .get_or_insert_with("b", || panic!("Entry already exists"))
+                .unwrap()
+        );
+        assert_eq!(7, wrapper0.get_or_insert_with("a", || 8).unwrap());
+        // A value that cannot be deserialized is neither computed nor overwritten
+        let wrapper1 = WrapperLevelDB::<StringKey, u8>::new(db.clone(), PRUEBA_TABLE);
+        wrapper1.put("c", 1).unwrap();
+        let wrapper1 = WrapperLevelDB::<StringKey, u64>::new(db.clone(), PRUEBA_TABLE);
+        assert!(matches!(
+            wrapper1.get_or_insert_with("c", || panic!("Entry already exists")),
+            Err(WrapperLevelDBErrors::DeserializeError)
+        ));
+        let wrapper1 = WrapperLevelDB::<StringKey, u8>::new(db.clone(), PRUEBA_TABLE);
+        assert_eq!(1, wrapper1.get("c").unwrap());
+    }
+
+    #[test]
+    fn test_get_verified() {
+        let temp_dir = TempDir::new("test_get_verified").unwrap();
+        let mut db_options = LevelDBOptions::new();
+        db_options.create_if_missing = true;
+        let db = Arc::new(
+            crate::bd::level_db::wrapper_leveldb::open_db::<StringKey>(
+                temp_dir.path(),
+                db_options,
+            )
+            .unwrap(),
+        );
+
+        let mut wrapper0 = WrapperLevelDB::<StringKey, u64>::new(db.clone(), EJEMPLO_TABLE);
+        let mut read_options = leveldb::options::ReadOptions::new();
+        read_options.verify_checksums = false;
+        wrapper0.set_read_options(read_options);
+        for i in 0..100u64 {
+            wrapper0.put(&i.to_string(), i).unwrap();
+        }
+        // Force the records to be read from disk and not from the memtable
+        wrapper0.compact_range();
+        assert_eq!(42, wrapper0.get_verified("42").unwrap());
+        assert!(matches!(
+            wrapper0.get_verified("100"),
+            Err(WrapperLevelDBErrors::EntryNotFoundError)
+        ));
+        // The default read options of the wrapper are left untouched
+        assert_eq!(42, wrapper0.get("42").unwrap());
+    }
+
+    #[test]
+    fn test_compact_range() {
+        let temp_dir = TempDir::new("test_compact_range").unwrap();
+        let mut db_options = LevelDBOptions::new();
+        db_options.create_if_missing = true;
+        let db = Arc::new(
+            crate::bd::level_db::wrapper_leveldb::open_db::<StringKey>(
+                temp_dir.path(),
+                db_options,
+            )
+            .unwrap(),
+        );
+
+        let wrapper0 = WrapperLevelDB::<StringKey, u64>::new(db.clone(), EJEMPLO_TABLE);
+        let wrapper1 = WrapperLevelDB::<StringKey, u64>::new(db.clone(), PRUEBA_TABLE);
+        wrapper1.put("a", 1).unwrap();
+        let other_size = wrapper1.approximate_size();
+        assert!(other_size > 0);
+        for i in 0..1000u64 {
+            wrapper0.put(&i.to_string(), i).unwrap();
+        }
+        assert!(wrapper0.approximate_size() > other_size);
+        for i in 0..1000u64 {
+            wrapper0.del(&i.to_string()).unwrap();
+        }
+        wrapper0.compact_range();
+        assert_eq!(0, wrapper0.get_count());
+        assert_eq!(0, wrapper0.approximate_size());
+        // The rest of the tables are untouched
+        assert_eq!(other_size, wrapper1.approximate_size());
+        assert_eq!(1, wrapper1.get("a").unwrap());
+    }
+
+    #[test]
+    fn test_approximate_size_prefix_table() {
+        let temp_dir = TempDir::new("test_approximate_size_prefix_table").unwrap();
+        let mut db_options = LevelDBOptions::new();
+        db_options.create_if_missing = true;
+        let db = Arc::new(
+            crate::bd::level_db::wrapper_leveldb::open_db::<StringKey>(
+                temp_dir.path(),
+                db_options,
+            )
+            .unwrap(),
+        );
+
+        let event = WrapperLevelDB::<StringKey, u64>::new(db.clone(), "event");
+        let index = WrapperLevelDB::<StringKey, u64>::new(db.clone(), "event-hash-index");
+        event.put("0", 0).unwrap();
+        let event_size = event.approximate_size();
+        assert!(event_size > 0);
+        // "event-" sorts before the keys of "event", whose separator comes last
+        index.put("0", 0).unwrap();
+        assert_eq!(event_size, event.approximate_size());
+        // Same entry, under the 11 more bytes of "-hash-index"
+        assert_eq!(event_size + 11, index.approximate_size());
+    }
+
     // TODO: Unit test for new_subtable
     #[test]
     fn test_simple_new_subtable() {
@@ -1,11 +1,12 @@
 pub mod db;
 pub mod level_db;
+pub mod snapshot;
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 use crate::{
-    errors::SubjectError,
-    identifier::DigestIdentifier,
+    errors::{ConflictError, DbError},
+    identifier::{DigestIdentifier, KeyIdentifier},
     models::{
         event::Event,
         event_content::EventContent,
@@ -16,7 +17,20 @@ use crate::{
 };
 
 pub trait TapleDB: Sized {
-    fn get_event(&self, subject_id: &DigestIdentifier, sn: u64) -> Option<Event>;
+    /// Event with SN `sn` of the subject, `None` if it is not stored. Errors other
+    /// than a missing event are returned instead of being taken as a missing event.
+    fn get_event(&self, subject_id: &DigestIdentifier, sn: u64) -> Result<Option<Event>, DbError>;
+
+    fn get_event_before(
+        &self,
+        subject_id: &DigestIdentifier,
+        sn: u64,
+    ) -> Result<Option<Event>, DbError>;
+
+    /// Event with SN 0 of the subject, which must be the `Create` request every
+    /// rebuild or verification of the subject starts from. `None` if it is missing
+    /// or is not a `Create` request.
+    fn get_genesis_event(&self, subject_id: &DigestIdentifier) -> Result<Option<Event>, DbError>;
 
     fn get_events_by_range(
         &self,
@@ -24,42 +38,118 @@ pub trait TapleDB: Sized {
         from: Option<String>,
         quantity: isize,
     ) -> Vec<Event>;
-    fn set_event(&self, subject_id: &DigestIdentifier, event: Event);
+
+    /// Events of the subject with an SN between `from_sn` and `to_sn`, both
+    /// included, in SN order.
+    fn get_events_between(
+        &self,
+        subject_id: &DigestIdentifier,
+        from_sn: u64,
+        to_sn: u64,
+    ) -> Vec<Event>;
+
+    fn set_event(&self, subject_id: &DigestIdentifier, event: Event) -> Result<(), DbError>;
+
+    fn set_events(&self, subject_id: &DigestIdentifier, events: Vec<Event>) -> Result<(), DbError>;
+
+    fn get_event_by_hash(&self, hash: &DigestIdentifier) -> Result<Option<Event>, DbError>;
+
+    fn has_event(&self, subject_id: &DigestIdentifier, sn: u64) -> Result<bool, DbError>;
+
+    fn event_count(&self, subject_id: &DigestIdentifier) -> usize;
+
+    fn iter_event_contents(
+        &self,
+        subject_id: &DigestIdentifier,
+    ) -> std::vec::IntoIter<EventContent>;
 
     fn get_signatures(&self, subject_id: &DigestIdentifier, sn: u64) -> Option<HashSet<Signature>>;
 
+    fn get_signatures_count(&self, subject_id: &DigestIdentifier, sn: u64) -> usize;
+
+    /// Signatures of every event of a subject, by SN.
+    fn get_all_signatures(
+        &self,
+        subject_id: &DigestIdentifier,
+    ) -> BTreeMap<u64, HashSet<Signature>>;
+
     fn set_signatures(
         &self,
         subject_id: &DigestIdentifier,
         sn: u64,
         signatures: HashSet<Signature>,
-    );
+    ) -> Result<(), DbError>;
+
+    /// Records when the approval of `approver` for event `sn` of a subject
+    /// arrived, in milliseconds since the Unix epoch. `None` records the current time.
+    fn set_approval_timestamp(
+        &self,
+        subject_id: &DigestIdentifier,
+        sn: u64,
+        approver: &KeyIdentifier,
+        timestamp: Option<i64>,
+    ) -> Result<(), DbError>;
+
+    /// Approvers of event `sn` of a subject with the arrival time of their
+    /// approvals, from the earliest to the latest.
+    fn get_approval_timeline(
+        &self,
+        subject_id: &DigestIdentifier,
+        sn: u64,
+    ) -> Vec<(KeyIdentifier, i64)>;
 
     fn get_subject(&self, subject_id: &DigestIdentifier) -> Option<Subject>;
 
-    fn set_subject(&self, subject_id: &DigestIdentifier, subject: Subject);
+    fn get_subjects_by_namespace(&self, namespace: &str) -> Vec<Subject>;
 
-    fn set_negociating_true(&self, subject_id: &DigestIdentifier) -> Result<(), SubjectError>;
+    fn list_namespaces(&self) -> HashSet<String>;
 
-    fn apply_event_sourcing(&self, event_content: EventContent) -> Result<(), SubjectError>;
+    fn set_subject(&self, subject_id: &DigestIdentifier, subject: Subject) -> Result<(), DbError>;
+
+    fn set_subject_if_sn(
+        &self,
+        subject_id: &DigestIdentifier,
+        subject: Subject,
+        expected_current_sn: u64,
+    ) -> Result<(), ConflictError>;
+
+    fn set_negociating_true(&self, subject_id: &DigestIdentifier) -> Result<(), DbError>;
+
+    fn apply_event_sourcing(&self, event_content: EventContent) -> Result<(), DbError>;
 
     fn get_all_heads(&self) -> HashMap<DigestIdentifier, LedgerState>;
+    /// Same heads as `get_all_heads`, sorted by subject id so that the heads of
+    /// two nodes can be compared.
+    fn get_all_heads_sorted(&self) -> Vec<(DigestIdentifier, LedgerState)>;
 
     fn get_all_subjects(&self) -> Vec<Subject>;
 
+    fn get_all_governances(&self) -> Vec<Subject>;
+
     fn get_all_request(&self) -> Vec<EventRequest>;
+    /// Number of pending requests of a single subject.
+    fn request_count(&self, subject_id: &DigestIdentifier) -> usize;
+    /// Pending requests of a single subject.
+    fn get_requests(&self, subject_id: &DigestIdentifier) -> Vec<EventRequest>;
     fn get_request(
         &self,
         subject_id: &DigestIdentifier,
         request_id: &DigestIdentifier,
     ) -> Option<EventRequest>;
-    fn set_request(&self, subject_id: &DigestIdentifier, request: EventRequest);
+    fn set_request(
+        &self,
+        subject_id: &DigestIdentifier,
+        request: EventRequest,
+    ) -> Result<(), DbError>;
     fn del_request(
         &self,
         subject_id: &DigestIdentifier,
         request_id: &DigestIdentifier,
     ) -> Option<EventRequest>;
 
-    fn get_controller_id(&self) -> Option<String>;
-    fn set_controller_id(&self, controller_id: String);
+    fn get_controller_id(&self) -> Result<Option<KeyIdentifier>, DbError>;
+    fn set_controller_id(&self, controller_id: &KeyIdentifier) -> Result<(), DbError>;
+
+    fn put_blob(&self, bytes: &[u8]) -> Result<DigestIdentifier, DbError>;
+    fn get_blob(&self, blob_id: &DigestIdentifier) -> Option<Vec<u8>>;
 }
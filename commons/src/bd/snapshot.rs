@@ -0,0 +1,95 @@
+//! Views of the database that may be slightly out of date
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::{identifier::DigestIdentifier, models::state::LedgerState};
+
+use super::TapleDB;
+
+/// Keeps the heads of every subject for `ttl`, so that frequent readers that
+/// tolerate stale data, like metrics endpoints, do not scan the subjects table
+/// on every call. The heads are only read again once they expire, on the next call.
+pub struct CachedSnapshot<D: TapleDB> {
+    db: D,
+    ttl: Duration,
+    heads: Mutex<Option<(Instant, HashMap<DigestIdentifier, LedgerState>)>>,
+}
+
+impl<D: TapleDB> CachedSnapshot<D> {
+    pub fn new(db: D, ttl: Duration) -> Self {
+        Self {
+            db,
+            ttl,
+            heads: Mutex::new(None),
+        }
+    }
+
+    /// Heads of every subject, as they were at most `ttl` ago.
+    pub fn heads_cached(&self) -> HashMap<DigestIdentifier, LedgerState> {
+        let mut heads = self.heads.lock().unwrap();
+        match heads.as_ref() {
+            Some((read_at, cached)) if read_at.elapsed() < self.ttl => cached.clone(),
+            _ => {
+                let fresh = self.db.get_all_heads();
+                *heads = Some((Instant::now(), fresh.clone()));
+                fresh
+            }
+        }
+    }
+
+    /// Database the snapshot reads from.
+    pub fn db(&self) -> &D {
+        &self.db
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tempdir::TempDir;
+
+    use crate::{
+        bd::{
+            db::{open_db, DB},
+            TapleDB,
+        },
+        identifier::DigestIdentifier,
+        models::state::{LedgerState, Subject},
+    };
+
+    use super::CachedSnapshot;
+
+    fn store_subject(db: &DB, seed: &str) {
+        let subject_id = DigestIdentifier::from_serializable_borsh(seed).unwrap();
+        let subject = Subject::new_empty(LedgerState::default());
+        db.set_subject(&subject_id, subject).unwrap();
+    }
+
+    #[test]
+    fn test_heads_cached() {
+        let temp_dir = TempDir::new("test_heads_cached").unwrap();
+        let snapshot =
+            CachedSnapshot::new(DB::new(open_db(temp_dir.path())), Duration::from_secs(3600));
+        store_subject(snapshot.db(), "a");
+        assert_eq!(1, snapshot.heads_cached().len());
+        // Within the TTL the new subject is not seen
+        store_subject(snapshot.db(), "b");
+        assert_eq!(1, snapshot.heads_cached().len());
+        assert_eq!(2, snapshot.db().get_all_heads().len());
+    }
+
+    #[test]
+    fn test_heads_cached_expiry() {
+        let temp_dir = TempDir::new("test_heads_cached_expiry").unwrap();
+        let snapshot = CachedSnapshot::new(DB::new(open_db(temp_dir.path())), Duration::ZERO);
+        store_subject(snapshot.db(), "a");
+        assert_eq!(1, snapshot.heads_cached().len());
+        // Every view has expired by the next call
+        store_subject(snapshot.db(), "b");
+        assert_eq!(2, snapshot.heads_cached().len());
+    }
+}
@@ -11,14 +11,15 @@ use base64::decode_config;
 use serde::{de::Deserializer, Deserialize, Serialize, Serializer};
 use std::convert::TryFrom;
 use std::convert::TryInto;
+use zeroize::Zeroizing;
 
 pub type Ed25519KeyPair = BaseKeyPair<PublicKey, SecretKey>;
 
 impl KeyGenerator for Ed25519KeyPair {
     fn from_seed(seed: &[u8]) -> Self {
-        let secret_seed = create_seed(seed).expect("invalid seed");
+        let secret_seed = Zeroizing::new(create_seed(seed).expect("invalid seed"));
         let sk: SecretKey =
-            SecretKey::from_bytes(&secret_seed).expect("cannot generate secret key");
+            SecretKey::from_bytes(secret_seed.as_slice()).expect("cannot generate secret key");
         let pk: PublicKey = (&sk).try_into().expect("cannot generate public key");
         Self {
             public_key: pk,
@@ -56,10 +57,11 @@ impl KeyMaterial for Ed25519KeyPair {
     }
 
     fn to_bytes(&self) -> Vec<u8> {
-        let mut bytes: [u8; KEYPAIR_LENGTH] = [0u8; KEYPAIR_LENGTH];
-        bytes[..SECRET_KEY_LENGTH].copy_from_slice(&self.secret_key_bytes());
+        // Built in place so no copy of the secret key is left behind
+        let mut bytes = vec![0u8; KEYPAIR_LENGTH];
+        bytes[..SECRET_KEY_LENGTH].copy_from_slice(&Zeroizing::new(self.secret_key_bytes()));
         bytes[SECRET_KEY_LENGTH..].copy_from_slice(&self.public_key_bytes());
-        bytes.to_vec()
+        bytes
     }
 }
 
@@ -114,7 +116,7 @@ impl Serialize for Ed25519KeyPair {
     where
         S: Serializer,
     {
-        serializer.serialize_str(&self.to_str())
+        serializer.serialize_str(&Zeroizing::new(self.to_str()))
     }
 }
 
@@ -124,8 +126,10 @@ impl<'de> Deserialize<'de> for Ed25519KeyPair {
     where
         D: Deserializer<'de>,
     {
-        let s = String::deserialize(deserializer)?;
-        let bytes = decode_config(&s, base64::URL_SAFE).map_err(serde::de::Error::custom)?;
+        let s = Zeroizing::new(String::deserialize(deserializer)?);
+        let bytes = Zeroizing::new(
+            decode_config(s.as_str(), base64::URL_SAFE).map_err(serde::de::Error::custom)?,
+        );
 
         Ok(Ed25519KeyPair::from_secret_key(&bytes[..SECRET_KEY_LENGTH]))
     }
@@ -22,6 +22,7 @@ pub use secp256k1::Secp256k1KeyPair;
 use serde::{Deserialize, Serialize};
 #[cfg(feature = "x25519")]
 pub use x25519::X25519KeyPair;
+use zeroize::Zeroizing;
 
 use crate::identifier::{self, derive::KeyDerivator};
 
@@ -78,7 +79,10 @@ pub trait KeyMaterial {
 
     /// Returns String from key pair encoded in base64
     fn to_str(&self) -> String {
-        encode_config(self.to_bytes(), base64::URL_SAFE_NO_PAD)
+        encode_config(
+            Zeroizing::new(self.to_bytes()).as_slice(),
+            base64::URL_SAFE_NO_PAD,
+        )
     }
 }
 
@@ -131,12 +135,12 @@ impl Clone for KeyPair {
             // KeyPair::Bls12381(kp) => KeyPair::Bls12381(
             //     Bls12381KeyPair::from_secret_key(&kp.secret_key_bytes()),
             // ),
-            KeyPair::Ed25519(kp) => {
-                KeyPair::Ed25519(Ed25519KeyPair::from_secret_key(&kp.secret_key_bytes()))
-            }
-            KeyPair::Secp256k1(kp) => {
-                KeyPair::Secp256k1(Secp256k1KeyPair::from_secret_key(&kp.secret_key_bytes()))
-            }
+            KeyPair::Ed25519(kp) => KeyPair::Ed25519(Ed25519KeyPair::from_secret_key(
+                &Zeroizing::new(kp.secret_key_bytes()),
+            )),
+            KeyPair::Secp256k1(kp) => KeyPair::Secp256k1(Secp256k1KeyPair::from_secret_key(
+                &Zeroizing::new(kp.secret_key_bytes()),
+            )),
             // KeyPair::X25519(kp) => KeyPair::X25519(
             //     X25519KeyPair::from_secret_key(&kp.secret_key_bytes()),
             // ),
@@ -144,6 +148,19 @@ impl Clone for KeyPair {
     }
 }
 
+/// Wipes the secret key when the key pair is dropped. Ed25519 secret keys already
+/// do it by themselves, but secp256k1 ones have to be cleared explicitly.
+impl Drop for KeyPair {
+    fn drop(&mut self) {
+        #[cfg(feature = "secp256k1")]
+        if let KeyPair::Secp256k1(key_pair) = self {
+            if let Some(secret_key) = key_pair.secret_key.as_mut() {
+                secret_key.clear();
+            }
+        }
+    }
+}
+
 impl KeyMaterial for KeyPair {
     fn public_key_bytes(&self) -> Vec<u8> {
         match self {
@@ -249,7 +266,10 @@ pub fn create_seed(initial_seed: &[u8]) -> Result<[u8; 32], Error> {
 #[cfg(test)]
 mod tests {
 
-    use super::{create_seed, ed25519::Ed25519KeyPair, generate, Payload, DSA};
+    use super::{
+        create_seed, ed25519::Ed25519KeyPair, generate, KeyGenerator, KeyMaterial, KeyPair,
+        Payload, DSA,
+    };
 
     #[cfg(feature = "secp256k1")]
     use super::secp256k1::Secp256k1KeyPair;
@@ -288,6 +308,38 @@ mod tests {
         matches!(valid, Ok(()));
     }
 
+    #[test]
+    fn test_drop_key_pair() {
+        let message = Payload::Buffer(b"secret message".to_vec());
+        let key_pair = KeyPair::Ed25519(Ed25519KeyPair::from_seed(&[1]));
+        let signature = key_pair.sign(message.clone()).unwrap();
+        let serialized = serde_json::to_string(&key_pair).unwrap();
+        {
+            // Dropping a clone must not affect the original key pair
+            let cloned = key_pair.clone();
+            assert_eq!(cloned.secret_key_bytes(), key_pair.secret_key_bytes());
+        }
+        assert_eq!(signature, key_pair.sign(message.clone()).unwrap());
+        drop(key_pair);
+        let key_pair: KeyPair = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(signature, key_pair.sign(message).unwrap());
+    }
+
+    #[test]
+    #[cfg(feature = "secp256k1")]
+    fn test_drop_secp256k1_key_pair() {
+        let message = Payload::Buffer(b"secret message".to_vec());
+        let key_pair = KeyPair::Secp256k1(Secp256k1KeyPair::from_seed(&[1]));
+        let secret_key_bytes = key_pair.secret_key_bytes();
+        let signature = key_pair.sign(message.clone()).unwrap();
+        {
+            let cloned = key_pair.clone();
+            assert_eq!(secret_key_bytes, cloned.secret_key_bytes());
+        }
+        assert_eq!(secret_key_bytes, key_pair.secret_key_bytes());
+        assert_eq!(signature, key_pair.sign(message).unwrap());
+    }
+
     // #[test]
     // #[cfg(feature = "bls12381")]
     // fn test_bls12381() {
@@ -10,6 +10,7 @@ use identifier::error::Error;
 use super::{create_seed, BaseKeyPair, KeyGenerator, KeyMaterial, KeyPair, Payload, DHKE, DSA};
 use libsecp256k1::{Message, PublicKey, SecretKey, Signature};
 use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
 
 /// Defines type
 pub type Secp256k1KeyPair = BaseKeyPair<PublicKey, SecretKey>;
@@ -21,7 +22,7 @@ pub const KEYPAIR_LENGTH: usize = 97;
 /// Keys generation
 impl KeyGenerator for Secp256k1KeyPair {
     fn from_seed(seed: &[u8]) -> Self {
-        let secret_seed = create_seed(seed).expect("invalid seed");
+        let secret_seed = Zeroizing::new(create_seed(seed).expect("invalid seed"));
         let sk = SecretKey::parse(&secret_seed).expect("Couldn't create key");
         let pk = PublicKey::from_secret_key(&sk);
         Secp256k1KeyPair {
@@ -62,10 +63,11 @@ impl KeyMaterial for Secp256k1KeyPair {
     }
 
     fn to_bytes(&self) -> Vec<u8> {
-        let mut bytes: [u8; KEYPAIR_LENGTH] = [0u8; KEYPAIR_LENGTH];
-        bytes[..SECRET_KEY_LENGTH].copy_from_slice(&self.secret_key_bytes());
+        // Built in place so no copy of the secret key is left behind
+        let mut bytes = vec![0u8; KEYPAIR_LENGTH];
+        bytes[..SECRET_KEY_LENGTH].copy_from_slice(&Zeroizing::new(self.secret_key_bytes()));
         bytes[SECRET_KEY_LENGTH..].copy_from_slice(&self.public_key_bytes());
-        bytes.to_vec()
+        bytes
     }
 }
 
@@ -121,7 +123,7 @@ impl Serialize for Secp256k1KeyPair {
     where
         S: Serializer,
     {
-        serializer.serialize_str(&self.to_str())
+        serializer.serialize_str(&Zeroizing::new(self.to_str()))
     }
 }
 
@@ -131,8 +133,10 @@ impl<'de> Deserialize<'de> for Secp256k1KeyPair {
     where
         D: Deserializer<'de>,
     {
-        let s = String::deserialize(deserializer)?;
-        let bytes = decode_config(&s, base64::URL_SAFE).map_err(serde::de::Error::custom)?;
+        let s = Zeroizing::new(String::deserialize(deserializer)?);
+        let bytes = Zeroizing::new(
+            decode_config(s.as_str(), base64::URL_SAFE).map_err(serde::de::Error::custom)?,
+        );
 
         Ok(Secp256k1KeyPair::from_secret_key(
             &bytes[..SECRET_KEY_LENGTH],
@@ -2,10 +2,14 @@ use ed25519_dalek::ed25519;
 use std::convert::Infallible;
 use thiserror::Error;
 
+use crate::{bd::level_db::error::WrapperLevelDBErrors, schema_handler::SchemaValidationError};
+
 #[derive(Error, Debug)]
 pub enum Error {
     #[error("Schema Creation Error")]
     SchemaCreationError,
+    #[error("Schema without $id")]
+    SchemaIdNotFound,
     #[error("Errors that can never happen")]
     InfalibleError {
         #[from]
@@ -125,6 +129,10 @@ pub enum CryptoErrorEvent {
     EventContentHashingConflict,
     #[error("Event request hash is not equal to signature hash")]
     EventRequestHashingConflict,
+    #[error("Signed hash is not equal to the content hash")]
+    SignatureHashingConflict,
+    #[error("Signature is not valid")]
+    SignatureInvalid,
 }
 
 #[derive(Error, Debug, PartialEq, Clone)]
@@ -159,6 +167,8 @@ pub enum SubjectError {
     DeleteSignaturesFailed,
     #[error("Schema Validation Failed")]
     SchemaValidationFailed,
+    #[error("Schema Validation Failed: {0:?}")]
+    SchemaValidationFailedDetailed(Vec<SchemaValidationError>),
     #[error("Schema does not compile")]
     SchemaDoesNotCompile,
     #[error("Error in criptography")]
@@ -167,18 +177,114 @@ pub enum SubjectError {
     InvalidPayload(String),
     #[error("Error parsing json string")]
     ErrorParsingJsonString,
+    #[error("JSON object with duplicated keys")]
+    DuplicateJsonKey,
     #[error("Error applying patch")]
     ErrorApplyingPatch,
+    #[error("JSON Patch test operation failed at {path}")]
+    PreconditionFailed { path: String },
+    #[error("JSON Patch does not change the properties of the subject")]
+    NoOpPatch,
     #[error("Duplicated schema or member")]
     DuplicatedSchemaOrMember,
+    #[error("Governance without members")]
+    EmptyGovernanceMembers,
+    #[error("Duplicated member id: {0}")]
+    DuplicatedMemberId(String),
+    #[error("Duplicated schema id: {0}")]
+    DuplicatedSchemaId(String),
     #[error("Policies Missing for Some Schema")]
     PoliciesMissing,
     #[error("Invalid Policies Id")]
     InvalidPoliciesId,
     #[error("Invalid Member in Policies")]
     InvalidMemberInPolicies,
+    #[error("Invalid key identifier: {0}")]
+    InvalidKeyIdentifier(String),
     #[error("JSON-PATCH on Create Event not allowed")]
     InvalidUseOfJSONPATCH,
     #[error("Approvers is not subset of validators")]
     ApproversAreNotValidators,
+    #[error("Schema {0} is not defined in the governance")]
+    UnknownSchemaId(String),
+    #[error("Governance version {got} does not match the current version {expected}")]
+    GovernanceVersionMismatch { expected: u64, got: u64 },
+    #[error("The signer of the request is not allowed to invoke the subject")]
+    InvokerNotAuthorized,
+    #[error("Request timestamp {timestamp} is older than allowed at {now}")]
+    RequestExpired { timestamp: i64, now: i64 },
+    #[error("Request timestamp {timestamp} is ahead of the clock at {now}")]
+    RequestFromFuture { timestamp: i64, now: i64 },
+    #[error("Approval signed by {0}, who is not an approver of the schema")]
+    UnknownApprover(String),
+    #[error("Request has {approvals} approvals and needs {quorum}")]
+    ApprovalQuorumNotReached { approvals: usize, quorum: usize },
+    #[error("The approvers rejected the request")]
+    RequestRejected,
+    #[error("The request is for another subject")]
+    RequestSubjectMismatch,
+    #[error("Subject is at SN {given} but the stored one is at {stored}")]
+    StaleSubject { given: u64, stored: u64 },
+    #[error("Subject could not be stored: {0}")]
+    SubjectNotStored(String),
+    #[error("Events up to SN {0} were pruned, so the subject can not be rebuilt")]
+    HistoryPruned(u64),
+    #[error("Database error: {0}")]
+    DatabaseError(String),
+}
+
+#[derive(Error, Debug, PartialEq, Clone)]
+pub enum ChainError {
+    #[error("Event with SN {0} is missing from the chain")]
+    MissingEvent(u64),
+    #[error("Previous hash of event with SN {0} does not match the prior event")]
+    PreviousHashMismatch(u64),
+    #[error("Event with SN {0} could not be hashed")]
+    EventHashingFailed(u64),
+}
+
+#[derive(Error, Debug, PartialEq, Clone)]
+pub enum AuditError {
+    #[error("Subject not found")]
+    SubjectNotFound,
+    #[error("Ledger state head SN {head_sn:?} does not match the stored events, whose highest SN is {stored_sn:?}")]
+    HeadSnMismatch {
+        head_sn: Option<u64>,
+        stored_sn: Option<u64>,
+    },
+    #[error("Subject data SN {subject_sn} does not match the ledger state head SN {head_sn:?}")]
+    SubjectSnMismatch {
+        subject_sn: u64,
+        head_sn: Option<u64>,
+    },
+    #[error("Database error: {0}")]
+    DatabaseError(String),
+}
+
+#[derive(Error, Debug)]
+pub enum ConflictError {
+    #[error("Subject not found")]
+    SubjectNotFound,
+    #[error("Expected the subject to be at SN {expected} but it is at {current:?}")]
+    SnMismatch { expected: u64, current: Option<u64> },
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] DbError),
+}
+
+#[derive(Error, Debug)]
+pub enum DbError {
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] WrapperLevelDBErrors),
+    #[error("Subject error: {0}")]
+    SubjectError(#[from] SubjectError),
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Malformed dump: {0}")]
+    MalformedDump(String),
+    #[error("Corrupt export: {0}")]
+    CorruptExport(String),
+    #[error("Stored controller id is not a valid key identifier: {0}")]
+    InvalidControllerId(String),
+    #[error("A page must hold at least one event")]
+    EmptyPageLimit,
 }
@@ -109,7 +109,9 @@ impl<'de> Deserialize<'de> for DigestIdentifier {
 #[cfg(test)]
 mod tests {
 
-    use super::DigestIdentifier;
+    use borsh::BorshSerialize;
+
+    use super::{Derivable, DigestIdentifier};
 
     #[test]
     fn test_digest_identifier_serde() {
@@ -118,4 +120,19 @@ mod tests {
         let new_id: DigestIdentifier = serde_json::from_str(&id_str).unwrap();
         assert_eq!(id, new_id);
     }
+
+    #[test]
+    fn test_digest_identifier_json_is_base_string() {
+        let id = DigestIdentifier::from_serializable_borsh("content").unwrap();
+        let json = serde_json::to_value(&id).unwrap();
+        assert_eq!(serde_json::Value::String(id.to_str()), json);
+        assert_eq!(
+            id,
+            serde_json::from_value::<DigestIdentifier>(json).unwrap()
+        );
+        // Borsh, used for signing, keeps the raw digest instead of the string
+        let borsh = id.try_to_vec().unwrap();
+        assert!(borsh.len() < id.to_str().len());
+        assert!(borsh.ends_with(&id.digest));
+    }
 }
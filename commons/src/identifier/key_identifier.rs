@@ -158,6 +158,16 @@ mod tests {
         assert!(des.is_ok());
     }
 
+    #[test]
+    fn test_json_is_base_string() {
+        let key_pair = Ed25519KeyPair::from_seed(&[]);
+        let id = KeyIdentifier::new(KeyDerivator::Ed25519, &key_pair.public_key_bytes());
+        let json = serde_json::to_value(&id).unwrap();
+        assert_eq!(serde_json::Value::String(id.to_str()), json);
+        assert_eq!(id, serde_json::from_value::<KeyIdentifier>(json).unwrap());
+        assert!(serde_json::from_value::<KeyIdentifier>(serde_json::json!([1, 2])).is_err());
+    }
+
     #[test]
     fn test_verify_ed25519() {
         let kp = Ed25519KeyPair::new();
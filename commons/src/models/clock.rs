@@ -0,0 +1,28 @@
+//! Sources of the current time, so that checks that depend on it can be tested
+use chrono::Utc;
+
+/// Source of the current time.
+pub trait Clock {
+    /// Seconds since the Unix epoch.
+    fn now_secs(&self) -> i64;
+}
+
+/// Clock of the system.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_secs(&self) -> i64 {
+        Utc::now().timestamp()
+    }
+}
+
+/// Clock that is always at the same second.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FixedClock(pub i64);
+
+impl Clock for FixedClock {
+    fn now_secs(&self) -> i64 {
+        self.0
+    }
+}
@@ -9,9 +9,7 @@ use utoipa::ToSchema;
 
 use crate::{
     errors::{CryptoErrorEvent, Error},
-    identifier::{
-        Derivable, DigestIdentifier, KeyIdentifier, SignatureIdentifier,
-    },
+    identifier::{DigestIdentifier, KeyIdentifier, SignatureIdentifier},
 };
 
 use super::{
@@ -41,29 +39,22 @@ impl Event {
     }
 
     pub fn get_event_content_hash(&self) -> Result<DigestIdentifier, Error> {
-        DigestIdentifier::from_serializable_borsh(self.event_content.clone())
+        self.event_content
+            .signing_hash()
+            .map_err(|_| Error::BorshSerializationFailed)
     }
 
     fn check_signatures(&self) -> Result<(), CryptoErrorEvent> {
         self.event_content.event_request.check_signatures()?;
-        match DigestIdentifier::from_serializable_borsh(self.event_content.clone()) {
-            Ok(hash) => {
-                if hash != self.signature.content.event_content_hash {
-                    return Err(CryptoErrorEvent::EventContentHashingConflict);
+        let hash = self.event_content.signing_hash()?;
+        self.signature
+            .verify_over(&hash)
+            .map_err(|error| match error {
+                CryptoErrorEvent::SignatureHashingConflict => {
+                    CryptoErrorEvent::EventContentHashingConflict
                 }
-                match &self
-                    .signature
-                    .content
-                    .signer
-                    .verify(&hash.derivative(), self.signature.signature.clone())
-                {
-                    Ok(_) => (),
-                    Err(_) => return Err(CryptoErrorEvent::RequestSignatureInvalid),
-                }
-            }
-            Err(_) => return Err(CryptoErrorEvent::EventContentHashingError),
-        }
-        Ok(())
+                _ => CryptoErrorEvent::RequestSignatureInvalid,
+            })
     }
 }
 
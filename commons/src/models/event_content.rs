@@ -4,9 +4,12 @@ use borsh::{BorshDeserialize, BorshSerialize};
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
-use crate::identifier::{DigestIdentifier, KeyIdentifier};
+use crate::{
+    errors::{CryptoErrorEvent, SubjectError},
+    identifier::{derive::digest::DigestDerivator, DigestIdentifier, KeyIdentifier},
+};
 
-use super::event_request::EventRequest;
+use super::{event_request::EventRequest, governance::Governance};
 /// Metadata of a TAPLE Event
 #[derive(
     Debug, Clone, Serialize, Deserialize, Eq, PartialEq, BorshSerialize, BorshDeserialize, ToSchema,
@@ -21,6 +24,27 @@ pub struct Metadata {
     pub owner: KeyIdentifier,
 }
 
+impl Metadata {
+    /// Checks that the metadata refers to a schema of `governance` and to its
+    /// current version, `governance_version`.
+    pub fn validate(
+        &self,
+        governance: &Governance,
+        governance_version: u64,
+    ) -> Result<(), SubjectError> {
+        if governance.content_schema(&self.schema_id).is_none() {
+            return Err(SubjectError::UnknownSchemaId(self.schema_id.clone()));
+        }
+        if self.governance_version != governance_version {
+            return Err(SubjectError::GovernanceVersionMismatch {
+                expected: governance_version,
+                got: self.governance_version,
+            });
+        }
+        Ok(())
+    }
+}
+
 /// Content of a TAPLE event
 #[derive(
     Debug, Clone, Serialize, Deserialize, Eq, PartialEq, BorshSerialize, BorshDeserialize, ToSchema,
@@ -57,4 +81,92 @@ impl EventContent {
             approved,
         }
     }
+
+    /// Borsh bytes of the content, the only bytes its signatures are made over.
+    pub fn signing_bytes(&self) -> Result<Vec<u8>, CryptoErrorEvent> {
+        self.try_to_vec()
+            .map_err(|_| CryptoErrorEvent::EventContentHashingError)
+    }
+
+    /// Hash of [EventContent::signing_bytes], the one signed by the subject.
+    pub fn signing_hash(&self) -> Result<DigestIdentifier, CryptoErrorEvent> {
+        let bytes = self.signing_bytes()?;
+        Ok(DigestIdentifier::new(
+            DigestDerivator::Blake3_256,
+            &DigestDerivator::Blake3_256.digest(&bytes),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use serde_json::json;
+
+    use crate::{
+        errors::SubjectError,
+        identifier::{DigestIdentifier, KeyIdentifier},
+        models::{event::Event, governance::Governance},
+    };
+
+    use super::Metadata;
+
+    fn metadata(schema_id: &str, governance_version: u64) -> Metadata {
+        Metadata {
+            namespace: "namespace".into(),
+            governance_id: DigestIdentifier::default(),
+            governance_version,
+            schema_id: schema_id.into(),
+            owner: KeyIdentifier::from_str("ED8MpwKh3OjPEw_hQdqJixrXlKzpVzdvHf2DqrPvdz7Y").unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_validate_metadata() {
+        let governance: Governance = serde_json::from_value(json!({
+            "members": [],
+            "schemas": [{"id": "Counter", "tags": {}, "content": {"type": "integer"}}],
+            "policies": []
+        }))
+        .unwrap();
+        assert_eq!(Ok(()), metadata("Counter", 2).validate(&governance, 2));
+        assert_eq!(
+            Err(SubjectError::UnknownSchemaId("Missing".into())),
+            metadata("Missing", 2).validate(&governance, 2)
+        );
+        assert_eq!(
+            Err(SubjectError::GovernanceVersionMismatch {
+                expected: 2,
+                got: 1
+            }),
+            metadata("Counter", 1).validate(&governance, 2)
+        );
+    }
+
+    #[test]
+    fn test_signing_bytes() {
+        let event = Event::default();
+        let content = event.event_content;
+        let bytes = content.signing_bytes().unwrap();
+        assert_eq!(477, bytes.len());
+        // Borsh starts with the subject id: derivator, digest length and digest
+        assert_eq!([0, 32, 0, 0, 0], bytes[..5]);
+        assert_eq!(content.subject_id.digest, bytes[5..37]);
+        // The hash the fixture was signed over
+        assert_eq!(
+            event.signature.content.event_content_hash,
+            content.signing_hash().unwrap()
+        );
+        assert_eq!(
+            DigestIdentifier::from_str("JvWXIptlBC_3Ybx0cTY3X-mL922Q0Ot8Jnl3inmHmsAA").unwrap(),
+            content.signing_hash().unwrap()
+        );
+        let mut next = content;
+        next.sn = 2;
+        assert_eq!(
+            DigestIdentifier::from_str("JmtOPzbJ8mvlNeefk6Q-KyYxWhnU2kXacuD8rbloUrwo").unwrap(),
+            next.signing_hash().unwrap()
+        );
+    }
 }
@@ -1,24 +1,30 @@
 //! Contains the data structures related to event requests.
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 use borsh::{BorshDeserialize, BorshSerialize};
-use json_patch::patch;
+use json_patch::{patch, Patch, PatchError, PatchOperation};
 
-use serde::{Deserialize, Serialize};
+use serde::{
+    de::{self, MapAccess, SeqAccess, Visitor},
+    Deserialize, Deserializer, Serialize,
+};
 use serde_json::Value;
 
 use crate::{
+    bd::TapleDB,
     crypto::{Ed25519KeyPair, KeyGenerator, KeyMaterial, KeyPair},
-    errors::{CryptoErrorEvent, SubjectError},
+    errors::{CryptoErrorEvent, Error, SubjectError},
     identifier::{Derivable, DigestIdentifier, KeyIdentifier},
-    schema_handler::Schema,
+    schema_handler::{digest_of, get_governance_schema, Schema},
 };
 use utoipa::ToSchema;
 
 use super::{
-    approval_signature::ApprovalResponse,
+    approval_signature::{Acceptance, ApprovalResponse, ApprovalResponseContent},
+    clock::{Clock, FixedClock},
     event::Event,
     event_content::{EventContent, Metadata},
+    governance::{Governance, InvokationDecision, Policy},
     signature::Signature,
     state::Subject,
 };
@@ -31,6 +37,27 @@ pub enum RequestPayload {
     JsonPatch(String),
 }
 
+impl RequestPayload {
+    /// Whole document the properties become with this payload. A `Json` payload is
+    /// the document itself, while a `JsonPatch` is applied to `current_properties`,
+    /// which it requires.
+    pub fn resolve(&self, current_properties: Option<&Value>) -> Result<Value, SubjectError> {
+        match self {
+            RequestPayload::Json(props) => parse_json_payload(props),
+            RequestPayload::JsonPatch(patch_string) => {
+                let patch_json = serde_json::from_value(parse_json_payload(patch_string)?)
+                    .map_err(|_| SubjectError::ErrorParsingJsonString)?;
+                let Some(current_properties) = current_properties else {
+                    return Err(SubjectError::InvalidUseOfJSONPATCH);
+                };
+                let mut properties = current_properties.clone();
+                apply_patch(&mut properties, &patch_json)?;
+                Ok(properties)
+            }
+        }
+    }
+}
+
 #[derive(
     Debug, Clone, Serialize, Deserialize, Eq, PartialEq, BorshSerialize, BorshDeserialize, ToSchema,
 )]
@@ -59,6 +86,32 @@ pub enum EventRequestType {
     State(StateRequest),
 }
 
+impl EventRequestType {
+    pub fn is_create(&self) -> bool {
+        matches!(self, EventRequestType::Create(_))
+    }
+
+    pub fn as_create(&self) -> Option<&CreateRequest> {
+        match self {
+            EventRequestType::Create(create_request) => Some(create_request),
+            EventRequestType::State(_) => None,
+        }
+    }
+
+    pub fn as_state(&self) -> Option<&StateRequest> {
+        match self {
+            EventRequestType::Create(_) => None,
+            EventRequestType::State(state_request) => Some(state_request),
+        }
+    }
+}
+
+/// Oldest a request can be, in seconds, to be accepted by `EventRequest::accept`.
+pub const MAX_REQUEST_AGE_SECS: i64 = 300;
+/// How far ahead of the clock of the node a request can be, in seconds, to be
+/// accepted by `EventRequest::accept`.
+pub const MAX_REQUEST_SKEW_SECS: i64 = 30;
+
 /// Request that originated the event. It contains basically 
 /// the proposed change and the votes obtained related to it.
 #[derive(
@@ -84,47 +137,134 @@ pub struct RequestData {
 }
 
 impl EventRequest {
+    /// Encodes the request with CBOR for its transport between nodes.
+    /// CBOR is only a wire format: hashes and signatures are always computed
+    /// over the Borsh serialization, so they do not depend on this encoding.
+    pub fn to_cbor(&self) -> Result<Vec<u8>, Error> {
+        Ok(serde_cbor::to_vec(self)?)
+    }
+
+    /// Decodes a request previously encoded with [EventRequest::to_cbor].
+    pub fn from_cbor(bytes: &[u8]) -> Result<Self, Error> {
+        Ok(serde_cbor::from_slice(bytes)?)
+    }
+
     pub fn check_against_schema(
         &self,
         schema: &Value,
         subject: &Subject,
     ) -> Result<(), SubjectError> {
+        let properties = self.get_future_properties(subject)?;
+        self.check_changes_subject(subject, &properties)?;
         let subject_schema =
             Schema::compile(&schema).map_err(|_| SubjectError::SchemaDoesNotCompile)?;
-        let payload = match &self.request {
-            EventRequestType::State(data) => &data.payload,
-            EventRequestType::Create(data) => &data.payload,
+        subject_schema
+            .validate_with_errors(&properties)
+            .map_err(SubjectError::SchemaValidationFailedDetailed)
+    }
+
+    /// Same checks as `check_against_schema`, but instead of stopping at the first
+    /// problem every one found is returned. Each schema violation is reported as
+    /// its own `SchemaValidationFailedDetailed`.
+    pub fn check_against_schema_all(
+        &self,
+        schema: &Value,
+        subject: &Subject,
+    ) -> Result<(), Vec<SubjectError>> {
+        let mut errors = Vec::new();
+        let subject_schema = match Schema::compile(schema) {
+            Ok(subject_schema) => Some(subject_schema),
+            Err(_) => {
+                errors.push(SubjectError::SchemaDoesNotCompile);
+                None
+            }
         };
-        match payload {
-            RequestPayload::Json(props) => {
-                // Validate with schema
-                let Ok(properties) = serde_json::from_str(&props) else {
-                    return Err(SubjectError::ErrorParsingJsonString);
-                };
-                if !subject_schema.validate(&properties) {
-                    return Err(SubjectError::SchemaValidationFailed);
-                }
-                Ok(())
+        let properties = match self.get_future_properties(subject) {
+            Ok(properties) => Some(properties),
+            Err(error) => {
+                errors.push(error);
+                None
             }
-            RequestPayload::JsonPatch(patch_string) => {
-                let Ok(patch_json) = serde_json::from_str(&patch_string) else {
-                    return Err(SubjectError::ErrorParsingJsonString);
-                };
-                let Some(subject_data) = &subject.subject_data else {
-                    return Err(SubjectError::InvalidUseOfJSONPATCH);
-                };
-                let Ok(mut properties) = serde_json::from_str(&subject_data.properties) else {
+        };
+        if let Some(properties) = properties.as_ref() {
+            if let Err(error) = self.check_changes_subject(subject, properties) {
+                errors.push(error);
+            }
+        }
+        if let (Some(subject_schema), Some(properties)) = (subject_schema, properties) {
+            if let Err(violations) = subject_schema.validate_with_errors(&properties) {
+                errors.extend(violations.into_iter().map(|violation| {
+                    SubjectError::SchemaValidationFailedDetailed(vec![violation])
+                }));
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// A patch that leaves the properties as they are would only create an empty event.
+    fn check_changes_subject(
+        &self,
+        subject: &Subject,
+        properties: &Value,
+    ) -> Result<(), SubjectError> {
+        if let (RequestPayload::JsonPatch(_), Some(subject_data)) =
+            (self.payload(), &subject.subject_data)
+        {
+            let Ok(current_properties) = serde_json::from_str(&subject_data.properties) else {
+                return Err(SubjectError::ErrorParsingJsonString);
+            };
+            let unchanged = match (digest_of(&current_properties), digest_of(properties)) {
+                (Ok(current_digest), Ok(future_digest)) => current_digest == future_digest,
+                _ => return Err(SubjectError::EventContentSerializationFailed),
+            };
+            if unchanged {
+                return Err(SubjectError::NoOpPatch);
+            }
+        }
+        Ok(())
+    }
+
+    fn payload(&self) -> &RequestPayload {
+        match &self.request {
+            EventRequestType::State(data) => &data.payload,
+            EventRequestType::Create(data) => &data.payload,
+        }
+    }
+
+    /// Checks whether the request would be accepted for the subject without
+    /// modifying it, returning the properties the subject would end up having.
+    pub fn simulate(
+        &self,
+        subject: &Subject,
+        subject_schema: &Value,
+    ) -> Result<Value, SubjectError> {
+        self.check_signatures().map_err(SubjectError::CryptoError)?;
+        let subject_schema =
+            Schema::compile(subject_schema).map_err(|_| SubjectError::SchemaDoesNotCompile)?;
+        let properties = self.get_future_properties(subject)?;
+        subject_schema
+            .validate_with_errors(&properties)
+            .map_err(SubjectError::SchemaValidationFailedDetailed)?;
+        Ok(properties)
+    }
+
+    fn get_future_properties(&self, subject: &Subject) -> Result<Value, SubjectError> {
+        let payload = self.payload();
+        // Only patches need the current properties
+        let current_properties = match (payload, &subject.subject_data) {
+            (RequestPayload::JsonPatch(_), Some(subject_data)) => {
+                let Ok(properties) = serde_json::from_str(&subject_data.properties) else {
                     return Err(SubjectError::ErrorParsingJsonString);
                 };
-                let Ok(()) = patch(&mut properties, &patch_json) else {
-                    return Err(SubjectError::ErrorApplyingPatch);
-                };
-                if !subject_schema.validate(&properties) {
-                    return Err(SubjectError::SchemaValidationFailed);
-                }
-                Ok(())
+                Some(properties)
             }
-        }
+            _ => None,
+        };
+        payload.resolve(current_properties.as_ref())
     }
 
     pub fn check_signatures(&self) -> Result<(), CryptoErrorEvent> {
@@ -132,19 +272,14 @@ impl EventRequest {
         let Ok(hash) = DigestIdentifier::from_serializable_borsh((self.request.clone(), self.timestamp)) else {
             return Err(CryptoErrorEvent::EventRequestHashingError);
         };
-        // println!("Primero {:?}, segundo {:?}", hash, self.signature.content.event_content_hash);
-        if hash != self.signature.content.event_content_hash {
-            return Err(CryptoErrorEvent::EventRequestHashingConflict);
-        }
-        match self
-            .signature
-            .content
-            .signer
-            .verify(&hash.derivative(), self.signature.signature.clone())
-        {
-            Ok(_) => (),
-            Err(_) => return Err(CryptoErrorEvent::RequestSignatureInvalid),
-        };
+        self.signature
+            .verify_over(&hash)
+            .map_err(|error| match error {
+                CryptoErrorEvent::SignatureHashingConflict => {
+                    CryptoErrorEvent::EventRequestHashingConflict
+                }
+                _ => CryptoErrorEvent::RequestSignatureInvalid,
+            })?;
         for approval in self.approvals.iter() {
             if hash != approval.content.event_request_hash {
                 return Err(CryptoErrorEvent::EventRequestHashingConflict);
@@ -170,6 +305,224 @@ impl EventRequest {
         Ok(())
     }
 
+    /// Runs `check_signatures` on every request, spreading them over at most as
+    /// many threads as the available parallelism. The results are in the order
+    /// of the requests.
+    pub fn check_signatures_batch(requests: &[EventRequest]) -> Vec<Result<(), CryptoErrorEvent>> {
+        let threads = std::thread::available_parallelism().map_or(1, usize::from);
+        let chunk_size = requests.len().div_ceil(threads).max(1);
+        std::thread::scope(|scope| {
+            let workers: Vec<_> = requests
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    scope.spawn(move || {
+                        chunk
+                            .iter()
+                            .map(EventRequest::check_signatures)
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().expect("Signature check panicked"))
+                .collect()
+        })
+    }
+
+    /// Checks the timestamp of the request, in milliseconds, against `clock`. It is
+    /// rejected if it is more than `max_age_secs` old or more than `max_skew_secs`
+    /// ahead. Both limits are inclusive and compared in whole seconds.
+    pub fn check_timestamp<C: Clock>(
+        &self,
+        clock: &C,
+        max_age_secs: i64,
+        max_skew_secs: i64,
+    ) -> Result<(), SubjectError> {
+        let now = clock.now_secs();
+        let timestamp = self.timestamp.div_euclid(1000);
+        if now - timestamp > max_age_secs {
+            return Err(SubjectError::RequestExpired { timestamp, now });
+        }
+        if timestamp - now > max_skew_secs {
+            return Err(SubjectError::RequestFromFuture { timestamp, now });
+        }
+        Ok(())
+    }
+
+    /// Counts the approvals and rejections of the request, as `(approvals, rejections)`.
+    /// Each signer is counted once, with its latest response. If a signer sent both
+    /// with the same timestamp, the rejection is counted.
+    pub fn tally_approvals(&self) -> (usize, usize) {
+        let mut latest: HashMap<&KeyIdentifier, &ApprovalResponseContent> = HashMap::new();
+        for approval in self.approvals.iter() {
+            let content = &approval.content;
+            latest
+                .entry(&content.signer)
+                .and_modify(|current| {
+                    if (content.timestamp, &content.approval_type)
+                        > (current.timestamp, &current.approval_type)
+                    {
+                        *current = content;
+                    }
+                })
+                .or_insert(content);
+        }
+        let approvals = latest
+            .values()
+            .filter(|content| content.approval_type == Acceptance::Accept)
+            .count();
+        (approvals, latest.len() - approvals)
+    }
+
+    /// Checks that the invokation policy of the schema of `subject` in `governance`
+    /// allows the signer of the request to invoke it.
+    pub fn check_invoker_authorized(
+        &self,
+        subject: &Subject,
+        governance: &Governance,
+    ) -> Result<(), SubjectError> {
+        self.authorized_invokation(subject, governance).map(|_| ())
+    }
+
+    /// Policy of the schema of `subject` and the decision of its invokation rules
+    /// for the signer, which must be allowed.
+    fn authorized_invokation<'a>(
+        &self,
+        subject: &Subject,
+        governance: &'a Governance,
+    ) -> Result<(&'a Policy, InvokationDecision), SubjectError> {
+        let Some(subject_data) = subject.subject_data.as_ref() else {
+            return Err(SubjectError::SubjectHasNoData);
+        };
+        let Some(policy) = governance
+            .policies
+            .iter()
+            .find(|policy| policy.id == subject_data.schema_id)
+        else {
+            return Err(SubjectError::PoliciesMissing);
+        };
+        let decision = policy.invokation.evaluate(
+            &self.signature.content.signer,
+            &subject_data.owner,
+            &governance.members,
+        )?;
+        if !decision.allowed {
+            return Err(SubjectError::InvokerNotAuthorized);
+        }
+        Ok((policy, decision))
+    }
+
+    /// Checks whether a state request for `subject` can be accepted, `now` being
+    /// the current time in seconds. The checks run from the cheapest to the most
+    /// expensive and the first failure is returned:
+    /// 1. The timestamp, within `MAX_REQUEST_AGE_SECS` and `MAX_REQUEST_SKEW_SECS`.
+    /// 2. The signatures of the request and of its approvals.
+    /// 3. The invokation policy of the schema of the subject for the signer.
+    /// 4. The approval quorum, if the invokation needs approval.
+    /// 5. The properties that result from the request, against the schema.
+    /// 6. The subject, which must be the stored one, at its head event and not
+    ///    negotiating another one.
+    pub fn accept<D: TapleDB>(
+        &self,
+        subject: &Subject,
+        governance: &Governance,
+        db: &D,
+        now: i64,
+    ) -> Result<(), SubjectError> {
+        self.check_timestamp(
+            &FixedClock(now),
+            MAX_REQUEST_AGE_SECS,
+            MAX_REQUEST_SKEW_SECS,
+        )?;
+        self.check_signatures().map_err(SubjectError::CryptoError)?;
+        let (policy, decision) = self.authorized_invokation(subject, governance)?;
+        if decision.approval_required {
+            self.check_approval_quorum(policy)?;
+        }
+        let Some(subject_data) = subject.subject_data.as_ref() else {
+            return Err(SubjectError::SubjectHasNoData);
+        };
+        let schema = if subject_data.schema_id == "governance" {
+            get_governance_schema()
+        } else {
+            let Some(schema) = governance.content_schema(&subject_data.schema_id) else {
+                return Err(SubjectError::UnknownSchemaId(
+                    subject_data.schema_id.clone(),
+                ));
+            };
+            schema.clone()
+        };
+        self.check_against_schema(&schema, subject)?;
+        self.check_chain_context(subject, db)
+    }
+
+    /// Checks that the approvals of the request reach the quorum of the approval
+    /// policy and were all signed by its approvers.
+    fn check_approval_quorum(&self, policy: &Policy) -> Result<(), SubjectError> {
+        let approvers = &policy.approval.approvers;
+        if let Some(approval) = self
+            .approvals
+            .iter()
+            .find(|approval| !approvers.contains(&approval.content.signer.to_str()))
+        {
+            return Err(SubjectError::UnknownApprover(
+                approval.content.signer.to_str(),
+            ));
+        }
+        let quorum = (approvers.len() as f64 * policy.approval.quorum).ceil() as usize;
+        let (approvals, rejections) = self.tally_approvals();
+        if approvals >= quorum {
+            Ok(())
+        } else if rejections >= approvers.len() + 1 - quorum {
+            Err(SubjectError::RequestRejected)
+        } else {
+            Err(SubjectError::ApprovalQuorumNotReached { approvals, quorum })
+        }
+    }
+
+    /// Checks that the request is for `subject`, which is the stored one, and that
+    /// the next event of the subject can be created on top of its head.
+    fn check_chain_context<D: TapleDB>(
+        &self,
+        subject: &Subject,
+        db: &D,
+    ) -> Result<(), SubjectError> {
+        let Some(state_request) = self.request.as_state() else {
+            return Err(SubjectError::NotStateEvent);
+        };
+        let Some(subject_data) = subject.subject_data.as_ref() else {
+            return Err(SubjectError::SubjectHasNoData);
+        };
+        if state_request.subject_id != subject_data.subject_id {
+            return Err(SubjectError::RequestSubjectMismatch);
+        }
+        let subject_id = &subject_data.subject_id;
+        let Some(stored) = db.get_subject(subject_id) else {
+            return Err(SubjectError::SubjectNotFound);
+        };
+        let Some(stored_data) = stored.subject_data.as_ref() else {
+            return Err(SubjectError::SubjectHasNoData);
+        };
+        if stored_data.sn != subject_data.sn {
+            return Err(SubjectError::StaleSubject {
+                given: subject_data.sn,
+                stored: stored_data.sn,
+            });
+        }
+        let has_event = |sn| {
+            db.has_event(subject_id, sn)
+                .map_err(|error| SubjectError::DatabaseError(error.to_string()))
+        };
+        if !has_event(subject_data.sn)? {
+            return Err(SubjectError::EventAlreadyAppliedNotFound);
+        }
+        if stored.ledger_state.negociating_next || has_event(subject_data.sn + 1)? {
+            return Err(SubjectError::EventAlreadyProcessing);
+        }
+        Ok(())
+    }
+
     pub fn create_subject_from_request(
         self,
         governance_version: u64,
@@ -213,43 +566,923 @@ impl EventRequest {
         }
     }
 
+    /// Builds and signs the next event of `subject`, whose SN and previous hash are
+    /// given by `DB::next_event_context`.
     pub fn get_event_from_state_request(
         self,
         subject: &Subject,
+        sn: u64,
         prev_event_hash: DigestIdentifier,
+        governance: &Governance,
         governance_version: u64,
         subject_schema: &Value,
         approved: bool,
     ) -> Result<Event, SubjectError> {
-        match self.request.clone() {
-            EventRequestType::Create(_) => {
-                panic!("Expected State Event")
-            }
-            EventRequestType::State(state_req) => {
-                // TODO: Check that the request invoker is you or it can be done by the governance
-                if subject.keys.is_none() {
-                    return Err(SubjectError::NotOwnerOfSubject);
+        let subject_id = match self.request.as_state() {
+            Some(state_req) => state_req.subject_id.clone(),
+            None => return Err(SubjectError::NotStateEvent),
+        };
+        if subject.keys.is_none() {
+            return Err(SubjectError::NotOwnerOfSubject);
+        }
+        // The event is signed with the keys of the subject, so only the invokers
+        // allowed by the governance may have it built
+        self.check_invoker_authorized(subject, governance)?;
+        let subject_data = subject.subject_data.as_ref().expect("Hay data");
+        let mut event_content = EventContent {
+            subject_id,
+            event_request: self,
+            sn,
+            previous_hash: prev_event_hash,
+            state_hash: DigestIdentifier::default(),
+            metadata: Metadata {
+                namespace: subject_data.namespace.clone(),
+                governance_id: subject_data.governance_id.clone(),
+                governance_version,
+                schema_id: subject_data.schema_id.clone(),
+                owner: subject_data.owner.clone(),
+            },
+            approved,
+        };
+        event_content.state_hash =
+            subject.get_future_subject_content_hash(event_content.clone(), subject_schema)?;
+        Ok(subject.get_signature_from_subject(event_content)?)
+    }
+}
+
+/// Applies a JSON Patch to `properties`, which are left unchanged if it fails. A
+/// failed `test` operation is reported as `PreconditionFailed` with its path, so the
+/// invoker knows that the subject changed and the request can be retried.
+pub(crate) fn apply_patch(properties: &mut Value, patch_json: &Patch) -> Result<(), SubjectError> {
+    match patch(properties, patch_json) {
+        Ok(()) => Ok(()),
+        Err(PatchError::TestFailed) => {
+            // The patch is applied again one operation at a time to find the test
+            let mut document = properties.clone();
+            for operation in patch_json.0.iter() {
+                if patch(&mut document, &Patch(vec![operation.clone()])).is_err() {
+                    if let PatchOperation::Test(test) = operation {
+                        return Err(SubjectError::PreconditionFailed {
+                            path: test.path.clone(),
+                        });
+                    }
+                    break;
                 }
-                let subject_data = subject.subject_data.as_ref().expect("Hay data");
-                let mut event_content = EventContent {
-                    subject_id: state_req.subject_id,
-                    event_request: self,
-                    sn: subject_data.sn + 1,
-                    previous_hash: prev_event_hash,
-                    state_hash: DigestIdentifier::default(),
-                    metadata: Metadata {
-                        namespace: subject_data.namespace.clone(),
-                        governance_id: subject_data.governance_id.clone(),
-                        governance_version,
-                        schema_id: subject_data.schema_id.clone(),
-                        owner: subject_data.owner.clone(),
-                    },
-                    approved,
-                };
-                event_content.state_hash = subject
-                    .get_future_subject_content_hash(event_content.clone(), subject_schema)?;
-                Ok(subject.get_signature_from_subject(event_content)?)
             }
+            Err(SubjectError::ErrorApplyingPatch)
+        }
+        Err(_) => Err(SubjectError::ErrorApplyingPatch),
+    }
+}
+
+/// Parses a JSON payload rejecting objects with duplicated keys. `serde_json` keeps
+/// the last value of a duplicated key, so two parties could read the same payload
+/// differently.
+fn parse_json_payload(json: &str) -> Result<Value, SubjectError> {
+    let Ok(value) = serde_json::from_str(json) else {
+        return Err(SubjectError::ErrorParsingJsonString);
+    };
+    let Ok(UniqueKeys) = serde_json::from_str(json) else {
+        return Err(SubjectError::DuplicateJsonKey);
+    };
+    Ok(value)
+}
+
+/// Walks a JSON document failing if any of its objects repeats a key
+struct UniqueKeys;
+
+impl<'de> Deserialize<'de> for UniqueKeys {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct UniqueKeysVisitor;
+        impl<'de> Visitor<'de> for UniqueKeysVisitor {
+            type Value = UniqueKeys;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("a JSON value")
+            }
+
+            fn visit_bool<E>(self, _: bool) -> Result<UniqueKeys, E> {
+                Ok(UniqueKeys)
+            }
+
+            fn visit_i64<E>(self, _: i64) -> Result<UniqueKeys, E> {
+                Ok(UniqueKeys)
+            }
+
+            fn visit_u64<E>(self, _: u64) -> Result<UniqueKeys, E> {
+                Ok(UniqueKeys)
+            }
+
+            fn visit_f64<E>(self, _: f64) -> Result<UniqueKeys, E> {
+                Ok(UniqueKeys)
+            }
+
+            fn visit_str<E>(self, _: &str) -> Result<UniqueKeys, E> {
+                Ok(UniqueKeys)
+            }
+
+            fn visit_unit<E>(self) -> Result<UniqueKeys, E> {
+                Ok(UniqueKeys)
+            }
+
+            fn visit_seq<V>(self, mut seq: V) -> Result<UniqueKeys, V::Error>
+            where
+                V: SeqAccess<'de>,
+            {
+                while let Some(UniqueKeys) = seq.next_element()? {}
+                Ok(UniqueKeys)
+            }
+
+            fn visit_map<V>(self, mut map: V) -> Result<UniqueKeys, V::Error>
+            where
+                V: MapAccess<'de>,
+            {
+                let mut keys = HashSet::new();
+                while let Some(key) = map.next_key::<String>()? {
+                    if !keys.insert(key.clone()) {
+                        return Err(de::Error::custom(format!("duplicate key `{}`", key)));
+                    }
+                    map.next_value::<UniqueKeys>()?;
+                }
+                Ok(UniqueKeys)
+            }
+        }
+
+        deserializer.deserialize_any(UniqueKeysVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use std::collections::HashSet;
+
+    use tempdir::TempDir;
+
+    use crate::{
+        bd::{
+            db::{open_db, DB},
+            TapleDB,
+        },
+        crypto::{Ed25519KeyPair, KeyGenerator, KeyMaterial, KeyPair, Payload, DSA},
+        errors::{CryptoErrorEvent, SubjectError},
+        identifier::{Derivable, DigestIdentifier, KeyIdentifier, SignatureIdentifier},
+        models::{
+            approval_signature::{Acceptance, ApprovalResponse, ApprovalResponseContent},
+            clock::{Clock, FixedClock, SystemClock},
+            event::Event,
+            event_request::{
+                CreateRequest, EventRequest, EventRequestType, RequestPayload, StateRequest,
+                MAX_REQUEST_AGE_SECS,
+            },
+            governance::Governance,
+            signature::{Signature, SignatureContent},
+            state::{LedgerState, Subject, SubjectData},
+        },
+    };
+
+    fn get_schema() -> serde_json::Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "tags": {
+                    "type": "array",
+                    "minItems": 2,
+                    "items": {"type": "string"}
+                }
+            },
+            "required": ["tags"]
+        })
+    }
+
+    fn get_request(payload: RequestPayload) -> EventRequest {
+        let mut request = Event::default().event_content.event_request;
+        request.request = EventRequestType::State(StateRequest {
+            subject_id: DigestIdentifier::default(),
+            payload,
+        });
+        request
+    }
+
+    fn sign_request(request: &mut EventRequest) {
+        sign_request_with(request, &KeyPair::Ed25519(Ed25519KeyPair::from_seed(&[])));
+    }
+
+    fn sign_request_with(request: &mut EventRequest, keys: &KeyPair) {
+        let hash =
+            DigestIdentifier::from_serializable_borsh((request.request.clone(), request.timestamp))
+                .unwrap();
+        let signer = KeyIdentifier::new(keys.get_key_derivator(), &keys.public_key_bytes());
+        let signature = keys.sign(Payload::Buffer(hash.derivative())).unwrap();
+        request.signature = Signature {
+            content: SignatureContent {
+                signer: signer.clone(),
+                event_content_hash: hash,
+                timestamp: request.timestamp,
+            },
+            signature: SignatureIdentifier::new(signer.to_signature_derivator(), &signature),
+        };
+    }
+
+    fn get_subject(properties: &str) -> Subject {
+        let event_content = Event::default().event_content;
+        let mut subject = Subject::new_empty(LedgerState::default());
+        subject.subject_data = Some(SubjectData {
+            subject_id: event_content.subject_id,
+            governance_id: event_content.metadata.governance_id,
+            sn: 0,
+            public_key: event_content.metadata.owner.clone(),
+            namespace: event_content.metadata.namespace,
+            schema_id: event_content.metadata.schema_id,
+            owner: event_content.metadata.owner,
+            properties: properties.to_owned(),
+        });
+        subject
+    }
+
+    /// Governance whose members are `signer` and `other`, where the schema
+    /// `schema_id` can be invoked by the owner and by `set_invokers`.
+    fn invokation_governance(
+        schema_id: &str,
+        signer: &KeyIdentifier,
+        other: &KeyIdentifier,
+        set_invokers: Vec<String>,
+    ) -> Governance {
+        let rule = json!({"allowance": false, "approvalRequired": false});
+        serde_json::from_value(json!({
+            "members": [
+                {"id": "Signer", "tags": {}, "key": signer.to_str()},
+                {"id": "Other", "tags": {}, "key": other.to_str()}
+            ],
+            "schemas": [],
+            "policies": [{
+                "id": schema_id,
+                "validation": {"quorum": 1.0, "validators": []},
+                "approval": {"quorum": 1.0, "approvers": []},
+                "invokation": {
+                    "owner": {"allowance": true, "approvalRequired": false},
+                    "set": {"allowance": true, "approvalRequired": true, "invokers": set_invokers},
+                    "all": rule,
+                    "external": rule
+                }
+            }]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_resolve_payload() {
+        let current = json!({"tags": ["a", "b"]});
+        let json = RequestPayload::Json("{\"tags\":[\"c\"]}".into());
+        assert_eq!(Ok(json!({"tags": ["c"]})), json.resolve(None));
+        assert_eq!(Ok(json!({"tags": ["c"]})), json.resolve(Some(&current)));
+        assert_eq!(
+            Err(SubjectError::ErrorParsingJsonString),
+            RequestPayload::Json("{".into()).resolve(None)
+        );
+
+        let patch = RequestPayload::JsonPatch(
+            "[{\"op\":\"add\",\"path\":\"/tags/-\",\"value\":\"c\"}]".into(),
+        );
+        assert_eq!(
+            Ok(json!({"tags": ["a", "b", "c"]})),
+            patch.resolve(Some(&current))
+        );
+        // The current properties are left as they were
+        assert_eq!(json!({"tags": ["a", "b"]}), current);
+        assert_eq!(
+            Err(SubjectError::InvalidUseOfJSONPATCH),
+            patch.resolve(None)
+        );
+        assert_eq!(
+            Err(SubjectError::ErrorApplyingPatch),
+            RequestPayload::JsonPatch("[{\"op\":\"remove\",\"path\":\"/missing\"}]".into())
+                .resolve(Some(&current))
+        );
+    }
+
+    #[test]
+    fn test_check_against_schema_json_reports_path() {
+        let request = get_request(RequestPayload::Json("{\"tags\":[\"a\"]}".into()));
+        let subject = get_subject("{\"tags\":[\"a\",\"b\"]}");
+        let Err(SubjectError::SchemaValidationFailedDetailed(errors)) =
+            request.check_against_schema(&get_schema(), &subject) else {
+            panic!("Expected detailed schema error");
+        };
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].instance_path, "/tags");
+        assert_eq!(errors[0].schema_path, "/properties/tags/minItems");
+    }
+
+    #[test]
+    fn test_check_against_schema_json_patch_reports_path() {
+        let request = get_request(RequestPayload::JsonPatch(
+            "[{\"op\":\"remove\",\"path\":\"/tags/1\"}]".into(),
+        ));
+        let subject = get_subject("{\"tags\":[\"a\",\"b\"]}");
+        let Err(SubjectError::SchemaValidationFailedDetailed(errors)) =
+            request.check_against_schema(&get_schema(), &subject) else {
+            panic!("Expected detailed schema error");
+        };
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].instance_path, "/tags");
+
+        let request = get_request(RequestPayload::JsonPatch(
+            "[{\"op\":\"add\",\"path\":\"/tags/-\",\"value\":\"c\"}]".into(),
+        ));
+        assert!(request
+            .check_against_schema(&get_schema(), &subject)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_check_against_schema_all() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "tags": {"type": "array", "minItems": 2},
+                "version": {"type": "integer"}
+            },
+            "required": ["tags", "version"]
+        });
+        let subject = get_subject("{\"tags\":[\"a\",\"b\"],\"version\":1}");
+        let request = get_request(RequestPayload::Json(
+            "{\"tags\":[\"a\"],\"version\":\"two\"}".into(),
+        ));
+        let errors = request
+            .check_against_schema_all(&schema, &subject)
+            .unwrap_err();
+        let mut paths: Vec<&str> = errors
+            .iter()
+            .map(|error| match error {
+                SubjectError::SchemaValidationFailedDetailed(violations) => {
+                    assert_eq!(1, violations.len());
+                    violations[0].instance_path.as_str()
+                }
+                error => panic!("Unexpected error {:?}", error),
+            })
+            .collect();
+        paths.sort_unstable();
+        assert_eq!(vec!["/tags", "/version"], paths);
+        // The fail fast mode reports both violations in a single error
+        assert!(matches!(
+            request.check_against_schema(&schema, &subject),
+            Err(SubjectError::SchemaValidationFailedDetailed(violations)) if violations.len() == 2
+        ));
+
+        // Errors that prevent the validation are reported together
+        let request = get_request(RequestPayload::Json("{".into()));
+        assert_eq!(
+            Err(vec![
+                SubjectError::SchemaDoesNotCompile,
+                SubjectError::ErrorParsingJsonString
+            ]),
+            request.check_against_schema_all(&json!({"type": 1}), &subject)
+        );
+        let request = get_request(RequestPayload::Json(
+            "{\"tags\":[\"a\",\"c\"],\"version\":2}".into(),
+        ));
+        assert_eq!(Ok(()), request.check_against_schema_all(&schema, &subject));
+    }
+
+    #[test]
+    fn test_check_against_schema_no_op_patch() {
+        let subject = get_subject("{\"tags\":[\"a\",\"b\"],\"version\":1}");
+        let request = get_request(RequestPayload::JsonPatch(
+            "[{\"op\":\"replace\",\"path\":\"/version\",\"value\":2}]".into(),
+        ));
+        assert_eq!(
+            Ok(()),
+            request.check_against_schema(&get_schema(), &subject)
+        );
+
+        // Replacing with the same value, or only testing, leaves the subject as it is
+        for patch in [
+            "[{\"op\":\"replace\",\"path\":\"/version\",\"value\":1}]",
+            "[{\"op\":\"test\",\"path\":\"/version\",\"value\":1}]",
+            "[]",
+        ] {
+            let request = get_request(RequestPayload::JsonPatch(patch.into()));
+            assert_eq!(
+                Err(SubjectError::NoOpPatch),
+                request.check_against_schema(&get_schema(), &subject)
+            );
+        }
+    }
+
+    #[test]
+    fn test_check_against_schema_json_patch_precondition() {
+        let subject = get_subject("{\"tags\":[\"a\",\"b\"],\"version\":1}");
+        let request = get_request(RequestPayload::JsonPatch(
+            "[{\"op\":\"test\",\"path\":\"/version\",\"value\":1},\
+             {\"op\":\"replace\",\"path\":\"/version\",\"value\":2}]"
+                .into(),
+        ));
+        assert_eq!(
+            Ok(()),
+            request.check_against_schema(&get_schema(), &subject)
+        );
+
+        // The subject has been modified since the invoker read it
+        let subject = get_subject("{\"tags\":[\"a\",\"b\"],\"version\":3}");
+        assert_eq!(
+            Err(SubjectError::PreconditionFailed {
+                path: "/version".into()
+            }),
+            request.check_against_schema(&get_schema(), &subject)
+        );
+
+        // Other failures are not preconditions
+        let request = get_request(RequestPayload::JsonPatch(
+            "[{\"op\":\"remove\",\"path\":\"/missing\"}]".into(),
+        ));
+        assert_eq!(
+            Err(SubjectError::ErrorApplyingPatch),
+            request.check_against_schema(&get_schema(), &subject)
+        );
+    }
+
+    #[test]
+    fn test_check_against_schema_duplicate_keys() {
+        let subject = get_subject("{\"tags\":[\"a\",\"b\"]}");
+        let request = get_request(RequestPayload::Json(
+            "{\"tags\":[\"a\"],\"tags\":[\"a\",\"b\"]}".into(),
+        ));
+        assert_eq!(
+            request.check_against_schema(&get_schema(), &subject),
+            Err(SubjectError::DuplicateJsonKey)
+        );
+        // Nested objects are also checked
+        let request = get_request(RequestPayload::Json(
+            "{\"tags\":[\"a\",\"b\"],\"extra\":[{\"id\":1,\"id\":2}]}".into(),
+        ));
+        assert_eq!(
+            request.check_against_schema(&get_schema(), &subject),
+            Err(SubjectError::DuplicateJsonKey)
+        );
+        let request = get_request(RequestPayload::JsonPatch(
+            "[{\"op\":\"add\",\"path\":\"/tags/-\",\"value\":\"c\",\"value\":\"d\"}]"
+                .into(),
+        ));
+        assert_eq!(
+            request.check_against_schema(&get_schema(), &subject),
+            Err(SubjectError::DuplicateJsonKey)
+        );
+        // The same key in different objects is not a duplicate
+        let request = get_request(RequestPayload::Json(
+            "{\"tags\":[\"a\",\"b\"],\"extra\":[{\"id\":1},{\"id\":2}]}".into(),
+        ));
+        assert!(request
+            .check_against_schema(&get_schema(), &subject)
+            .is_ok());
+    }
+
+    fn add_approval(request: &mut EventRequest) {
+        let approval = ApprovalResponse {
+            content: ApprovalResponseContent {
+                signer: request.signature.content.signer.clone(),
+                event_request_hash: request.signature.content.event_content_hash.clone(),
+                approval_type: Acceptance::Accept,
+                expected_sn: 1,
+                timestamp: request.timestamp,
+            },
+            signature: request.signature.signature.clone(),
+        };
+        request.approvals = HashSet::from([approval]);
+    }
+
+    fn add_signed_approval(request: &mut EventRequest, seed: u8, approval_type: Acceptance) {
+        let keys = KeyPair::Ed25519(Ed25519KeyPair::from_seed(&[seed]));
+        let signer = KeyIdentifier::new(keys.get_key_derivator(), &keys.public_key_bytes());
+        let event_request_hash = request.signature.content.event_content_hash.clone();
+        let hash =
+            DigestIdentifier::from_serializable_borsh((&event_request_hash, &approval_type, 1u64))
+                .unwrap();
+        let signature = keys.sign(Payload::Buffer(hash.derivative())).unwrap();
+        request.approvals.insert(ApprovalResponse {
+            content: ApprovalResponseContent {
+                signer: signer.clone(),
+                event_request_hash,
+                approval_type,
+                expected_sn: 1,
+                timestamp: request.timestamp,
+            },
+            signature: SignatureIdentifier::new(signer.to_signature_derivator(), &signature),
+        });
+    }
+
+    #[test]
+    fn test_accept() {
+        let key = |seed: u8| {
+            let keys = KeyPair::Ed25519(Ed25519KeyPair::from_seed(&[seed]));
+            KeyIdentifier::new(keys.get_key_derivator(), &keys.public_key_bytes())
+        };
+        // The requests are signed with seed 0 and approved with seed 1
+        let (owner, approver) = (key(0), key(1).to_str());
+        let mut subject = get_subject("{\"tags\":[\"a\",\"b\"]}");
+        subject.subject_data.as_mut().unwrap().owner = owner.clone();
+        let subject_id = subject.subject_data.as_ref().unwrap().subject_id.clone();
+        let rule = json!({"allowance": false, "approvalRequired": false});
+        let governance: Governance = serde_json::from_value(json!({
+            "members": [
+                {"id": "Owner", "tags": {}, "key": owner.to_str()},
+                {"id": "Approver", "tags": {}, "key": approver}
+            ],
+            "schemas": [{"id": "Prueba", "tags": {}, "content": get_schema()}],
+            "policies": [{
+                "id": "Prueba",
+                "validation": {"quorum": 1.0, "validators": [owner.to_str()]},
+                "approval": {"quorum": 1.0, "approvers": [approver]},
+                "invokation": {
+                    "owner": {"allowance": true, "approvalRequired": true},
+                    "set": {"allowance": false, "approvalRequired": false, "invokers": []},
+                    "all": rule,
+                    "external": rule
+                }
+            }]
+        }))
+        .unwrap();
+        let temp_dir = TempDir::new("test_accept").unwrap();
+        let db = DB::new(open_db(temp_dir.path()));
+        let mut head = Event::default();
+        head.event_content.sn = 0;
+        db.set_event(&subject_id, head).unwrap();
+        db.set_subject(&subject_id, subject.clone()).unwrap();
+
+        let now = 1_700_000_000;
+        let request_for = |subject_id: &DigestIdentifier, payload: &str| {
+            let mut request = get_request(RequestPayload::Json(payload.into()));
+            request.request = EventRequestType::State(StateRequest {
+                subject_id: subject_id.clone(),
+                payload: RequestPayload::Json(payload.into()),
+            });
+            request.timestamp = now * 1000;
+            sign_request_with(
+                &mut request,
+                &KeyPair::Ed25519(Ed25519KeyPair::from_seed(&[0])),
+            );
+            add_signed_approval(&mut request, 1, Acceptance::Accept);
+            request
+        };
+        let request = request_for(&subject_id, "{\"tags\":[\"a\",\"b\",\"c\"]}");
+        let accept = |request: &EventRequest, subject: &Subject, now: i64| {
+            request.accept(subject, &governance, &db, now)
+        };
+        assert_eq!(Ok(()), accept(&request, &subject, now));
+
+        // Timestamp
+        assert!(matches!(
+            accept(&request, &subject, now + MAX_REQUEST_AGE_SECS + 1),
+            Err(SubjectError::RequestExpired { .. })
+        ));
+        // Signatures
+        let mut tampered = request.clone();
+        tampered.request = request_for(&subject_id, "{\"tags\":[]}").request;
+        assert!(matches!(
+            accept(&tampered, &subject, now),
+            Err(SubjectError::CryptoError(_))
+        ));
+        // Invoker authorization
+        let mut not_owned = subject.clone();
+        not_owned.subject_data.as_mut().unwrap().owner = key(1);
+        assert_eq!(
+            Err(SubjectError::InvokerNotAuthorized),
+            accept(&request, &not_owned, now)
+        );
+        // Quorum
+        let mut unapproved = request.clone();
+        unapproved.approvals.clear();
+        assert_eq!(
+            Err(SubjectError::ApprovalQuorumNotReached {
+                approvals: 0,
+                quorum: 1
+            }),
+            accept(&unapproved, &subject, now)
+        );
+        let mut rejected = unapproved.clone();
+        add_signed_approval(&mut rejected, 1, Acceptance::Reject);
+        assert_eq!(
+            Err(SubjectError::RequestRejected),
+            accept(&rejected, &subject, now)
+        );
+        let mut foreign = request.clone();
+        add_signed_approval(&mut foreign, 2, Acceptance::Accept);
+        assert_eq!(
+            Err(SubjectError::UnknownApprover(key(2).to_str())),
+            accept(&foreign, &subject, now)
+        );
+        // Schema
+        let invalid = request_for(&subject_id, "{\"tags\":[\"a\"]}");
+        assert!(matches!(
+            accept(&invalid, &subject, now),
+            Err(SubjectError::SchemaValidationFailedDetailed(_))
+        ));
+        // Chain context
+        let other = request_for(
+            &DigestIdentifier::default(),
+            "{\"tags\":[\"a\",\"b\",\"c\"]}",
+        );
+        assert_eq!(
+            Err(SubjectError::RequestSubjectMismatch),
+            accept(&other, &subject, now)
+        );
+        let mut stale = subject.clone();
+        stale.subject_data.as_mut().unwrap().sn = 1;
+        assert_eq!(
+            Err(SubjectError::StaleSubject {
+                given: 1,
+                stored: 0
+            }),
+            accept(&request, &stale, now)
+        );
+        db.set_negociating_true(&subject_id).unwrap();
+        assert_eq!(
+            Err(SubjectError::EventAlreadyProcessing),
+            accept(&request, &subject, now)
+        );
+    }
+
+    #[test]
+    fn test_check_timestamp() {
+        let mut request = get_request(RequestPayload::Json("{}".into()));
+        request.timestamp = 1_000_999;
+        let check = |now: i64| request.check_timestamp(&FixedClock(now), 60, 5);
+        assert_eq!(Ok(()), check(1_000));
+        assert_eq!(Ok(()), check(1_060));
+        assert_eq!(
+            Err(SubjectError::RequestExpired {
+                timestamp: 1_000,
+                now: 1_061
+            }),
+            check(1_061)
+        );
+        assert_eq!(Ok(()), check(995));
+        assert_eq!(
+            Err(SubjectError::RequestFromFuture {
+                timestamp: 1_000,
+                now: 994
+            }),
+            check(994)
+        );
+
+        request.timestamp = SystemClock.now_secs() * 1000;
+        assert_eq!(Ok(()), request.check_timestamp(&SystemClock, 60, 5));
+    }
+
+    #[test]
+    fn test_tally_approvals() {
+        let mut request = get_request(RequestPayload::Json("{}".into()));
+        assert_eq!((0, 0), request.tally_approvals());
+        let response = |seed: u8, approval_type: Acceptance, expected_sn: u64, timestamp: i64| {
+            let keys = KeyPair::Ed25519(Ed25519KeyPair::from_seed(&[seed]));
+            ApprovalResponse {
+                content: ApprovalResponseContent {
+                    signer: KeyIdentifier::new(keys.get_key_derivator(), &keys.public_key_bytes()),
+                    event_request_hash: DigestIdentifier::default(),
+                    approval_type,
+                    expected_sn,
+                    timestamp,
+                },
+                signature: request.signature.signature.clone(),
+            }
+        };
+        request.approvals = HashSet::from([
+            response(0, Acceptance::Accept, 1, 0),
+            response(1, Acceptance::Accept, 1, 0),
+            response(2, Acceptance::Reject, 1, 0),
+            // The latest response of a signer is the one counted
+            response(3, Acceptance::Accept, 1, 0),
+            response(3, Acceptance::Reject, 2, 1),
+            response(4, Acceptance::Reject, 1, 0),
+            response(4, Acceptance::Accept, 2, 1),
+            // A rejection wins a tie
+            response(5, Acceptance::Accept, 1, 0),
+            response(5, Acceptance::Reject, 2, 0),
+        ]);
+        assert_eq!(9, request.approvals.len());
+        assert_eq!((3, 3), request.tally_approvals());
+    }
+
+    #[test]
+    fn test_cbor_state_request() {
+        let mut request = get_request(RequestPayload::JsonPatch(
+            "[{\"op\":\"add\",\"path\":\"/tags/-\",\"value\":\"c\"}]".into(),
+        ));
+        add_approval(&mut request);
+        let bytes = request.to_cbor().unwrap();
+        assert_eq!(EventRequest::from_cbor(&bytes).unwrap(), request);
+    }
+
+    #[test]
+    fn test_cbor_create_request() {
+        let mut request = Event::default().event_content.event_request;
+        request.request = EventRequestType::Create(CreateRequest {
+            governance_id: Event::default().event_content.metadata.governance_id,
+            schema_id: "Prueba".into(),
+            namespace: "namespace1".into(),
+            payload: RequestPayload::Json("{\"tags\":[\"a\",\"b\"]}".into()),
+        });
+        add_approval(&mut request);
+        let bytes = request.to_cbor().unwrap();
+        assert_eq!(EventRequest::from_cbor(&bytes).unwrap(), request);
+        assert!(EventRequest::from_cbor(&bytes[1..]).is_err());
+    }
+
+    #[test]
+    fn test_simulate_valid_request() {
+        let mut request = get_request(RequestPayload::JsonPatch(
+            "[{\"op\":\"add\",\"path\":\"/tags/-\",\"value\":\"c\"}]".into(),
+        ));
+        sign_request(&mut request);
+        let subject = get_subject("{\"tags\":[\"a\",\"b\"]}");
+        let properties = request.simulate(&subject, &get_schema()).unwrap();
+        assert_eq!(properties, json!({"tags": ["a", "b", "c"]}));
+        // The subject itself is left untouched
+        assert_eq!(
+            subject.subject_data.unwrap().properties,
+            "{\"tags\":[\"a\",\"b\"]}"
+        );
+    }
+
+    #[test]
+    fn test_simulate_invalid_request() {
+        let mut request = get_request(RequestPayload::JsonPatch(
+            "[{\"op\":\"remove\",\"path\":\"/tags/1\"}]".into(),
+        ));
+        sign_request(&mut request);
+        let subject = get_subject("{\"tags\":[\"a\",\"b\"]}");
+        let Err(SubjectError::SchemaValidationFailedDetailed(errors)) =
+            request.simulate(&subject, &get_schema()) else {
+            panic!("Expected detailed schema error");
+        };
+        assert_eq!(errors[0].instance_path, "/tags");
+        // A request whose content no longer matches its signature is rejected
+        request.timestamp += 1;
+        assert_eq!(
+            request.simulate(&subject, &get_schema()),
+            Err(SubjectError::CryptoError(
+                CryptoErrorEvent::EventRequestHashingConflict
+            ))
+        );
+    }
+
+    #[test]
+    fn test_check_signatures_batch() {
+        let requests: Vec<EventRequest> = (0..9)
+            .map(|index| {
+                let mut request = get_request(RequestPayload::Json("{}".into()));
+                request.timestamp = index;
+                sign_request(&mut request);
+                // Every third request no longer matches its signature
+                if index % 3 == 0 {
+                    request.timestamp += 100;
+                }
+                request
+            })
+            .collect();
+        let results = EventRequest::check_signatures_batch(&requests);
+        assert_eq!(requests.len(), results.len());
+        for (request, result) in requests.iter().zip(results) {
+            assert_eq!(request.check_signatures(), result);
+            assert_eq!(request.timestamp < 100, result.is_ok());
         }
+        assert!(EventRequest::check_signatures_batch(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_check_invoker_authorized() {
+        let mut request = get_request(RequestPayload::Json("{}".into()));
+        sign_request(&mut request);
+        let signer = request.signature.content.signer.clone();
+        let other_keys = KeyPair::Ed25519(Ed25519KeyPair::from_seed(&[1]));
+        let other = KeyIdentifier::new(
+            other_keys.get_key_derivator(),
+            &other_keys.public_key_bytes(),
+        );
+        let mut subject = get_subject("{}");
+        let schema_id = subject.subject_data.as_ref().unwrap().schema_id.clone();
+        let governance = |set_invokers: Vec<String>| {
+            invokation_governance(&schema_id, &signer, &other, set_invokers)
+        };
+
+        // The signer is the owner of the subject
+        subject.subject_data.as_mut().unwrap().owner = signer.clone();
+        assert_eq!(
+            Ok(()),
+            request.check_invoker_authorized(&subject, &governance(vec![]))
+        );
+
+        // The signer is part of the set
+        subject.subject_data.as_mut().unwrap().owner = other.clone();
+        assert_eq!(
+            Ok(()),
+            request.check_invoker_authorized(&subject, &governance(vec![signer.to_str()]))
+        );
+        assert_eq!(
+            Err(SubjectError::InvokerNotAuthorized),
+            request.check_invoker_authorized(&subject, &governance(vec![]))
+        );
+
+        // The signer is not a member of the governance
+        let mut external = governance(vec![]);
+        external
+            .members
+            .retain(|member| member.key != signer.to_str());
+        assert_eq!(
+            Err(SubjectError::InvokerNotAuthorized),
+            request.check_invoker_authorized(&subject, &external)
+        );
+
+        external.policies.clear();
+        assert_eq!(
+            Err(SubjectError::PoliciesMissing),
+            request.check_invoker_authorized(&subject, &external)
+        );
+    }
+
+    #[test]
+    fn test_request_type_accessors() {
+        let state = get_request(RequestPayload::Json("{}".into())).request;
+        let create = EventRequestType::Create(CreateRequest {
+            governance_id: DigestIdentifier::default(),
+            schema_id: "schema".into(),
+            namespace: "namespace".into(),
+            payload: RequestPayload::Json("{}".into()),
+        });
+        assert!(create.is_create());
+        assert_eq!("schema", create.as_create().unwrap().schema_id);
+        assert!(create.as_state().is_none());
+        assert!(!state.is_create());
+        assert!(state.as_create().is_none());
+        assert_eq!(
+            DigestIdentifier::default(),
+            state.as_state().unwrap().subject_id
+        );
+
+        // A create request is rejected instead of panicking
+        let mut request = get_request(RequestPayload::Json("{}".into()));
+        request.request = create;
+        let subject = get_subject("{\"tags\":[\"a\",\"b\"]}");
+        assert_eq!(
+            Err(SubjectError::NotStateEvent),
+            request.get_event_from_state_request(
+                &subject,
+                1,
+                DigestIdentifier::default(),
+                &Governance {
+                    members: vec![],
+                    schemas: vec![],
+                    policies: vec![],
+                },
+                0,
+                &get_schema(),
+                true
+            )
+        );
+    }
+
+    #[test]
+    fn test_get_event_from_state_request_checks_invoker() {
+        let owner_keys = KeyPair::Ed25519(Ed25519KeyPair::from_seed(&[1]));
+        let owner = KeyIdentifier::new(
+            owner_keys.get_key_derivator(),
+            &owner_keys.public_key_bytes(),
+        );
+        let mut request = get_request(RequestPayload::Json("{\"tags\":[\"c\",\"d\"]}".into()));
+        sign_request(&mut request);
+        let signer = request.signature.content.signer.clone();
+        let mut subject = get_subject("{\"tags\":[\"a\",\"b\"]}");
+        subject.keys = Some(owner_keys);
+        subject.ledger_state.head_sn = Some(0);
+        let subject_data = subject.subject_data.as_mut().unwrap();
+        subject_data.owner = owner.clone();
+        let schema_id = subject_data.schema_id.clone();
+        let build = |governance: &Governance| {
+            request.clone().get_event_from_state_request(
+                &subject,
+                1,
+                DigestIdentifier::default(),
+                governance,
+                0,
+                &get_schema(),
+                true,
+            )
+        };
+
+        // The signer is neither the owner nor in the set of invokers
+        let governance = invokation_governance(&schema_id, &signer, &owner, vec![]);
+        assert_eq!(Err(SubjectError::InvokerNotAuthorized), build(&governance));
+
+        let governance = invokation_governance(&schema_id, &signer, &owner, vec![signer.to_str()]);
+        let event = build(&governance).unwrap();
+        assert_eq!(1, event.event_content.sn);
+        assert_eq!(
+            signer,
+            event.event_content.event_request.signature.content.signer
+        );
     }
 }
@@ -0,0 +1,546 @@
+//! Typed views over the properties of a governance subject
+use crate::{
+    errors::{Error, SubjectError},
+    identifier::{Derivable, DigestIdentifier, KeyIdentifier},
+    schema_handler::{digest_of, normalize_schema, ContentSchema, Schema},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+};
+
+/// Properties of a governance subject.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Governance {
+    pub members: Vec<Member>,
+    pub schemas: Vec<GovernanceSchema>,
+    pub policies: Vec<Policy>,
+}
+
+impl Governance {
+    /// Properties of a new governance whose only member is `founder`. It has no
+    /// schemas, and the founder validates, approves and invokes the governance
+    /// on their own.
+    pub fn bootstrap(founder: &KeyIdentifier) -> Value {
+        let founder = founder.to_str();
+        let owner_only = InvokationRule {
+            allowance: false,
+            approval_required: false,
+        };
+        let governance = Governance {
+            members: vec![Member {
+                id: "Founder".into(),
+                tags: HashMap::new(),
+                description: None,
+                key: founder.clone(),
+            }],
+            schemas: vec![],
+            policies: vec![Policy {
+                id: "governance".into(),
+                validation: ValidationPolicy {
+                    quorum: 1.0,
+                    validators: vec![founder.clone()],
+                },
+                approval: ApprovalPolicy {
+                    quorum: 1.0,
+                    approvers: vec![founder],
+                },
+                invokation: InvokationPolicy {
+                    owner: InvokationRule {
+                        allowance: true,
+                        approval_required: true,
+                    },
+                    set: SetInvokationRule {
+                        allowance: false,
+                        approval_required: false,
+                        invokers: vec![],
+                    },
+                    all: owner_only,
+                    external: owner_only,
+                },
+            }],
+        };
+        serde_json::to_value(governance).expect("Governance serializes to JSON")
+    }
+
+    /// Parses the properties of a governance subject.
+    pub fn from_properties(properties: &str) -> Result<Self, SubjectError> {
+        serde_json::from_str(properties).map_err(|_| SubjectError::ErrorParsingJsonString)
+    }
+
+    /// Identifier of the content of the governance, equal for governances that only
+    /// differ in formatting, key order or the annotations of their schemas.
+    pub fn digest(&self) -> Result<DigestIdentifier, Error> {
+        let mut governance = self.clone();
+        for schema in governance.schemas.iter_mut() {
+            schema.content = normalize_schema(&schema.content);
+        }
+        digest_of(&serde_json::to_value(governance)?)
+    }
+
+    /// Returns the JSON Schema of the subjects with schema `schema_id`.
+    pub fn content_schema(&self, schema_id: &str) -> Option<&Value> {
+        self.schemas
+            .iter()
+            .find(|schema| schema.id == schema_id)
+            .map(|schema| &schema.content)
+    }
+
+    pub fn compile_content_schema(&self, schema_id: &str) -> Result<ContentSchema, Error> {
+        let content = self
+            .content_schema(schema_id)
+            .ok_or(Error::SchemaNotFoundError)?;
+        ContentSchema::compile(content)
+    }
+
+    /// Checks that the content of every schema compiles, so that a broken schema is
+    /// found when the governance is created instead of when a subject uses it.
+    /// Returns the id of each schema that does not compile, with its error.
+    pub fn validate_content_schemas(&self) -> Result<(), Vec<(String, Error)>> {
+        let failed: Vec<(String, Error)> = self
+            .schemas
+            .iter()
+            .filter_map(|schema| {
+                Schema::compile(&schema.content)
+                    .err()
+                    .map(|error| (schema.id.clone(), error))
+            })
+            .collect();
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            Err(failed)
+        }
+    }
+
+    /// Members tagged with `key`. If `value` is given, the tag must also have that value.
+    pub fn members_with_tag(&self, key: &str, value: Option<&str>) -> Vec<&Member> {
+        self.members
+            .iter()
+            .filter(|member| match (member.tags.get(key), value) {
+                (None, _) => false,
+                (Some(_), None) => true,
+                (Some(tag_value), Some(value)) => tag_value.as_deref() == Some(value),
+            })
+            .collect()
+    }
+
+    /// Checks that no two members share a key, as signatures are attributed to
+    /// members by their key. Returns the duplicated keys in order of appearance.
+    pub fn check_unique_member_keys(&self) -> Result<(), Vec<String>> {
+        let mut seen = HashSet::new();
+        let mut duplicated: Vec<String> = Vec::new();
+        for member in self.members.iter() {
+            if !seen.insert(member.key.as_str()) && !duplicated.contains(&member.key) {
+                duplicated.push(member.key.clone());
+            }
+        }
+        if duplicated.is_empty() {
+            Ok(())
+        } else {
+            Err(duplicated)
+        }
+    }
+}
+
+/// Schema that the subjects of a governance can be created with.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GovernanceSchema {
+    pub id: String,
+    pub tags: HashMap<String, Option<String>>,
+    /// JSON Schema of the properties of the subjects
+    pub content: Value,
+}
+
+/// Who validates, approves and invokes the subjects of a schema.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Policy {
+    /// Id of the schema the policy applies to
+    pub id: String,
+    pub validation: ValidationPolicy,
+    pub approval: ApprovalPolicy,
+    pub invokation: InvokationPolicy,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ValidationPolicy {
+    pub quorum: f64,
+    /// Keys of the validators
+    pub validators: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ApprovalPolicy {
+    pub quorum: f64,
+    /// Keys of the approvers
+    pub approvers: Vec<String>,
+}
+
+/// Participant of a governance, identified in its policies by its key.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Member {
+    pub id: String,
+    pub tags: HashMap<String, Option<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub key: String,
+}
+
+/// Whether a group of invokers may send requests and if those need approval.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InvokationRule {
+    pub allowance: bool,
+    pub approval_required: bool,
+}
+
+/// Invokation rule that only applies to an explicit list of members.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetInvokationRule {
+    pub allowance: bool,
+    pub approval_required: bool,
+    /// Keys of the members of the set
+    pub invokers: Vec<String>,
+}
+
+/// Outcome of evaluating an [InvokationPolicy] for an invoker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvokationDecision {
+    pub allowed: bool,
+    pub approval_required: bool,
+}
+
+impl From<InvokationRule> for InvokationDecision {
+    fn from(rule: InvokationRule) -> Self {
+        Self {
+            allowed: rule.allowance,
+            approval_required: rule.approval_required,
+        }
+    }
+}
+
+/// `invokation` section of the policy of a schema.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InvokationPolicy {
+    pub owner: InvokationRule,
+    pub set: SetInvokationRule,
+    pub all: InvokationRule,
+    pub external: InvokationRule,
+}
+
+impl InvokationPolicy {
+    /// Parses the keys of the members of the set.
+    pub fn set_invokers(&self) -> Result<HashSet<KeyIdentifier>, SubjectError> {
+        self.set
+            .invokers
+            .iter()
+            .map(|invoker| {
+                KeyIdentifier::from_str(invoker)
+                    .map_err(|_| SubjectError::InvalidKeyIdentifier(invoker.clone()))
+            })
+            .collect()
+    }
+
+    /// Decides whether `invoker` may send a request to a subject owned by `owner`.
+    /// The most specific rule applies: the owner one, then the set one, then the
+    /// one for all the members and finally the external one.
+    pub fn evaluate(
+        &self,
+        invoker: &KeyIdentifier,
+        owner: &KeyIdentifier,
+        members: &[Member],
+    ) -> Result<InvokationDecision, SubjectError> {
+        // Only members can be part of the set
+        for set_invoker in self.set.invokers.iter() {
+            if !members.iter().any(|member| &member.key == set_invoker) {
+                return Err(SubjectError::InvalidMemberInPolicies);
+            }
+        }
+        let set_invokers = self.set_invokers()?;
+        let rule = if invoker == owner {
+            self.owner
+        } else if set_invokers.contains(invoker) {
+            InvokationRule {
+                allowance: self.set.allowance,
+                approval_required: self.set.approval_required,
+            }
+        } else if members.iter().any(|member| member.key == invoker.to_str()) {
+            self.all
+        } else {
+            self.external
+        };
+        Ok(rule.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+
+    use serde_json::{json, Value};
+
+    use crate::{
+        crypto::{Ed25519KeyPair, KeyGenerator, KeyMaterial, KeyPair},
+        errors::{Error, SubjectError},
+        identifier::{Derivable, KeyIdentifier},
+        models::state::validate_governance_invariants,
+        schema_handler::{get_governance_schema, Schema},
+    };
+
+    use super::{Governance, GovernanceSchema, InvokationDecision, InvokationPolicy, Member};
+
+    fn key(seed: u8) -> KeyIdentifier {
+        let keys = KeyPair::Ed25519(Ed25519KeyPair::from_seed(&[seed]));
+        KeyIdentifier::new(keys.get_key_derivator(), &keys.public_key_bytes())
+    }
+
+    fn member(id: &str, key: &KeyIdentifier) -> Member {
+        serde_json::from_value(json!({"id": id, "tags": {}, "key": key.to_str()})).unwrap()
+    }
+
+    fn decision(allowed: bool, approval_required: bool) -> InvokationDecision {
+        InvokationDecision {
+            allowed,
+            approval_required,
+        }
+    }
+
+    #[test]
+    fn test_content_schema() {
+        let properties = json!({
+            "members": [{"id": "Owner", "tags": {}, "key": key(0).to_str()}],
+            "schemas": [{
+                "id": "Counter",
+                "tags": {},
+                "content": {"type": "integer", "minimum": 0}
+            }],
+            "policies": []
+        });
+        let governance = Governance::from_properties(&properties.to_string()).unwrap();
+        assert_eq!(
+            Some(&json!({"type": "integer", "minimum": 0})),
+            governance.content_schema("Counter")
+        );
+        let schema = governance.compile_content_schema("Counter").unwrap();
+        assert!(schema.validate(&json!(1)));
+        assert!(!schema.validate(&json!(-1)));
+
+        assert_eq!(None, governance.content_schema("Missing"));
+        assert!(matches!(
+            governance.compile_content_schema("Missing"),
+            Err(Error::SchemaNotFoundError)
+        ));
+        assert_eq!(
+            Err(SubjectError::ErrorParsingJsonString),
+            Governance::from_properties("{\"members\": []}")
+        );
+    }
+
+    #[test]
+    fn test_validate_content_schemas() {
+        let schema = |id: &str, content: Value| GovernanceSchema {
+            id: id.into(),
+            tags: HashMap::new(),
+            content,
+        };
+        let mut governance = Governance {
+            members: vec![],
+            schemas: vec![schema("Counter", json!({"type": "integer", "minimum": 0}))],
+            policies: vec![],
+        };
+        assert!(governance.validate_content_schemas().is_ok());
+
+        governance
+            .schemas
+            .push(schema("Broken", json!({"type": "not-a-type"})));
+        governance
+            .schemas
+            .push(schema("Text", json!({"type": "string"})));
+        let failed = governance.validate_content_schemas().unwrap_err();
+        assert_eq!(1, failed.len());
+        assert_eq!("Broken", failed[0].0);
+        assert!(matches!(failed[0].1, Error::SchemaCreationError));
+    }
+
+    #[test]
+    fn test_bootstrap() {
+        let founder = key(0);
+        let properties = Governance::bootstrap(&founder);
+        let schema = Schema::compile(&get_governance_schema()).unwrap();
+        assert!(schema.validate(&properties));
+        assert_eq!(Ok(()), validate_governance_invariants(&properties));
+
+        let governance = Governance::from_properties(&properties.to_string()).unwrap();
+        assert_eq!(1, governance.members.len());
+        assert_eq!(founder.to_str(), governance.members[0].key);
+        assert_eq!(1, governance.policies.len());
+        let invokation = &governance.policies[0].invokation;
+        let members = &governance.members;
+        assert_eq!(
+            Ok(decision(true, true)),
+            invokation.evaluate(&founder, &founder, members)
+        );
+        assert_eq!(
+            Ok(decision(false, false)),
+            invokation.evaluate(&key(1), &founder, members)
+        );
+    }
+
+    #[test]
+    fn test_digest() {
+        let founder = key(0).to_str();
+        let governance = Governance::from_properties(&format!(
+            r#"{{
+                "members": [{{"id": "Founder", "tags": {{}}, "key": "{founder}"}}],
+                "schemas": [{{
+                    "id": "Counter",
+                    "tags": {{}},
+                    "content": {{"type": "integer", "minimum": 0, "$comment": "Never negative"}}
+                }}],
+                "policies": []
+            }}"#
+        ))
+        .unwrap();
+        let reformatted = Governance::from_properties(&format!(
+            r#"{{"policies":[],"schemas":[{{"content":{{"minimum":0,"type":"integer"}},"tags":{{}},"id":"Counter"}}],"members":[{{"key":"{founder}","tags":{{}},"id":"Founder"}}]}}"#
+        ))
+        .unwrap();
+        assert_eq!(governance.digest().unwrap(), reformatted.digest().unwrap());
+
+        let mut changed = reformatted.clone();
+        changed.schemas[0].content = json!({"type": "integer", "minimum": 1});
+        assert_ne!(governance.digest().unwrap(), changed.digest().unwrap());
+        let mut changed = reformatted;
+        changed.members[0].id = "Owner".into();
+        assert_ne!(governance.digest().unwrap(), changed.digest().unwrap());
+    }
+
+    #[test]
+    fn test_members_with_tag() {
+        let tagged = |id: &str, seed: u8, tags: serde_json::Value| -> Member {
+            serde_json::from_value(json!({"id": id, "tags": tags, "key": key(seed).to_str()}))
+                .unwrap()
+        };
+        let governance = Governance {
+            members: vec![
+                tagged("Alice", 0, json!({"role": "validator"})),
+                tagged("Bob", 1, json!({"role": "approver", "region": "eu"})),
+                tagged("Carol", 2, json!({"role": null})),
+                tagged("Dave", 3, json!({})),
+            ],
+            schemas: vec![],
+            policies: vec![],
+        };
+        let ids = |members: Vec<&Member>| -> Vec<String> {
+            members
+                .into_iter()
+                .map(|member| member.id.clone())
+                .collect()
+        };
+
+        assert_eq!(
+            vec!["Alice", "Bob", "Carol"],
+            ids(governance.members_with_tag("role", None))
+        );
+        assert_eq!(
+            vec!["Alice"],
+            ids(governance.members_with_tag("role", Some("validator")))
+        );
+        assert_eq!(
+            vec!["Bob"],
+            ids(governance.members_with_tag("region", None))
+        );
+        assert!(governance
+            .members_with_tag("role", Some("owner"))
+            .is_empty());
+        assert!(governance.members_with_tag("missing", None).is_empty());
+    }
+
+    #[test]
+    fn test_check_unique_member_keys() {
+        let mut governance = Governance {
+            members: vec![
+                member("Alice", &key(0)),
+                member("Bob", &key(1)),
+                member("Carol", &key(2)),
+            ],
+            schemas: vec![],
+            policies: vec![],
+        };
+        assert_eq!(Ok(()), governance.check_unique_member_keys());
+
+        governance.members.push(member("Dave", &key(1)));
+        governance.members.push(member("Eve", &key(0)));
+        governance.members.push(member("Frank", &key(1)));
+        assert_eq!(
+            Err(vec![key(1).to_str(), key(0).to_str()]),
+            governance.check_unique_member_keys()
+        );
+    }
+
+    #[test]
+    fn test_set_invokers() {
+        let rule = json!({"allowance": true, "approvalRequired": false});
+        let mut policy: InvokationPolicy = serde_json::from_value(json!({
+            "owner": rule,
+            "set": {"allowance": true, "approvalRequired": true, "invokers": [key(1).to_str(), key(2).to_str()]},
+            "all": rule,
+            "external": rule
+        }))
+        .unwrap();
+        assert_eq!(Ok(HashSet::from([key(1), key(2)])), policy.set_invokers());
+
+        policy.set.invokers.push("not a key".into());
+        assert_eq!(
+            Err(SubjectError::InvalidKeyIdentifier("not a key".into())),
+            policy.set_invokers()
+        );
+    }
+
+    #[test]
+    fn test_evaluate_invokation_policy() {
+        let (owner, set_member, member_key, external) = (key(0), key(1), key(2), key(3));
+        let members = vec![
+            member("Owner", &owner),
+            member("SetMember", &set_member),
+            member("Member", &member_key),
+        ];
+        let policy: InvokationPolicy = serde_json::from_value(json!({
+            "owner": {"allowance": true, "approvalRequired": false},
+            "set": {"allowance": true, "approvalRequired": true, "invokers": [set_member.to_str()]},
+            "all": {"allowance": false, "approvalRequired": false},
+            "external": {"allowance": true, "approvalRequired": true}
+        }))
+        .unwrap();
+
+        assert_eq!(
+            Ok(decision(true, false)),
+            policy.evaluate(&owner, &owner, &members)
+        );
+        assert_eq!(
+            Ok(decision(true, true)),
+            policy.evaluate(&set_member, &owner, &members)
+        );
+        assert_eq!(
+            Ok(decision(false, false)),
+            policy.evaluate(&member_key, &owner, &members)
+        );
+        assert_eq!(
+            Ok(decision(true, true)),
+            policy.evaluate(&external, &owner, &members)
+        );
+        // The owner rule applies even if the owner is also part of the set
+        assert_eq!(
+            Ok(decision(true, false)),
+            policy.evaluate(&set_member, &set_member, &members)
+        );
+        // Members of the set must be members of the governance
+        assert_eq!(
+            Err(SubjectError::InvalidMemberInPolicies),
+            policy.evaluate(&owner, &owner, &members[..1])
+        );
+    }
+}
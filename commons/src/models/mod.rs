@@ -1,7 +1,9 @@
 pub mod approval_signature;
+pub mod clock;
 pub mod event;
 pub mod event_content;
 pub mod event_request;
+pub mod governance;
 pub mod notification;
 pub mod signature;
 pub mod state;
@@ -1,8 +1,14 @@
 //! Define the data structures related to signatures
-use crate::identifier::{DigestIdentifier, KeyIdentifier, SignatureIdentifier};
+use crate::{
+    errors::CryptoErrorEvent,
+    identifier::{Derivable, DigestIdentifier, KeyIdentifier, SignatureIdentifier},
+};
 use borsh::{BorshDeserialize, BorshSerialize};
 use serde::{Deserialize, Serialize};
-use std::hash::{Hash, Hasher};
+use std::{
+    collections::{HashMap, HashSet},
+    hash::{Hash, Hasher},
+};
 use utoipa::ToSchema;
 
 /// Defines the data used to generate the signature, as well as the signer's identifier.
@@ -48,3 +54,172 @@ pub struct Signature {
     #[schema(value_type = String)]
     pub signature: SignatureIdentifier,
 }
+
+impl Signature {
+    /// Checks that the signature was made over `content_hash` and that it is
+    /// valid for its signer.
+    pub fn verify_over(&self, content_hash: &DigestIdentifier) -> Result<(), CryptoErrorEvent> {
+        if &self.content.event_content_hash != content_hash {
+            return Err(CryptoErrorEvent::SignatureHashingConflict);
+        }
+        self.content
+            .signer
+            .verify(&content_hash.derivative(), self.signature.clone())
+            .map_err(|_| CryptoErrorEvent::SignatureInvalid)
+    }
+}
+
+/// Collection of signatures that keeps a single signature per signer.
+/// It converts from and into the `HashSet<Signature>` used by the database.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SignatureSet(HashMap<KeyIdentifier, Signature>);
+
+impl SignatureSet {
+    pub fn new() -> Self {
+        Self(HashMap::new())
+    }
+
+    /// Inserts the signature replacing the one of the same signer, if any,
+    /// which is returned.
+    pub fn insert_dedup_by_signer(&mut self, signature: Signature) -> Option<Signature> {
+        self.0.insert(signature.content.signer.clone(), signature)
+    }
+
+    pub fn signers(&self) -> HashSet<KeyIdentifier> {
+        self.0.keys().cloned().collect()
+    }
+
+    pub fn get(&self, signer: &KeyIdentifier) -> Option<&Signature> {
+        self.0.get(signer)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Signature> {
+        self.0.values()
+    }
+}
+
+impl Extend<Signature> for SignatureSet {
+    fn extend<T: IntoIterator<Item = Signature>>(&mut self, iter: T) {
+        for signature in iter {
+            self.insert_dedup_by_signer(signature);
+        }
+    }
+}
+
+impl FromIterator<Signature> for SignatureSet {
+    fn from_iter<T: IntoIterator<Item = Signature>>(iter: T) -> Self {
+        let mut signature_set = SignatureSet::new();
+        signature_set.extend(iter);
+        signature_set
+    }
+}
+
+impl From<HashSet<Signature>> for SignatureSet {
+    fn from(signatures: HashSet<Signature>) -> Self {
+        signatures.into_iter().collect()
+    }
+}
+
+impl From<SignatureSet> for HashSet<Signature> {
+    fn from(signature_set: SignatureSet) -> Self {
+        signature_set.0.into_values().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::{
+        crypto::{Ed25519KeyPair, KeyGenerator, KeyMaterial, KeyPair, Payload, DSA},
+        errors::CryptoErrorEvent,
+        identifier::{Derivable, DigestIdentifier, KeyIdentifier, SignatureIdentifier},
+    };
+
+    use super::{Signature, SignatureContent, SignatureSet};
+
+    fn sign(seed: u8, content: &str) -> Signature {
+        let keys = KeyPair::Ed25519(Ed25519KeyPair::from_seed(&[seed]));
+        let hash = DigestIdentifier::from_serializable_borsh(content).unwrap();
+        let signer = KeyIdentifier::new(keys.get_key_derivator(), &keys.public_key_bytes());
+        let signature = keys.sign(Payload::Buffer(hash.derivative())).unwrap();
+        Signature {
+            content: SignatureContent {
+                signer: signer.clone(),
+                event_content_hash: hash,
+                timestamp: 0,
+            },
+            signature: SignatureIdentifier::new(signer.to_signature_derivator(), &signature),
+        }
+    }
+
+    #[test]
+    fn test_signature_set_dedup_by_signer() {
+        let mut signature_set = SignatureSet::new();
+        assert!(signature_set.is_empty());
+        let old_signature = sign(0, "old content");
+        let new_signature = sign(0, "new content");
+        assert_eq!(
+            None,
+            signature_set.insert_dedup_by_signer(old_signature.clone())
+        );
+        assert_eq!(
+            Some(old_signature),
+            signature_set.insert_dedup_by_signer(new_signature.clone())
+        );
+        assert_eq!(1, signature_set.len());
+        assert_eq!(
+            Some(&new_signature),
+            signature_set.get(&new_signature.content.signer)
+        );
+    }
+
+    #[test]
+    fn test_signature_set_signers() {
+        let signatures: Vec<Signature> = vec![
+            sign(0, "content"),
+            sign(1, "content"),
+            sign(1, "other content"),
+            sign(2, "content"),
+        ];
+        let expected_signers: HashSet<KeyIdentifier> = signatures
+            .iter()
+            .map(|signature| signature.content.signer.clone())
+            .collect();
+        let signature_set: SignatureSet = signatures.into_iter().collect();
+        assert_eq!(3, signature_set.len());
+        assert_eq!(expected_signers, signature_set.signers());
+        // Round trip with the representation stored in the database
+        let stored: HashSet<Signature> = signature_set.clone().into();
+        assert_eq!(3, stored.len());
+        assert_eq!(signature_set, SignatureSet::from(stored));
+    }
+
+    #[test]
+    fn test_verify_over() {
+        let signature = sign(0, "content");
+        let hash = DigestIdentifier::from_serializable_borsh("content").unwrap();
+        assert_eq!(Ok(()), signature.verify_over(&hash));
+        // Content other than the signed one
+        let other_hash = DigestIdentifier::from_serializable_borsh("other content").unwrap();
+        assert_eq!(
+            Err(CryptoErrorEvent::SignatureHashingConflict),
+            signature.verify_over(&other_hash)
+        );
+        // Signature made by a different key than the declared signer
+        let mut tampered = signature.clone();
+        tampered.signature = sign(1, "content").signature;
+        assert_eq!(
+            Err(CryptoErrorEvent::SignatureInvalid),
+            tampered.verify_over(&hash)
+        );
+    }
+}
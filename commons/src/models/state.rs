@@ -9,15 +9,15 @@ use crate::{
     schema_handler::{get_governance_schema, Schema},
 };
 use chrono::Utc;
-use json_patch::patch;
-use serde::{Deserialize, Serialize};
+use json_patch::{diff, PatchOperation};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::Value;
 use utoipa::ToSchema;
 
 use super::{
     event::Event,
     event_content::EventContent,
-    event_request::{EventRequestType, RequestPayload},
+    event_request::{apply_patch, EventRequestType, RequestPayload},
     signature::{Signature, SignatureContent},
 };
 use borsh::{BorshDeserialize, BorshSerialize};
@@ -72,6 +72,33 @@ pub struct SubjectData {
     pub properties: String,
 }
 
+/// Checks the invariants of a governance document that must hold even if its
+/// JSON Schema validation has been skipped: there is at least one member, and
+/// neither member ids nor schema ids are repeated. The id "governance" is
+/// reserved for the governance schema itself.
+pub fn validate_governance_invariants(properties: &Value) -> Result<(), SubjectError> {
+    let members = match properties.get("members").and_then(Value::as_array) {
+        Some(members) if !members.is_empty() => members,
+        _ => return Err(SubjectError::EmptyGovernanceMembers),
+    };
+    let mut member_ids = HashSet::new();
+    for member in members {
+        let member_id = member.get("id").and_then(Value::as_str).unwrap_or_default();
+        if !member_ids.insert(member_id) {
+            return Err(SubjectError::DuplicatedMemberId(member_id.to_owned()));
+        }
+    }
+    let mut schema_ids = HashSet::from(["governance"]);
+    let schemas = properties.get("schemas").and_then(Value::as_array);
+    for schema in schemas.into_iter().flatten() {
+        let schema_id = schema.get("id").and_then(Value::as_str).unwrap_or_default();
+        if !schema_ids.insert(schema_id) {
+            return Err(SubjectError::DuplicatedSchemaId(schema_id.to_owned()));
+        }
+    }
+    Ok(())
+}
+
 impl Subject {
     pub fn new(
         event_content: &EventContent,
@@ -105,6 +132,7 @@ impl Subject {
                         let mut schema_ids: HashSet<String> = HashSet::new();
                         schema_ids.insert(String::from("governance"));
                         if &gov_schema == subject_schema {
+                            validate_governance_invariants(&properties)?;
                             // It is governance: checking subschemas
                             // Properties validation
                             let schemas = properties
@@ -288,9 +316,7 @@ impl Subject {
                         serde_json::from_str(&subject_data.properties) else {
                             return Err(SubjectError::ErrorParsingJsonString);
                         };
-                        let Ok(()) = patch(&mut properties, &patch_json) else {
-                        return Err(SubjectError::ErrorApplyingPatch);
-                    };
+                        apply_patch(&mut properties, &patch_json)?;
                         let Ok(result) = serde_json::to_string(&properties) else {
                         return Err(SubjectError::ErrorParsingJsonString);
                     };
@@ -322,6 +348,56 @@ impl Subject {
         Ok(())
     }
 
+    /// Returns the JSON Patch (RFC 6902) that turns the current properties of the
+    /// subject into `candidate_properties`, without modifying the subject.
+    pub fn diff(&self, candidate_properties: &Value) -> Result<Vec<PatchOperation>, SubjectError> {
+        let Some(subject_data) = self.subject_data.as_ref() else {
+            return Err(SubjectError::SubjectHasNoData);
+        };
+        let Ok(properties) = serde_json::from_str::<Value>(&subject_data.properties) else {
+            return Err(SubjectError::ErrorParsingJsonString);
+        };
+        Ok(diff(&properties, candidate_properties).0)
+    }
+
+    /// Deserializes the properties of the subject into `T`.
+    pub fn properties_as<T: DeserializeOwned>(&self) -> Result<T, SubjectError> {
+        let Some(subject_data) = self.subject_data.as_ref() else {
+            return Err(SubjectError::SubjectHasNoData);
+        };
+        serde_json::from_str(&subject_data.properties)
+            .map_err(|_| SubjectError::ErrorParsingJsonString)
+    }
+
+    /// Checks that the current properties of the subject are still valid for
+    /// `schema`, e.g. after the governance has changed the schema of the subject.
+    pub fn validate_against_schema(&self, schema: &Value) -> Result<(), SubjectError> {
+        let Some(subject_data) = self.subject_data.as_ref() else {
+            return Err(SubjectError::SubjectHasNoData);
+        };
+        let Ok(properties) = serde_json::from_str::<Value>(&subject_data.properties) else {
+            return Err(SubjectError::ErrorParsingJsonString);
+        };
+        let Ok(schema) = Schema::compile(schema) else {
+            return Err(SubjectError::SchemaDoesNotCompile);
+        };
+        schema
+            .validate_with_errors(&properties)
+            .map_err(SubjectError::SchemaValidationFailedDetailed)
+    }
+
+    /// Governances are created without a governance of their own, so their
+    /// `governance_id` is empty. A subject governed by itself is also a governance.
+    pub fn is_governance(&self) -> bool {
+        match self.subject_data.as_ref() {
+            Some(subject_data) => {
+                subject_data.governance_id.digest.is_empty()
+                    || subject_data.governance_id == subject_data.subject_id
+            }
+            None => false,
+        }
+    }
+
     pub fn get_sn(&self) -> u64 {
         self.subject_data.as_ref().expect("Hay subject").sn
     }
@@ -333,7 +409,7 @@ impl Subject {
         if self.keys.is_none() {
             return Err(SubjectError::NotOwnerOfSubject);
         }
-        match DigestIdentifier::from_serializable_borsh(event_content.clone()) {
+        match event_content.signing_hash() {
             Err(_) => Err(SubjectError::EventContentSerializationFailed),
             Ok(event_content_hash) => {
                 let signature = match self
@@ -441,9 +517,7 @@ impl Subject {
                         serde_json::from_str(&subject_data.properties) else {
                             return Err(SubjectError::ErrorParsingJsonString);
                         };
-                        let Ok(()) = patch(&mut properties, &patch_json) else {
-                            return Err(SubjectError::ErrorApplyingPatch);
-                        };
+                        apply_patch(&mut properties, &patch_json)?;
                         if !subject_schema.validate(&properties) {
                             return Err(SubjectError::SchemaValidationFailed);
                         }
@@ -489,9 +563,7 @@ impl Subject {
                         serde_json::from_str(&subject_data.properties) else {
                             return Err(SubjectError::ErrorParsingJsonString);
                         };
-                        let Ok(()) = patch(&mut properties, &patch_json) else {
-                            return Err(SubjectError::ErrorApplyingPatch);
-                        };
+                        apply_patch(&mut properties, &patch_json)?;
                         if !subject_schema.validate(&properties) {
                             return Err(SubjectError::SchemaValidationFailed);
                         }
@@ -522,3 +594,193 @@ impl PartialEq for Subject {
             && self.ledger_state == other.ledger_state
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use json_patch::{patch, Patch};
+    use serde::Deserialize;
+    use serde_json::json;
+
+    use crate::{
+        crypto::{Ed25519KeyPair, KeyGenerator, KeyMaterial, KeyPair},
+        errors::SubjectError,
+        identifier::{DigestIdentifier, KeyIdentifier},
+    };
+
+    use super::{validate_governance_invariants, LedgerState, Subject, SubjectData};
+
+    fn create_subject(properties: &str) -> Subject {
+        let keys = KeyPair::Ed25519(Ed25519KeyPair::from_seed(&[]));
+        let public_key = KeyIdentifier::new(keys.get_key_derivator(), &keys.public_key_bytes());
+        Subject {
+            subject_data: Some(SubjectData {
+                subject_id: Default::default(),
+                governance_id: Default::default(),
+                sn: 0,
+                public_key: public_key.clone(),
+                namespace: "".into(),
+                schema_id: "governance".into(),
+                owner: public_key,
+                properties: properties.into(),
+            }),
+            keys: None,
+            ledger_state: LedgerState::default(),
+        }
+    }
+
+    #[test]
+    fn test_properties_as() {
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Person {
+            name: String,
+            age: u8,
+        }
+
+        let subject = create_subject(r#"{"name": "Alice", "age": 17}"#);
+        assert_eq!(
+            Ok(Person {
+                name: "Alice".into(),
+                age: 17
+            }),
+            subject.properties_as::<Person>()
+        );
+        let subject = create_subject(r#"{"name": "Alice", "age": "seventeen"}"#);
+        assert_eq!(
+            Err(SubjectError::ErrorParsingJsonString),
+            subject.properties_as::<Person>()
+        );
+        assert_eq!(
+            Err(SubjectError::SubjectHasNoData),
+            Subject::new_empty(LedgerState::default()).properties_as::<Person>()
+        );
+    }
+
+    #[test]
+    fn test_validate_against_schema() {
+        let subject = create_subject(r#"{"name": "Alice", "age": 17}"#);
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "name": {"type": "string"},
+                "age": {"type": "integer", "minimum": 0}
+            },
+            "required": ["name"]
+        });
+        assert_eq!(Ok(()), subject.validate_against_schema(&schema));
+
+        // The new version of the schema only accepts adults
+        let tightened = json!({
+            "type": "object",
+            "properties": {
+                "name": {"type": "string"},
+                "age": {"type": "integer", "minimum": 18}
+            },
+            "required": ["name", "age"]
+        });
+        match subject.validate_against_schema(&tightened) {
+            Err(SubjectError::SchemaValidationFailedDetailed(errors)) => {
+                assert_eq!(1, errors.len());
+                assert_eq!("/age", errors[0].instance_path);
+            }
+            result => panic!("Unexpected result {:?}", result),
+        }
+
+        assert_eq!(
+            Err(SubjectError::SchemaDoesNotCompile),
+            subject.validate_against_schema(&json!({"type": 1}))
+        );
+        assert_eq!(
+            Err(SubjectError::SubjectHasNoData),
+            Subject::new_empty(LedgerState::default()).validate_against_schema(&schema)
+        );
+    }
+
+    #[test]
+    fn test_diff() {
+        let current = json!({
+            "members": [{"id": "Alice", "key": "A"}],
+            "quorum": 0.5,
+            "description": "old"
+        });
+        let candidate = json!({
+            "members": [{"id": "Alice", "key": "A"}, {"id": "Bob", "key": "B"}],
+            "quorum": 1.0,
+            "tags": {"version": "2"}
+        });
+        let subject = create_subject(&current.to_string());
+        let operations = subject.diff(&candidate).unwrap();
+        assert!(!operations.is_empty());
+        let mut patched = current.clone();
+        patch(&mut patched, &Patch(operations)).unwrap();
+        assert_eq!(candidate, patched);
+        // The subject itself is not modified
+        assert_eq!(
+            current.to_string(),
+            subject.subject_data.unwrap().properties
+        );
+
+        let subject = create_subject(&candidate.to_string());
+        assert!(subject.diff(&candidate).unwrap().is_empty());
+        let subject = Subject::new_empty(LedgerState::default());
+        assert_eq!(
+            Err(SubjectError::SubjectHasNoData),
+            subject.diff(&candidate)
+        );
+    }
+
+    #[test]
+    fn test_is_governance() {
+        let governance = create_subject("{}");
+        assert!(governance.is_governance());
+        let mut subject = create_subject("{}");
+        let subject_data = subject.subject_data.as_mut().unwrap();
+        subject_data.subject_id = DigestIdentifier::from_serializable_borsh("subject").unwrap();
+        subject_data.governance_id = DigestIdentifier::from_serializable_borsh("gov").unwrap();
+        assert!(!subject.is_governance());
+        // Self governed subject
+        let subject_data = subject.subject_data.as_mut().unwrap();
+        subject_data.governance_id = subject_data.subject_id.clone();
+        assert!(subject.is_governance());
+        assert!(!Subject::new_empty(LedgerState::default()).is_governance());
+    }
+
+    #[test]
+    fn test_validate_governance_invariants() {
+        let mut properties = json!({
+            "members": [
+                {"id": "Alice", "tags": {}, "key": "A"},
+                {"id": "Bob", "tags": {}, "key": "B"}
+            ],
+            "schemas": [{"id": "test", "tags": {}, "content": {}}],
+            "policies": []
+        });
+        assert_eq!(Ok(()), validate_governance_invariants(&properties));
+
+        properties["members"][1]["id"] = json!("Alice");
+        assert_eq!(
+            Err(SubjectError::DuplicatedMemberId("Alice".into())),
+            validate_governance_invariants(&properties)
+        );
+
+        properties["members"] = json!([]);
+        assert_eq!(
+            Err(SubjectError::EmptyGovernanceMembers),
+            validate_governance_invariants(&properties)
+        );
+
+        properties["members"] = json!([{"id": "Alice", "tags": {}, "key": "A"}]);
+        properties["schemas"] = json!([
+            {"id": "test", "tags": {}, "content": {}},
+            {"id": "test", "tags": {}, "content": {}}
+        ]);
+        assert_eq!(
+            Err(SubjectError::DuplicatedSchemaId("test".into())),
+            validate_governance_invariants(&properties)
+        );
+        properties["schemas"] = json!([{"id": "governance", "tags": {}, "content": {}}]);
+        assert_eq!(
+            Err(SubjectError::DuplicatedSchemaId("governance".into())),
+            validate_governance_invariants(&properties)
+        );
+    }
+}
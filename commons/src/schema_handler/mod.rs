@@ -1,8 +1,67 @@
+use std::collections::HashMap;
+
 use serde_json::{json, Value};
 
-use jsonschema::JSONSchema;
+use jsonschema::{error::ValidationErrorKind, JSONSchema, ValidationError};
+use thiserror::Error;
+
+use crate::{
+    errors::Error,
+    identifier::{derive::digest::DigestDerivator, DigestIdentifier},
+};
+
+/// Single failure reported by the JSON Schema validator.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaValidationError {
+    /// JSON Pointer to the offending location of the validated instance
+    pub instance_path: String,
+    /// JSON Pointer to the keyword of the schema that failed
+    pub schema_path: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for SchemaValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.instance_path, self.message)
+    }
+}
 
-use crate::errors::Error;
+/// Reason why a schema could not be compiled.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum SchemaCompileError {
+    #[error("Invalid reference: {0}")]
+    InvalidReference(String),
+    #[error("Invalid regular expression: {0}")]
+    InvalidPattern(String),
+    #[error("Unknown format: {0}")]
+    UnknownFormat(String),
+    #[error("{0}")]
+    Other(String),
+}
+
+impl From<ValidationError<'_>> for SchemaCompileError {
+    fn from(error: ValidationError<'_>) -> Self {
+        // Invalid regexes and unknown formats are both reported as format errors
+        // of the offending schema value
+        let value = match error.instance.as_str() {
+            Some(value) => value.to_owned(),
+            None => error.instance.to_string(),
+        };
+        match &error.kind {
+            ValidationErrorKind::Format { format: "regex" } => Self::InvalidPattern(value),
+            ValidationErrorKind::Format {
+                format: "unknown format",
+            } => Self::UnknownFormat(value),
+            ValidationErrorKind::InvalidReference { reference } => {
+                Self::InvalidReference(reference.clone())
+            }
+            ValidationErrorKind::InvalidURL { .. }
+            | ValidationErrorKind::UnknownReferenceScheme { .. }
+            | ValidationErrorKind::Resolver { .. } => Self::InvalidReference(error.to_string()),
+            _ => Self::Other(error.to_string()),
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct Schema {
@@ -17,12 +76,234 @@ impl Schema {
         }
     }
 
+    /// Same as `compile`, but the reason of the failure is returned.
+    pub fn compile_detailed(schema: &Value) -> Result<Self, SchemaCompileError> {
+        match JSONSchema::compile(schema) {
+            Ok(json_schema) => Ok(Schema { json_schema }),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Compiles a schema whose `$ref`s may point to any of the documents of the
+    /// registry. References are resolved against the registry instead of being
+    /// fetched from their URL.
+    pub fn compile_with_registry(schema: &Value, registry: &SchemaRegistry) -> Result<Self, Error> {
+        let mut options = JSONSchema::options();
+        for (id, document) in registry.schemas.iter() {
+            options.with_document(id.clone(), document.clone());
+        }
+        match options.compile(schema) {
+            Ok(json_schema) => Ok(Schema { json_schema }),
+            Err(_) => Err(Error::SchemaCreationError),
+        }
+    }
+
+    /// Compiles a schema choosing how the `format` keyword is handled. With `assert`
+    /// set, a value that does not match its format (e.g. an invalid `uri`) fails the
+    /// validation. Otherwise `format` is only an annotation and never rejects a value,
+    /// as the `format-annotation` vocabulary of draft 2020-12 used by the governance
+    /// schemas specifies. `compile` keeps the default behaviour of the validator.
+    pub fn compile_with_format_assertion(schema: &Value, assert: bool) -> Result<Self, Error> {
+        match JSONSchema::options()
+            .should_validate_formats(assert)
+            .compile(schema)
+        {
+            Ok(json_schema) => Ok(Schema { json_schema }),
+            Err(_) => Err(Error::SchemaCreationError),
+        }
+    }
+
     pub fn validate(&self, value: &Value) -> bool {
         match self.json_schema.validate(value) {
             Ok(_) => true,
             Err(_) => false,
         }
     }
+
+    pub fn validate_with_errors(&self, value: &Value) -> Result<(), Vec<SchemaValidationError>> {
+        match self.json_schema.validate(value) {
+            Ok(_) => Ok(()),
+            Err(errors) => Err(errors
+                .map(|error| SchemaValidationError {
+                    instance_path: error.instance_path.to_string(),
+                    schema_path: error.schema_path.to_string(),
+                    message: error.to_string(),
+                })
+                .collect()),
+        }
+    }
+}
+
+/// The schema that the properties of every governance subject follow, compiled.
+/// It is a different type from [ContentSchema], so one can not be passed where the
+/// other is expected:
+///
+/// ```compile_fail
+/// use commons::schema_handler::{CompiledGovernanceSchema, ContentSchema};
+///
+/// fn validate_content(schema: &ContentSchema) {}
+/// validate_content(&CompiledGovernanceSchema::compile().unwrap());
+/// ```
+#[derive(Debug)]
+pub struct CompiledGovernanceSchema(Schema);
+
+impl CompiledGovernanceSchema {
+    pub fn compile() -> Result<Self, Error> {
+        Schema::compile(&get_governance_schema()).map(Self)
+    }
+
+    pub fn validate(&self, governance: &Value) -> bool {
+        self.0.validate(governance)
+    }
+
+    pub fn validate_with_errors(
+        &self,
+        governance: &Value,
+    ) -> Result<(), Vec<SchemaValidationError>> {
+        self.0.validate_with_errors(governance)
+    }
+}
+
+/// A schema defined by a governance for the properties of its subjects, compiled.
+#[derive(Debug)]
+pub struct ContentSchema(Schema);
+
+impl ContentSchema {
+    pub fn compile(schema: &Value) -> Result<Self, Error> {
+        Schema::compile(schema).map(Self)
+    }
+
+    pub fn validate(&self, properties: &Value) -> bool {
+        self.0.validate(properties)
+    }
+
+    pub fn validate_with_errors(
+        &self,
+        properties: &Value,
+    ) -> Result<(), Vec<SchemaValidationError>> {
+        self.0.validate_with_errors(properties)
+    }
+}
+
+/// Set of schemas, indexed by their `$id`, that other schemas can reference.
+#[derive(Debug, Clone, Default)]
+pub struct SchemaRegistry {
+    schemas: HashMap<String, Value>,
+}
+
+impl SchemaRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a schema under its `$id`, replacing any previous one with the same id.
+    pub fn register(&mut self, schema: Value) -> Result<(), Error> {
+        let Some(id) = schema.get("$id").and_then(Value::as_str) else {
+            return Err(Error::SchemaIdNotFound);
+        };
+        self.schemas.insert(id.to_owned(), schema);
+        Ok(())
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Value> {
+        self.schemas.get(id)
+    }
+}
+
+/// Digest of a JSON value. The value is serialized canonically, with the keys of
+/// the objects sorted and without whitespace, so equal documents have equal digests
+/// whatever the order of their keys.
+pub fn digest_of(value: &Value) -> Result<DigestIdentifier, Error> {
+    let mut canonical = String::new();
+    write_canonical(value, &mut canonical)?;
+    let bytes = DigestDerivator::Blake3_256.digest(canonical.as_bytes());
+    Ok(DigestIdentifier::new(DigestDerivator::Blake3_256, &bytes))
+}
+
+/// Keywords that only annotate a schema and never change what it accepts.
+const ANNOTATION_KEYWORDS: [&str; 5] =
+    ["$comment", "title", "description", "examples", "deprecated"];
+/// Keywords whose value maps names, which may be anything, to subschemas.
+const NAMED_SUBSCHEMA_KEYWORDS: [&str; 5] = [
+    "properties",
+    "patternProperties",
+    "$defs",
+    "definitions",
+    "dependentSchemas",
+];
+/// Keywords whose value is an instance rather than a schema.
+const INSTANCE_KEYWORDS: [&str; 3] = ["const", "enum", "default"];
+
+/// Copy of `schema` without its annotation keywords, so that schemas that only
+/// differ in them have the same digest. Properties named like an annotation
+/// keyword are kept. The schema itself must still be used to validate.
+pub fn normalize_schema(schema: &Value) -> Value {
+    match schema {
+        Value::Object(keywords) => Value::Object(
+            keywords
+                .iter()
+                .filter(|(keyword, _)| !ANNOTATION_KEYWORDS.contains(&keyword.as_str()))
+                .map(|(keyword, value)| {
+                    let value = match value {
+                        Value::Object(named)
+                            if NAMED_SUBSCHEMA_KEYWORDS.contains(&keyword.as_str()) =>
+                        {
+                            Value::Object(
+                                named
+                                    .iter()
+                                    .map(|(name, subschema)| {
+                                        (name.clone(), normalize_schema(subschema))
+                                    })
+                                    .collect(),
+                            )
+                        }
+                        _ if INSTANCE_KEYWORDS.contains(&keyword.as_str()) => value.clone(),
+                        _ => normalize_schema(value),
+                    };
+                    (keyword.clone(), value)
+                })
+                .collect(),
+        ),
+        Value::Array(subschemas) => Value::Array(subschemas.iter().map(normalize_schema).collect()),
+        value => value.clone(),
+    }
+}
+
+/// Digest of a schema that ignores its annotations, to identify cached schemas.
+pub fn schema_digest(schema: &Value) -> Result<DigestIdentifier, Error> {
+    digest_of(&normalize_schema(schema))
+}
+
+fn write_canonical(value: &Value, output: &mut String) -> Result<(), Error> {
+    match value {
+        Value::Array(items) => {
+            output.push('[');
+            for (index, item) in items.iter().enumerate() {
+                if index > 0 {
+                    output.push(',');
+                }
+                write_canonical(item, output)?;
+            }
+            output.push(']');
+        }
+        Value::Object(object) => {
+            // The order of the map depends on the features of serde_json
+            let mut entries: Vec<(&String, &Value)> = object.iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            output.push('{');
+            for (index, (key, item)) in entries.into_iter().enumerate() {
+                if index > 0 {
+                    output.push(',');
+                }
+                output.push_str(&serde_json::to_string(key)?);
+                output.push(':');
+                write_canonical(item, output)?;
+            }
+            output.push('}');
+        }
+        scalar => output.push_str(&serde_json::to_string(scalar)?),
+    }
+    Ok(())
 }
 
 pub fn get_governance_schema() -> Value {
@@ -688,3 +969,177 @@ pub fn get_governance_schema() -> Value {
       }
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::errors::Error;
+
+    use super::{
+        digest_of, normalize_schema, schema_digest, CompiledGovernanceSchema, ContentSchema,
+        Schema, SchemaCompileError, SchemaRegistry,
+    };
+
+    #[test]
+    fn test_compile_with_registry() {
+        let mut registry = SchemaRegistry::new();
+        registry
+            .register(json!({
+                "$id": "https://taple.es/schemas/common",
+                "$defs": {
+                    "positive": {"type": "integer", "minimum": 1}
+                }
+            }))
+            .unwrap();
+        assert!(matches!(
+            registry.register(json!({"type": "object"})),
+            Err(Error::SchemaIdNotFound)
+        ));
+        assert!(registry.get("https://taple.es/schemas/common").is_some());
+
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "quantity": {"$ref": "https://taple.es/schemas/common#/$defs/positive"}
+            },
+            "required": ["quantity"]
+        });
+        let compiled = Schema::compile_with_registry(&schema, &registry).unwrap();
+        assert!(compiled.validate(&json!({"quantity": 3})));
+        assert!(!compiled.validate(&json!({"quantity": 0})));
+        assert!(!compiled.validate(&json!({"quantity": "3"})));
+    }
+
+    #[test]
+    fn test_compile_detailed() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "code": {"type": "string", "pattern": "^[a-z"}
+            }
+        });
+        assert_eq!(
+            Err(SchemaCompileError::InvalidPattern("^[a-z".into())),
+            Schema::compile_detailed(&schema).map(|_| ())
+        );
+        assert!(matches!(
+            Schema::compile(&schema),
+            Err(Error::SchemaCreationError)
+        ));
+        assert!(matches!(
+            Schema::compile_detailed(&json!({"type": 1})),
+            Err(SchemaCompileError::Other(_))
+        ));
+        assert!(
+            Schema::compile_detailed(&json!({"type": "string", "pattern": "^[a-z]+$"})).is_ok()
+        );
+    }
+
+    #[test]
+    fn test_compile_with_format_assertion() {
+        let schema = json!({
+            "$schema": "https://json-schema.org/draft/2020-12/schema",
+            "type": "string",
+            "format": "uri"
+        });
+        let annotation = Schema::compile_with_format_assertion(&schema, false).unwrap();
+        let assertion = Schema::compile_with_format_assertion(&schema, true).unwrap();
+        assert!(annotation.validate(&json!("not a uri")));
+        assert!(!assertion.validate(&json!("not a uri")));
+        assert!(annotation.validate(&json!("https://taple.es")));
+        assert!(assertion.validate(&json!("https://taple.es")));
+    }
+
+    #[test]
+    fn test_governance_and_content_schemas() {
+        let governance = json!({
+            "members": [{"id": "Owner", "tags": {}, "key": "key"}],
+            "schemas": [],
+            "policies": []
+        });
+        let properties = json!({"counter": 1});
+
+        let governance_schema = CompiledGovernanceSchema::compile().unwrap();
+        assert!(governance_schema.validate(&governance));
+        assert!(!governance_schema.validate(&properties));
+        assert!(governance_schema.validate_with_errors(&properties).is_err());
+
+        let content_schema = ContentSchema::compile(&json!({
+            "type": "object",
+            "additionalProperties": false,
+            "properties": {"counter": {"type": "integer"}}
+        }))
+        .unwrap();
+        assert!(content_schema.validate(&properties));
+        assert!(!content_schema.validate(&governance));
+        assert_eq!(Ok(()), content_schema.validate_with_errors(&properties));
+        assert!(matches!(
+            ContentSchema::compile(&json!({"type": 1})),
+            Err(Error::SchemaCreationError)
+        ));
+    }
+
+    #[test]
+    fn test_normalize_schema() {
+        let schema = json!({
+            "$comment": "Counter of the subject",
+            "title": "Counter",
+            "type": "object",
+            "properties": {
+                "title": {"type": "string", "description": "Not an annotation"},
+                "value": {"type": "integer", "examples": [1, 2], "deprecated": false}
+            },
+            "allOf": [{"$comment": "Nested", "required": ["value"]}],
+            "default": {"title": "kept", "value": 0}
+        });
+        let commented = json!({
+            "$comment": "Another comment",
+            "type": "object",
+            "properties": {
+                "title": {"type": "string"},
+                "value": {"type": "integer", "$comment": "Also stripped"}
+            },
+            "allOf": [{"required": ["value"]}],
+            "default": {"title": "kept", "value": 0}
+        });
+        assert_eq!(
+            json!({
+                "type": "object",
+                "properties": {
+                    "title": {"type": "string"},
+                    "value": {"type": "integer"}
+                },
+                "allOf": [{"required": ["value"]}],
+                "default": {"title": "kept", "value": 0}
+            }),
+            normalize_schema(&schema)
+        );
+        assert_ne!(digest_of(&schema).unwrap(), digest_of(&commented).unwrap());
+        assert_eq!(
+            schema_digest(&schema).unwrap(),
+            schema_digest(&commented).unwrap()
+        );
+        let stricter = json!({"type": "object", "required": ["value"]});
+        assert_ne!(
+            schema_digest(&schema).unwrap(),
+            schema_digest(&stricter).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_digest_of() {
+        let value = json!({"a": 1, "b": {"c": [1, "two", null], "d": true}});
+        let reordered: serde_json::Value =
+            serde_json::from_str(r#"{ "b": { "d": true, "c": [1, "two", null] }, "a": 1 }"#)
+                .unwrap();
+        assert_eq!(digest_of(&value).unwrap(), digest_of(&reordered).unwrap());
+        let different = json!({"a": 1, "b": {"c": [1, "two", null], "d": false}});
+        assert_ne!(digest_of(&value).unwrap(), digest_of(&different).unwrap());
+        // Array order is significant
+        assert_ne!(
+            digest_of(&json!([1, 2])).unwrap(),
+            digest_of(&json!([2, 1])).unwrap()
+        );
+    }
+}
@@ -27,4 +27,6 @@ pub enum Error {
     InvalidHexString,
     #[error("Node has previously executed with a different KeyPair. Please, specify the same KeyPair as before. Current ControllerID {0}")]
     InvalidKeyPairSpecified(String),
+    #[error("Database error: {0}")]
+    DatabaseError(String),
 }
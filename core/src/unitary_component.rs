@@ -147,7 +147,7 @@ impl Taple {
 
     /// This method allows the creation of cryptographic material through a
     /// given public key.
-    fn generate_mc(&mut self, stored_public_key: Option<String>) -> Result<KeyPair, Error> {
+    fn generate_mc(&mut self, stored_public_key: Option<KeyIdentifier>) -> Result<KeyPair, Error> {
         let kp = Taple::create_key_pair(
             &self.settings.node.key_derivator,
             self.settings.node.seed.clone(),
@@ -156,7 +156,7 @@ impl Taple {
         let public_key = kp.public_key_bytes();
         let key_identifier = KeyIdentifier::new(kp.get_key_derivator(), &public_key).to_str();
         if let Some(key) = stored_public_key {
-            if (key_identifier != key) && !self.settings.node.dev_mode {
+            if (key_identifier != key.to_str()) && !self.settings.node.dev_mode {
                 log::error!("Invalid MC specified. There is a previous defined MC in the system");
                 return Err(Error::InvalidKeyPairSpecified(key_identifier));
             }
@@ -315,12 +315,16 @@ impl Taple {
         let db = open_db(path);
         let db_access = DB::new(db.clone());
         // Creation of cryptographic material
-        let stored_public_key = db_access.get_controller_id();
+        let stored_public_key = db_access
+            .get_controller_id()
+            .map_err(|error| Error::DatabaseError(error.to_string()))?;
         let kp = self.generate_mc(stored_public_key)?;
-        // Store controller_id in database
-        db_access.set_controller_id(self.controller_id().unwrap());
         let public_key = kp.public_key_bytes();
         let key_identifier = KeyIdentifier::new(kp.get_key_derivator(), &public_key);
+        // Store controller_id in database
+        db_access
+            .set_controller_id(&key_identifier)
+            .map_err(|error| Error::DatabaseError(error.to_string()))?;
         // Creation Network
         let network_manager = NetworkProcessor::new(
             Some(format!(
@@ -1,3 +1,4 @@
+use commons::schema_handler::SchemaValidationError;
 use thiserror::Error;
 
 #[derive(Error, Debug, Clone, PartialEq)]
@@ -20,6 +21,8 @@ pub enum RequestError {
     InvalidRequestType,
     #[error("Schema Not Found in policies")]
     SchemaNotFoundInPolicies,
+    #[error("Schema Validation Failed: {0:?}")]
+    SchemaValidationFailed(Vec<SchemaValidationError>),
 }
 
 #[derive(Error, Debug)]
@@ -36,3 +39,18 @@ pub enum InternalError {
     #[error("Invalid KeyIdentifier")]
     InvalidGovernancePayload,
 }
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum QuorumError {
+    #[error("Governance without policies")]
+    PoliciesNotFound,
+    #[error("Policy {0} is not well formed")]
+    InvalidPolicy(String),
+    #[error("Policy {policy_id}: {stage} quorum {quorum} can not be reached by {participants} participants")]
+    Unreachable {
+        policy_id: String,
+        stage: String,
+        quorum: f64,
+        participants: usize,
+    },
+}
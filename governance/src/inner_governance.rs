@@ -13,7 +13,8 @@ use commons::{
 use serde_json::Value;
 
 use crate::{
-    error::{InternalError, RequestError},
+    error::{InternalError, QuorumError, RequestError},
+    schema::GovernanceSchemas,
     RequestQuorum,
 };
 use commons::models::event_request::EventRequestType::State;
@@ -67,6 +68,23 @@ impl InnerGovernance {
         }
     }
 
+    /// Compiles all the content schemas of the governance at once, so they can be
+    /// reused to validate every request made against it.
+    pub fn get_governance_schemas(
+        &self,
+        governance_id: &DigestIdentifier,
+    ) -> Result<Result<GovernanceSchemas, RequestError>, InternalError> {
+        let Some(governance) = self.repo_access.get_subject(governance_id) else {
+            return Ok(Err(RequestError::GovernanceNotFound));
+        };
+        let Some(subject_data) = governance.subject_data else {
+            return Ok(Err(RequestError::GovernanceNotFound));
+        };
+        let properties: Value = serde_json::from_str(&subject_data.properties)
+            .map_err(|_| InternalError::DeserializationError)?;
+        GovernanceSchemas::new(&properties)
+    }
+
     pub fn get_validators(
         &self,
         event: Event,
@@ -436,14 +454,89 @@ impl InnerGovernance {
     }
 }
 
-fn get_as_str<'a>(data: &'a Value, key: &str) -> Result<&'a str, InternalError> {
+/// Checks that the quorums of every policy of a governance can be reached by its
+/// validators and approvers. Validation is always required, while approval is
+/// only required if some invokation rule allows requests that need approval.
+/// One error is returned per offending policy.
+pub fn check_quorum_feasibility(properties: &Value) -> Result<(), Vec<QuorumError>> {
+    let Some(policies) = properties.get("policies").and_then(Value::as_array) else {
+        return Err(vec![QuorumError::PoliciesNotFound]);
+    };
+    let errors: Vec<QuorumError> = policies
+        .iter()
+        .filter_map(|policy| check_policy_quorums(policy).err())
+        .collect();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+fn check_policy_quorums(policy: &Value) -> Result<(), QuorumError> {
+    let policy_id = policy
+        .get("id")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_owned();
+    let invokation = policy
+        .get("invokation")
+        .ok_or_else(|| QuorumError::InvalidPolicy(policy_id.clone()))?;
+    let mut approval_gated = false;
+    for rule in ["owner", "set", "all", "external"] {
+        let (allowance, approval_required) = invokation
+            .get(rule)
+            .ok_or(InternalError::InvalidGovernancePayload)
+            .and_then(extract_allowance_and_approval_required)
+            .map_err(|_| QuorumError::InvalidPolicy(policy_id.clone()))?;
+        approval_gated |= allowance && approval_required;
+    }
+    check_stage_quorum(policy, &policy_id, "validation", "validators", true)?;
+    check_stage_quorum(policy, &policy_id, "approval", "approvers", approval_gated)
+}
+
+fn check_stage_quorum(
+    policy: &Value,
+    policy_id: &str,
+    stage: &str,
+    participants_key: &str,
+    gated: bool,
+) -> Result<(), QuorumError> {
+    let invalid_policy = |_| QuorumError::InvalidPolicy(policy_id.to_owned());
+    let quorum = get_quorum(policy, stage).map_err(invalid_policy)?;
+    let stage_rules = policy
+        .get(stage)
+        .ok_or(InternalError::InvalidGovernancePayload)
+        .map_err(invalid_policy)?;
+    let participants: HashSet<&str> = get_as_array(stage_rules, participants_key)
+        .map_err(invalid_policy)?
+        .iter()
+        .filter_map(Value::as_str)
+        .collect();
+    let required = (quorum * participants.len() as f64).ceil();
+    let unreachable = (gated && required < 1.0)
+        || (quorum > 0.0 && participants.is_empty())
+        || required > participants.len() as f64;
+    if unreachable {
+        Err(QuorumError::Unreachable {
+            policy_id: policy_id.to_owned(),
+            stage: stage.to_owned(),
+            quorum,
+            participants: participants.len(),
+        })
+    } else {
+        Ok(())
+    }
+}
+
+pub(crate) fn get_as_str<'a>(data: &'a Value, key: &str) -> Result<&'a str, InternalError> {
     data.get(key)
         .ok_or(InternalError::InvalidGovernancePayload)?
         .as_str()
         .ok_or(InternalError::InvalidGovernancePayload)
 }
 
-fn get_as_array<'a>(data: &'a Value, key: &str) -> Result<&'a Vec<Value>, InternalError> {
+pub(crate) fn get_as_array<'a>(data: &'a Value, key: &str) -> Result<&'a Vec<Value>, InternalError> {
     data.get(key)
         .ok_or(InternalError::InvalidGovernancePayload)?
         .as_array()
@@ -560,3 +653,95 @@ fn get_members_from_governance(properties: &Value) -> Result<HashSet<String>, In
     }
     Ok(member_ids)
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use crate::error::QuorumError;
+
+    use super::check_quorum_feasibility;
+
+    fn get_policy(id: &str, validation: Value, approval: Value, approval_required: bool) -> Value {
+        json!({
+            "id": id,
+            "validation": validation,
+            "approval": approval,
+            "invokation": {
+                "owner": {"allowance": true, "approvalRequired": approval_required},
+                "set": {"allowance": false, "approvalRequired": false, "invokers": []},
+                "all": {"allowance": false, "approvalRequired": false},
+                "external": {"allowance": false, "approvalRequired": false}
+            }
+        })
+    }
+
+    #[test]
+    fn test_quorum_feasible() {
+        let properties = json!({
+            "policies": [
+                get_policy(
+                    "governance",
+                    json!({"quorum": 0.5, "validators": ["A", "B", "C"]}),
+                    json!({"quorum": 1.0, "approvers": ["A"]}),
+                    true
+                ),
+                // Approval is never required, so nobody has to approve
+                get_policy(
+                    "schema",
+                    json!({"quorum": 1.0, "validators": ["A"]}),
+                    json!({"quorum": 0.0, "approvers": []}),
+                    false
+                )
+            ]
+        });
+        assert_eq!(Ok(()), check_quorum_feasibility(&properties));
+    }
+
+    #[test]
+    fn test_quorum_unreachable() {
+        let properties = json!({
+            "policies": [
+                get_policy(
+                    "empty_validators",
+                    json!({"quorum": 1.0, "validators": []}),
+                    json!({"quorum": 0.5, "approvers": ["A"]}),
+                    true
+                ),
+                get_policy(
+                    "zero_approval_quorum",
+                    json!({"quorum": 0.5, "validators": ["A"]}),
+                    json!({"quorum": 0.0, "approvers": ["A"]}),
+                    true
+                ),
+                get_policy(
+                    "feasible",
+                    json!({"quorum": 0.5, "validators": ["A"]}),
+                    json!({"quorum": 0.5, "approvers": ["A"]}),
+                    true
+                )
+            ]
+        });
+        assert_eq!(
+            Err(vec![
+                QuorumError::Unreachable {
+                    policy_id: "empty_validators".into(),
+                    stage: "validation".into(),
+                    quorum: 1.0,
+                    participants: 0,
+                },
+                QuorumError::Unreachable {
+                    policy_id: "zero_approval_quorum".into(),
+                    stage: "approval".into(),
+                    quorum: 0.0,
+                    participants: 1,
+                }
+            ]),
+            check_quorum_feasibility(&properties)
+        );
+        assert_eq!(
+            Err(vec![QuorumError::PoliciesNotFound]),
+            check_quorum_feasibility(&json!({}))
+        );
+    }
+}
@@ -1,4 +1,12 @@
+use std::{collections::HashMap, sync::Arc};
+
 use commons::schema_handler::{get_governance_schema, Schema};
+use serde_json::Value;
+
+use crate::{
+    error::{InternalError, RequestError},
+    inner_governance::{get_as_array, get_as_str},
+};
 
 pub fn get_schema_json_schema() -> Schema {
     let schema = get_governance_schema();
@@ -12,10 +20,99 @@ pub fn get_schema_json_schema() -> Schema {
     }
 }
 
+/// Content schemas of a governance, compiled once and indexed by their id.
+#[derive(Debug, Clone, Default)]
+pub struct GovernanceSchemas {
+    schemas: HashMap<String, Arc<Schema>>,
+}
+
+impl GovernanceSchemas {
+    /// Compiles every schema declared in the `schemas` of the governance properties.
+    pub fn new(properties: &Value) -> Result<Result<Self, RequestError>, InternalError> {
+        let mut schemas = HashMap::new();
+        for schema in get_as_array(properties, "schemas")? {
+            let id = get_as_str(schema, "id")?;
+            let content = schema
+                .get("content")
+                .ok_or(InternalError::InvalidGovernancePayload)?;
+            let Ok(compiled) = Schema::compile(content) else {
+                return Ok(Err(RequestError::JSONCompileError));
+            };
+            schemas.insert(id.to_owned(), Arc::new(compiled));
+        }
+        Ok(Ok(Self { schemas }))
+    }
+
+    pub fn get(&self, schema_id: &str) -> Option<Arc<Schema>> {
+        self.schemas.get(schema_id).cloned()
+    }
+
+    /// Validates `value` against the schema with id `schema_id`.
+    pub fn validate(&self, schema_id: &str, value: &Value) -> Result<(), RequestError> {
+        let schema = self
+            .schemas
+            .get(schema_id)
+            .ok_or(RequestError::SchemaNotFound)?;
+        schema
+            .validate_with_errors(value)
+            .map_err(RequestError::SchemaValidationFailed)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use commons::schema_handler::Schema;
     use serde_json::json;
+
+    use crate::error::RequestError;
+
+    use super::GovernanceSchemas;
+
+    #[test]
+    fn test_governance_schemas() {
+        let properties = json!({
+            "members": [],
+            "schemas": [
+                {
+                    "id": "Person",
+                    "tags": {},
+                    "content": {
+                        "type": "object",
+                        "properties": {"name": {"type": "string"}},
+                        "required": ["name"]
+                    }
+                },
+                {
+                    "id": "Counter",
+                    "tags": {},
+                    "content": {"type": "integer", "minimum": 0}
+                }
+            ]
+        });
+        let schemas = GovernanceSchemas::new(&properties).unwrap().unwrap();
+        assert_eq!(
+            Ok(()),
+            schemas.validate("Person", &json!({"name": "Alice"}))
+        );
+        assert!(matches!(
+            schemas.validate("Person", &json!({"age": 1})),
+            Err(RequestError::SchemaValidationFailed(errors)) if errors.len() == 1
+        ));
+        assert_eq!(Ok(()), schemas.validate("Counter", &json!(3)));
+        assert!(schemas.validate("Counter", &json!(-3)).is_err());
+        assert_eq!(
+            Err(RequestError::SchemaNotFound),
+            schemas.validate("Unknown", &json!(3))
+        );
+
+        let properties = json!({
+            "schemas": [{"id": "Broken", "tags": {}, "content": {"type": 1}}]
+        });
+        assert_eq!(
+            Some(RequestError::JSONCompileError),
+            GovernanceSchemas::new(&properties).unwrap().err()
+        );
+    }
     
     #[test]
     fn test_meta_schema() {
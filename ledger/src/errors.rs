@@ -1,5 +1,5 @@
 use commons::{
-    errors::{CryptoErrorEvent, SubjectError},
+    errors::{CryptoErrorEvent, DbError, SubjectError},
     models::state::LedgerState,
 };
 use governance::error::RequestError;
@@ -45,6 +45,17 @@ pub enum LedgerManagerError {
     SubjectError(SubjectError),
     #[error("Error with Governance")]
     GovernanceError(RequestError),
+    #[error("Database Error: {0}")]
+    DatabaseError(String),
+}
+
+impl From<DbError> for LedgerManagerError {
+    fn from(error: DbError) -> Self {
+        match error {
+            DbError::SubjectError(error) => LedgerManagerError::SubjectError(error),
+            error => LedgerManagerError::DatabaseError(error.to_string()),
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
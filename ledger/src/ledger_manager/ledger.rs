@@ -6,13 +6,14 @@ use commons::{
     identifier::{DigestIdentifier, KeyIdentifier},
     models::{
         event::Event,
-        event_content::EventContent,
+        event_content::{EventContent, Metadata},
         event_request::{EventRequest, EventRequestType},
+        governance::Governance,
         signature::Signature,
         state::{LedgerState, Subject, SubjectData},
     },
 };
-use governance::{GovernanceAPI, GovernanceInterface};
+use governance::{error::RequestError, GovernanceAPI, GovernanceInterface};
 use serde_json::Value;
 
 use crate::errors::{CryptoError, LedgerManagerError};
@@ -70,28 +71,81 @@ impl Ledger {
         }
     }
 
+    /// Checks the metadata of a new event against its governance, read from the
+    /// database, whose current version is the SN of the governance subject. The
+    /// metadata of governance events is not checked.
+    fn check_metadata(&self, metadata: &Metadata) -> Result<(), LedgerManagerError> {
+        if metadata.governance_id.digest.is_empty() {
+            return Ok(());
+        }
+        let (governance, governance_version) = self.load_governance(&metadata.governance_id)?;
+        metadata
+            .validate(&governance, governance_version)
+            .map_err(LedgerManagerError::SubjectError)
+    }
+
+    /// Reads a governance from the database with its current version, the SN of
+    /// the governance subject.
+    fn load_governance(
+        &self,
+        governance_id: &DigestIdentifier,
+    ) -> Result<(Governance, u64), LedgerManagerError> {
+        let subject_data = match self.repo_access.get_subject(governance_id) {
+            Some(Subject {
+                subject_data: Some(subject_data),
+                ..
+            }) => subject_data,
+            _ => {
+                return Err(LedgerManagerError::GovernanceError(
+                    RequestError::GovernanceNotFound,
+                ))
+            }
+        };
+        let governance = Governance::from_properties(&subject_data.properties)
+            .map_err(LedgerManagerError::SubjectError)?;
+        Ok((governance, subject_data.sn))
+    }
+
+    /// Governance that rules `subject`, which is the subject itself for governances.
+    fn governance_of(&self, subject: &Subject) -> Result<Governance, LedgerManagerError> {
+        let Some(subject_data) = subject.subject_data.as_ref() else {
+            return Err(LedgerManagerError::SubjectError(
+                SubjectError::SubjectHasNoData,
+            ));
+        };
+        if subject.is_governance() {
+            return Governance::from_properties(&subject_data.properties)
+                .map_err(LedgerManagerError::SubjectError);
+        }
+        self.load_governance(&subject_data.governance_id)
+            .map(|(governance, _)| governance)
+    }
+
     pub fn genesis_event(
         &mut self,
         event_request: EventRequest,
         governance_version: u64,
         subject_schema: &Value,
         approved: bool,
-    ) -> Result<CommandManagerResponse, SubjectError> {
+    ) -> Result<CommandManagerResponse, LedgerManagerError> {
         if self.id != event_request.signature.content.signer {
-            return Err(SubjectError::NotOwnerOfSubject);
+            return Err(LedgerManagerError::SubjectError(
+                SubjectError::NotOwnerOfSubject,
+            ));
         }
         // TODO: Here I always accept genesis events
         let res =
             event_request.create_subject_from_request(governance_version, subject_schema, approved);
         if res.is_err() {
             log::error!("ERROR: {:?}", res);
-            return Err(res.unwrap_err());
+            return Err(LedgerManagerError::SubjectError(res.unwrap_err()));
         }
         let (subject, event) = res.unwrap();
+        self.check_metadata(&event.event_content.metadata)?;
         let subject_id = event.event_content.subject_id.clone();
-        self.repo_access.set_event(&subject_id, event.clone());
+        self.repo_access.set_event(&subject_id, event.clone())?;
         let ledger_state = subject.ledger_state.clone();
-        self.repo_access.set_subject(&subject_id, subject);
+        self.repo_access.set_subject(&subject_id, subject)?;
         self.ledger_state.insert(subject_id, ledger_state.clone());
         Ok(CommandManagerResponse::CreateEventResponse(
             event,
@@ -106,35 +160,46 @@ impl Ledger {
         mut subject: Subject,
         subject_schema: &Value,
         approved: bool,
-    ) -> Result<CommandManagerResponse, SubjectError> {
-        let subject_id = if let EventRequestType::State(state_req) = event_request.request.clone() {
-            state_req.subject_id
-        } else {
-            return Err(SubjectError::NotStateEvent);
+    ) -> Result<CommandManagerResponse, LedgerManagerError> {
+        let subject_id = match event_request.request.as_state() {
+            Some(state_req) => state_req.subject_id.clone(),
+            None => {
+                return Err(LedgerManagerError::SubjectError(
+                    SubjectError::NotStateEvent,
+                ))
+            }
         };
         if subject.subject_data.is_none() {
-            return Err(SubjectError::SubjectHasNoData);
+            return Err(LedgerManagerError::SubjectError(
+                SubjectError::SubjectHasNoData,
+            ));
         } else if subject.ledger_state.negociating_next {
-            return Err(SubjectError::EventAlreadyProcessing);
+            return Err(LedgerManagerError::SubjectError(
+                SubjectError::EventAlreadyProcessing,
+            ));
         }
-        let prev_event_hash = match self
+        let (sn, prev_event_hash) = self
             .repo_access
-            .get_event(&subject_id, subject.subject_data.as_ref().unwrap().sn)
-        {
-            Some(event) => event.signature.content.event_content_hash,
-            None => return Err(SubjectError::EventAlreadyAppliedNotFound),
-        };
-        let event = event_request.get_event_from_state_request(
-            &subject,
-            prev_event_hash,
-            governance_version,
-            subject_schema,
-            approved,
-        )?;
-        self.repo_access.set_event(&subject_id, event.clone());
+            .next_event_context(&subject_id)
+            .map_err(LedgerManagerError::SubjectError)?;
+        let governance = self.governance_of(&subject)?;
+        let event = event_request
+            .get_event_from_state_request(
+                &subject,
+                sn,
+                prev_event_hash,
+                &governance,
+                governance_version,
+                subject_schema,
+                approved,
+            )
+            .map_err(LedgerManagerError::SubjectError)?;
+        self.check_metadata(&event.event_content.metadata)?;
+        self.repo_access.set_event(&subject_id, event.clone())?;
         self.repo_access.set_negociating_true(&subject_id)?;
         subject.ledger_state.negociating_next = true;
-        self.set_negociating_true(&subject_id)?;
+        self.set_negociating_true(&subject_id)
+            .map_err(LedgerManagerError::SubjectError)?;
         Ok(CommandManagerResponse::CreateEventResponse(
             event,
             subject.ledger_state,
@@ -159,12 +224,12 @@ impl Ledger {
         let mut prev_hash = event.signature.content.event_content_hash.clone();
         let event_sourcing = self.repo_access.apply_event_sourcing(event.event_content);
         if let Err(e) = event_sourcing {
-            return Err(LedgerManagerError::SubjectError(e));
+            return Err(e.into());
         }
         if ledger_state.head_candidate_sn.is_some() {
             for sn in (ev_sn + 1)..=(ledger_state.head_candidate_sn.unwrap()) {
                 // Check for next event (if there is no more we stop and modify head)
-                if let Some(ev) = self.repo_access.get_event(&subject_id, sn) {
+                if let Some(ev) = self.repo_access.get_event(&subject_id, sn)? {
                     // Check that it engages with the preloader
                     if prev_hash != ev.event_content.previous_hash {
                         return Err(LedgerManagerError::CryptoError(CryptoError::Conflict));
@@ -179,7 +244,7 @@ impl Ledger {
                     prev_hash = ev.signature.content.event_content_hash.clone();
                     let event_sourcing = self.repo_access.apply_event_sourcing(ev.event_content);
                     if let Err(e) = event_sourcing {
-                        return Err(LedgerManagerError::SubjectError(e));
+                        return Err(e.into());
                     }
                 } else {
                     // There are no more events and the end is not reached
@@ -221,7 +286,8 @@ impl Ledger {
                 && ledger_state.unwrap().head_sn.unwrap() == sn)
         {
             // Case: signatures for candidate or for head (same protocol)
-            self.repo_access.set_signatures(&subject_id, sn, signatures);
+            self.repo_access
+                .set_signatures(&subject_id, sn, signatures)?;
             Ok(self.get_ledger_state(&subject_id).unwrap().to_owned())
         } else if ledger_state.is_some()
             && ledger_state.unwrap().head_sn.is_some()
@@ -255,7 +321,8 @@ impl Ledger {
             None => return Err(LedgerManagerError::SubjectNotFound),
             Some(candidate_list) => {
                 if let Some(ev) = candidate_list.get(&sn) {
-                    self.repo_access.set_signatures(&subject_id, sn, signatures);
+                    self.repo_access
+                        .set_signatures(&subject_id, sn, signatures)?;
                     if quorum {
                         match self.get_ledger_state(&subject_id) {
                             None => {
@@ -267,8 +334,8 @@ impl Ledger {
                                 };
                                 // Update ledger state, add event and add subject in database
                                 let subject = Subject::new_empty(new_ledger_state.clone());
-                                self.repo_access.set_subject(&subject_id, subject);
-                                self.repo_access.set_event(&subject_id, ev.clone());
+                                self.repo_access.set_subject(&subject_id, subject)?;
+                                self.repo_access.set_event(&subject_id, ev.clone())?;
                                 self.ledger_state
                                     .insert(subject_id, new_ledger_state.clone());
                                 Ok(new_ledger_state)
@@ -277,7 +344,7 @@ impl Ledger {
                                 // If there is a previous subject (it is an older candidate or a new one)
                                 let mut ledger_state = ledger_state.to_owned();
                                 ledger_state.head_candidate_sn = Some(sn);
-                                self.repo_access.set_event(&subject_id, ev.clone());
+                                self.repo_access.set_event(&subject_id, ev.clone())?;
                                 self.ledger_state.insert(subject_id, ledger_state.clone());
                                 Ok(ledger_state)
                             }
@@ -305,11 +372,11 @@ impl Ledger {
     ) -> Result<LedgerState, LedgerManagerError> {
         let ledger_state = self.get_ledger_state(&subject_id).unwrap().to_owned();
         self.repo_access
-            .set_signatures(&subject_id, sn, signatures.clone());
+            .set_signatures(&subject_id, sn, signatures.clone())?;
         if quorum {
             // Change ledger_state and event sourcing
             let event = self
-                .get_event_from_db(&subject_id, sn)
+                .get_event_from_db(&subject_id, sn)?
                 .expect("Tiene que haber evento");
             match self.apply_event_sourcing(event, subject_schema) {
                 Ok(ledger_state) => Ok(ledger_state),
@@ -366,8 +433,12 @@ impl Ledger {
         }
     }
 
-    pub fn get_event_from_db(&self, subject_id: &DigestIdentifier, sn: u64) -> Option<Event> {
-        self.repo_access.get_event(subject_id, sn)
+    pub fn get_event_from_db(
+        &self,
+        subject_id: &DigestIdentifier,
+        sn: u64,
+    ) -> Result<Option<Event>, LedgerManagerError> {
+        Ok(self.repo_access.get_event(subject_id, sn)?)
     }
 
     pub fn get_signatures_from_db(
@@ -389,7 +460,7 @@ impl Ledger {
                     EventSN::SN(num) => {
                         if num <= head_sn {
                             Ok(CommandManagerResponse::GetEventResponse {
-                                event: self.repo_access.get_event(subject_id, num).unwrap(),
+                                event: self.repo_access.get_event(subject_id, num)?.unwrap(),
                                 ledger_state: self.get_ledger_state(subject_id).unwrap().to_owned(),
                             })
                         } else if self.get_ledger_state(subject_id).unwrap().negociating_next
@@ -397,7 +468,7 @@ impl Ledger {
                         {
                             // Case head + 1
                             Ok(CommandManagerResponse::GetEventResponse {
-                                event: self.repo_access.get_event(subject_id, num).unwrap(),
+                                event: self.repo_access.get_event(subject_id, num)?.unwrap(),
                                 ledger_state: self.get_ledger_state(subject_id).unwrap().to_owned(),
                             })
                         } else if self
@@ -413,7 +484,7 @@ impl Ledger {
                                 == num
                         {
                             Ok(CommandManagerResponse::GetEventResponse {
-                                event: self.repo_access.get_event(subject_id, num).unwrap(),
+                                event: self.repo_access.get_event(subject_id, num)?.unwrap(),
                                 ledger_state: self.get_ledger_state(subject_id).unwrap().to_owned(),
                             })
                         } else {
@@ -423,7 +494,7 @@ impl Ledger {
                         }
                     }
                     EventSN::HEAD => Ok(CommandManagerResponse::GetEventResponse {
-                        event: self.repo_access.get_event(subject_id, head_sn).unwrap(),
+                        event: self.repo_access.get_event(subject_id, head_sn)?.unwrap(),
                         ledger_state: self.get_ledger_state(subject_id).unwrap().to_owned(),
                     }),
                 },
@@ -562,9 +633,9 @@ impl Ledger {
             Err(e) => return Err(LedgerManagerError::SubjectError(e)),
         };
         self.repo_access
-            .set_event(&event.event_content.subject_id, event.clone());
+            .set_event(&event.event_content.subject_id, event.clone())?;
         self.repo_access
-            .set_subject(&event.event_content.subject_id, subject);
+            .set_subject(&event.event_content.subject_id, subject)?;
         let mut ledger_state = LedgerState {
             head_sn: Some(0),
             head_candidate_sn: if let Some(ledger_s) =
@@ -581,7 +652,7 @@ impl Ledger {
         if ledger_state.head_candidate_sn.is_some() {
             for sn in 1..=(ledger_state.head_candidate_sn.unwrap()) {
                 // Check for next event (if there is no more we stop and modify head)
-                if let Some(ev) = self.repo_access.get_event(&subject_id, sn) {
+                if let Some(ev) = self.repo_access.get_event(&subject_id, sn)? {
                     // Check that it engages with the prev
                     if prev_hash != ev.event_content.previous_hash {
                         return Err(LedgerManagerError::CryptoError(CryptoError::Conflict));
@@ -596,7 +667,7 @@ impl Ledger {
                     prev_hash = ev.signature.content.event_content_hash.clone();
                     let event_sourcing = self.repo_access.apply_event_sourcing(ev.event_content);
                     if let Err(e) = event_sourcing {
-                        return Err(LedgerManagerError::SubjectError(e));
+                        return Err(e.into());
                     }
                 } else {
                     // No more events and no end is reached
@@ -647,7 +718,7 @@ impl Ledger {
         let subject_id = event.event_content.subject_id.clone();
         let prev_event = self
             .repo_access
-            .get_event(&event.event_content.subject_id, event.event_content.sn - 1)
+            .get_event(&event.event_content.subject_id, event.event_content.sn - 1)?
             .expect("Tiene que haber evento anterior");
         if prev_event.signature.content.event_content_hash != event.event_content.previous_hash {
             return Err(LedgerManagerError::CryptoError(CryptoError::Conflict));
@@ -659,7 +730,7 @@ impl Ledger {
         }
         // Add event
         self.repo_access
-            .set_event(&event.event_content.subject_id, event.clone());
+            .set_event(&event.event_content.subject_id, event.clone())?;
         // Check if there is a candidate and if it is reached
         let mut ledger_state = self
             .ledger_state
@@ -676,7 +747,7 @@ impl Ledger {
         } else {
             match self.repo_access.set_negociating_true(&subject_id) {
                 Ok(_) => (),
-                Err(e) => return Err(LedgerManagerError::SubjectError(e)),
+                Err(e) => return Err(e.into()),
             };
             ledger_state.negociating_next = true;
             Ok(CommandManagerResponse::PutEventResponse {
@@ -762,7 +833,7 @@ impl Ledger {
     ) -> Result<CommandManagerResponse, LedgerManagerError> {
         // Check ledger state
         let ledger_state = self.get_ledger_state(&subject_id);
-        let mut event = self.get_event_from_db(&subject_id, sn);
+        let mut event = self.get_event_from_db(&subject_id, sn)?;
         if event.is_none() {
             match self.get_event_from_candidate_cache(&subject_id, sn) {
                 Some(event_cach) => event = Some(event_cach.to_owned()),
@@ -877,7 +948,7 @@ impl Ledger {
                     approved,
                 ) {
                     Ok(create_event_response) => Ok(create_event_response),
-                    Err(e) => Err(LedgerManagerError::SubjectError(e)),
+                    Err(e) => Err(e),
                 }
             }
             EventRequestType::State(state_request) => {
@@ -909,7 +980,7 @@ impl Ledger {
                         approved,
                     ) {
                         Ok(create_event_response) => Ok(create_event_response),
-                        Err(e) => Err(LedgerManagerError::SubjectError(e)),
+                        Err(e) => Err(e),
                     }
                 } else {
                     Err(LedgerManagerError::SubjectError(
@@ -937,13 +1008,10 @@ impl Ledger {
         &self,
         namespace: &str,
     ) -> Result<CommandManagerResponse, LedgerManagerError> {
-        let subjects = self.get_all_subjects();
-        let subjects = subjects
+        let subjects = self
+            .repo_access
+            .get_subjects_by_namespace(namespace)
             .into_iter()
-            .filter(|subject| {
-                subject.subject_data.is_some()
-                    && subject.subject_data.as_ref().unwrap().namespace == namespace
-            })
             .map(|subject| subject.subject_data.unwrap())
             .collect::<Vec<SubjectData>>();
         Ok(CommandManagerResponse::GetSubjectsResponse { subjects })
@@ -40,4 +40,12 @@ pub enum Error {
     InvalidIdentifier,
     #[error("Cant send message. Channel closed")]
     ChannelClosed,
+    #[error("Unknown message format. JSON: {json}. CBOR: {cbor}. MessagePack: {msgpack}")]
+    UnknownFormat {
+        json: String,
+        cbor: String,
+        msgpack: String,
+    },
+    #[error("Protocol version {got} is not compatible with version {expected}")]
+    IncompatibleProtocolVersion { expected: u32, got: u32 },
 }
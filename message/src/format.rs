@@ -0,0 +1,76 @@
+use serde::de::DeserializeOwned;
+
+use crate::error::Error;
+
+/// Codecs a message may have been serialized with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageFormat {
+    Json,
+    Cbor,
+    MsgPack,
+}
+
+/// Deserializes bytes whose codec is unknown, as sent by peers that do not wrap
+/// their messages. JSON, CBOR and MessagePack are tried in that order and the
+/// first one that succeeds is returned along with the value.
+pub fn try_deserialize_any<T: DeserializeOwned>(bytes: &[u8]) -> Result<(T, MessageFormat), Error> {
+    let json_error = match serde_json::from_slice(bytes) {
+        Ok(value) => return Ok((value, MessageFormat::Json)),
+        Err(error) => error,
+    };
+    let cbor_error = match serde_cbor::from_slice(bytes) {
+        Ok(value) => return Ok((value, MessageFormat::Cbor)),
+        Err(error) => error,
+    };
+    match rmp_serde::from_slice(bytes) {
+        Ok(value) => Ok((value, MessageFormat::MsgPack)),
+        Err(msgpack_error) => Err(Error::UnknownFormat {
+            json: json_error.to_string(),
+            cbor: cbor_error.to_string(),
+            msgpack: msgpack_error.to_string(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use crate::error::Error;
+
+    use super::{try_deserialize_any, MessageFormat};
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Payload {
+        id: String,
+        sn: u64,
+        tags: Vec<String>,
+    }
+
+    #[test]
+    fn test_try_deserialize_any() {
+        let payload = Payload {
+            id: "subject".into(),
+            sn: 3,
+            tags: vec!["a".into(), "b".into()],
+        };
+        let encoded = [
+            (serde_json::to_vec(&payload).unwrap(), MessageFormat::Json),
+            (serde_cbor::to_vec(&payload).unwrap(), MessageFormat::Cbor),
+            (rmp_serde::to_vec(&payload).unwrap(), MessageFormat::MsgPack),
+            (
+                rmp_serde::to_vec_named(&payload).unwrap(),
+                MessageFormat::MsgPack,
+            ),
+        ];
+        for (bytes, format) in encoded {
+            let (decoded, detected) = try_deserialize_any::<Payload>(&bytes).unwrap();
+            assert_eq!(payload, decoded);
+            assert_eq!(format, detected);
+        }
+        assert!(matches!(
+            try_deserialize_any::<Payload>(&[0xc1, 0xff, 0x00]),
+            Err(Error::UnknownFormat { .. })
+        ));
+    }
+}
@@ -1,14 +1,18 @@
 mod command;
 mod error;
+mod format;
 mod message_receiver;
 mod message_sender;
 mod message_task_manager;
+mod protocol_message;
 
 pub use command::*;
 use commons::identifier::KeyIdentifier;
+pub use format::*;
 pub use message_receiver::*;
 pub use message_sender::*;
 pub use message_task_manager::*;
+pub use protocol_message::*;
 
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
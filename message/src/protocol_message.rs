@@ -0,0 +1,92 @@
+use commons::models::{event::Event, event_request::EventRequest};
+use serde::{Deserialize, Serialize};
+
+use crate::{error::Error, TaskCommandContent};
+
+/// Messages exchanged between nodes. Any change to them that old nodes can not
+/// read must bump [ProtocolMessage::VERSION].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ProtocolMessage {
+    /// A request that has to reach the owner of its subject
+    RequestPropagation(EventRequest),
+    /// An event that has been validated and has to reach the witnesses of its subject
+    EventPropagation(Event),
+}
+
+impl ProtocolMessage {
+    pub const VERSION: u32 = 1;
+}
+
+/// [ProtocolMessage] tagged with the version of the protocol of the sender.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProtocolEnvelope {
+    pub version: u32,
+    pub message: ProtocolMessage,
+}
+
+impl TaskCommandContent for ProtocolEnvelope {}
+
+impl ProtocolEnvelope {
+    /// Wraps `message` with the version of this node.
+    pub fn new(message: ProtocolMessage) -> Self {
+        Self {
+            version: ProtocolMessage::VERSION,
+            message,
+        }
+    }
+
+    /// Returns the message if the version of the sender is at most `tolerance`
+    /// versions away from the version of this node.
+    pub fn open(self, tolerance: u32) -> Result<ProtocolMessage, Error> {
+        if self.version.abs_diff(ProtocolMessage::VERSION) > tolerance {
+            return Err(Error::IncompatibleProtocolVersion {
+                expected: ProtocolMessage::VERSION,
+                got: self.version,
+            });
+        }
+        Ok(self.message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use commons::models::event::Event;
+
+    use crate::error::Error;
+
+    use super::{ProtocolEnvelope, ProtocolMessage};
+
+    #[test]
+    fn test_protocol_envelope_round_trip() {
+        let messages = [
+            ProtocolMessage::RequestPropagation(Event::default().event_content.event_request),
+            ProtocolMessage::EventPropagation(Event::default()),
+        ];
+        for message in messages {
+            let envelope = ProtocolEnvelope::new(message.clone());
+            let bytes = rmp_serde::to_vec(&envelope).unwrap();
+            let decoded: ProtocolEnvelope = rmp_serde::from_slice(&bytes).unwrap();
+            assert_eq!(envelope, decoded);
+            assert_eq!(message, decoded.open(0).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_protocol_envelope_version() {
+        let envelope = |version: u32| ProtocolEnvelope {
+            version,
+            message: ProtocolMessage::EventPropagation(Event::default()),
+        };
+        let newer = ProtocolMessage::VERSION + 2;
+        assert!(envelope(newer).open(2).is_ok());
+        assert!(matches!(
+            envelope(newer).open(1),
+            Err(Error::IncompatibleProtocolVersion { expected, got })
+                if expected == ProtocolMessage::VERSION && got == newer
+        ));
+        assert!(matches!(
+            envelope(0).open(0),
+            Err(Error::IncompatibleProtocolVersion { .. })
+        ));
+    }
+}
@@ -138,4 +138,6 @@ pub enum RequestManagerError {
     },
     #[error("Database corrupted {0}")]
     DatabaseCorrupted(String),
+    #[error("Database error {0}")]
+    DatabaseError(String),
 }
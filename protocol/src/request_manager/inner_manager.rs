@@ -320,7 +320,9 @@ impl<
                         ));
                     }
                     // TODO: It should be managed in memory also the votes. Ask whether to implement it in this version
-                    self.db.set_request(&data.subject_id, request.clone());
+                    self.db
+                        .set_request(&data.subject_id, request.clone())
+                        .map_err(|error| RequestManagerError::DatabaseError(error.to_string()))?;
                     let (_, mut targets) = self
                         .governance_api
                         .check_quorum_request(request.clone(), HashSet::new())
@@ -768,7 +770,11 @@ impl<
             let Some((request, expected_sn)) = self.to_approval_request.get(&subject_id) else {
                 return Ok((RequestManagerResponse::VoteResolve(Err(ResponseError::VoteNotNeeded)), None));
             };
-            let None = self.db.get_event(subject_id, *expected_sn) else {
+            let event = self
+                .db
+                .get_event(subject_id, *expected_sn)
+                .map_err(|error| RequestManagerError::DatabaseError(error.to_string()))?;
+            let None = event else {
                 self.to_approval_request.remove(&subject_id);
                 self.request_table.remove(id);
                 return Ok((RequestManagerResponse::VoteResolve(Err(ResponseError::VoteNotNeeded)), None));